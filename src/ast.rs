@@ -48,6 +48,7 @@ struct NodeData {
 pub struct Ast {
     nodes: Vec<NodeData>,
     root: usize,
+    line_starts: Vec<usize>,
 }
 
 impl Ast {
@@ -102,7 +103,40 @@ impl Ast {
         let line_starts = line_starts(sql);
         let mut nodes: Vec<NodeData> = Vec::new();
         let root = build_googlesql(root, None, &line_starts, &mut nodes);
-        Self { nodes, root }
+        Self {
+            nodes,
+            root,
+            line_starts,
+        }
+    }
+
+    /// The 0-based position of `byte` in the source this tree was built from.
+    /// The column counts bytes; see [`crate::diagnostic::ColumnUnit`] for
+    /// editor units.
+    #[must_use]
+    pub fn point_at(&self, byte: usize) -> Point {
+        point_at(&self.line_starts, byte)
+    }
+
+    /// The innermost node whose byte range contains the 0-based `point`, or
+    /// `None` when the point lies outside the tree (or past the end of its
+    /// line). Columns are byte offsets within the line, as in [`Point`].
+    #[must_use]
+    pub fn node_at(&self, point: Point) -> Option<NodeRef<'_>> {
+        let line_start = *self.line_starts.get(point.row)?;
+        let byte = line_start.checked_add(point.column)?;
+        if let Some(&next_line) = self.line_starts.get(point.row.saturating_add(1))
+            && byte >= next_line
+        {
+            return None;
+        }
+
+        let contains = |node: &NodeRef<'_>| node.byte_range().contains(&byte);
+        let mut node = Some(self.root()).filter(contains)?;
+        while let Some(child) = node.children().into_iter().find(contains) {
+            node = child;
+        }
+        Some(node)
     }
 
     fn get(&self, idx: usize) -> Option<&NodeData> {
@@ -123,6 +157,14 @@ fn line_starts(sql: &str) -> Vec<usize> {
     starts
 }
 
+/// The 0-based position of `byte` in `sql`, for callers that have no tree for
+/// it (such as source the parser gave up on). Columns count bytes, as in
+/// [`Ast::point_at`].
+#[must_use]
+pub fn point_in(sql: &str, byte: usize) -> Point {
+    point_at(&line_starts(sql), byte)
+}
+
 /// The 0-based (row, column) of `byte`, where `column` is a byte offset within
 /// the line. `starts` must be the ascending line-start table from
 /// [`line_starts`].
@@ -276,6 +318,12 @@ impl<'a> NodeRef<'a> {
         self.data().map_or(Point { row: 0, column: 0 }, |d| d.start)
     }
 
+    /// The node's 0-based end position (exclusive, like [`Self::end_byte`]).
+    #[must_use]
+    pub fn end_position(&self) -> Point {
+        self.ast.point_at(self.end_byte())
+    }
+
     /// The node's source text, or `None` if the byte range does not map to a
     /// valid `&str` slice of `sql` (e.g. a tree/source mismatch that cuts a
     /// multibyte character). Replaces `Node::utf8_text`.
@@ -446,6 +494,22 @@ mod tests {
         assert_eq!(next.text(sql), Some("b"));
     }
 
+    #[test]
+    fn node_at_returns_the_innermost_node_under_the_point() {
+        // Row 1, column 16 (0-based) is inside the GROUP BY `col1`, whose
+        // innermost node is the identifier.
+        let sql = "SELECT col1\nFROM t GROUP BY col1";
+        let ast = parse_sql(sql);
+        let node = ast.node_at(Point { row: 1, column: 16 }).unwrap();
+        assert_eq!(node.kind(), "ASTIdentifier");
+        assert_eq!(node.start_byte(), 28);
+
+        // Before the first located node, and past the end of a line.
+        assert!(ast.node_at(Point { row: 0, column: 0 }).is_none());
+        assert!(ast.node_at(Point { row: 0, column: 40 }).is_none());
+        assert!(ast.node_at(Point { row: 5, column: 0 }).is_none());
+    }
+
     #[test]
     fn derive_span_ignores_locationless_children() {
         // The span of a rangeless node must cover only its children that carry
@@ -0,0 +1,241 @@
+//! On-disk cache of lint results, so unchanged files are not parsed again.
+//!
+//! Each entry is a JSON file named after the SHA-256 of the run's key and the
+//! file's content. The key covers everything else that decides the result:
+//! the bqvalid version, the effective rule set and rule options, and the
+//! user-defined functions the rules know about. Changing any of them (for
+//! example by editing the config file) changes every entry's name, so stale
+//! entries are never read. [`Cache::prune`] removes the entries a run did not
+//! use, so the directory holds one entry per file of the last run rather than
+//! growing with every edit. The path is not part of the name, so renamed or
+//! duplicated files hit the cache too.
+//!
+//! The cache is best effort: entries that cannot be read or parsed are misses,
+//! and failures to write one are only logged.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::diagnostic::Diagnostic;
+
+/// The default cache directory, relative to the working directory.
+pub const DEFAULT_DIR: &str = ".bqvalid_cache";
+
+/// Numbers the temporary files of this process's writes apart.
+static NEXT_TMP: AtomicUsize = AtomicUsize::new(0);
+
+/// What is stored for one file: its diagnostics (columns in bytes) and the
+/// error that stopped the parser, if any.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub diagnostics: Vec<Diagnostic>,
+    pub parse_error: Option<String>,
+}
+
+/// [`Entry`], borrowed for writing.
+#[derive(Serialize)]
+struct EntryRef<'a> {
+    diagnostics: &'a [Diagnostic],
+    parse_error: Option<&'a str>,
+}
+
+/// A cache directory plus the key of the current run.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+    key: [u8; 32],
+    /// The entries this run read or wrote, which [`Cache::prune`] keeps.
+    used: Arc<Mutex<HashSet<PathBuf>>>,
+}
+
+impl Cache {
+    /// A cache in `dir` for runs described by `key_parts` (the version, rule
+    /// set, options and so on). The directory is created on the first write.
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>, key_parts: &[&str]) -> Self {
+        let mut hasher = Sha256::new();
+        for part in key_parts {
+            // Length-prefix the parts so their boundaries are part of the key.
+            hasher.update(part.len().to_le_bytes());
+            hasher.update(part.as_bytes());
+        }
+        Self {
+            dir: dir.into(),
+            key: hasher.finalize().into(),
+            used: Arc::default(),
+        }
+    }
+
+    /// The cached entry for a file with contents `sql`, if there is one.
+    #[must_use]
+    pub fn get(&self, sql: &str) -> Option<Entry> {
+        let path = self.path_for(sql);
+        let text = fs::read_to_string(&path).ok()?;
+        let entry = serde_json::from_str(&text).ok()?;
+        self.mark_used(path);
+        Some(entry)
+    }
+
+    /// Store the result for a file with contents `sql`. The entry is written to
+    /// a temporary file and renamed into place, so concurrent runs never see a
+    /// partial one.
+    pub fn put(&self, sql: &str, diagnostics: &[Diagnostic], parse_error: Option<&str>) {
+        let entry = EntryRef {
+            diagnostics,
+            parse_error,
+        };
+        if let Err(e) = self.try_put(sql, &entry) {
+            debug!("cannot write cache entry in {}: {}", self.dir.display(), e);
+        }
+    }
+
+    fn try_put(&self, sql: &str, entry: &EntryRef<'_>) -> io::Result<()> {
+        if !self.dir.is_dir() {
+            fs::create_dir_all(&self.dir)?;
+            // Keep the cache out of version control and backups.
+            fs::write(self.dir.join(".gitignore"), "*\n")?;
+            fs::write(
+                self.dir.join("CACHEDIR.TAG"),
+                "Signature: 8a477f597d28d172789f06886806bc55\n",
+            )?;
+        }
+        let path = self.path_for(sql);
+        let tmp = path.with_extension(format!(
+            "{}-{}.tmp",
+            std::process::id(),
+            NEXT_TMP.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&tmp, serde_json::to_vec(entry).map_err(io::Error::other)?)?;
+        fs::rename(&tmp, &path)?;
+        self.mark_used(path);
+        Ok(())
+    }
+
+    /// Remove the entries this run neither read nor wrote: those of files that
+    /// changed, were deleted or were linted with another key. Only call it after
+    /// a run over the whole project (`--prune-cache`), since entries of files
+    /// left out of the run go too. Failures are only logged.
+    pub fn prune(&self) {
+        let used = self.used.lock().unwrap_or_else(PoisonError::into_inner);
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            if path.extension().is_some_and(|ext| ext == "json")
+                && !used.contains(&path)
+                && let Err(e) = fs::remove_file(&path)
+            {
+                debug!("cannot remove cache entry {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    fn mark_used(&self, path: PathBuf) {
+        self.used
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(path);
+    }
+
+    fn path_for(&self, sql: &str) -> PathBuf {
+        let digest = Sha256::new()
+            .chain_update(self.key)
+            .chain_update(sql.as_bytes())
+            .finalize();
+        let name: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+        self.dir.join(format!("{}.json", name))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, reason = "test code")]
+mod tests {
+    use super::*;
+    use crate::diagnostic::{Related, Severity};
+    use tempfile::tempdir;
+
+    fn put(cache: &Cache, sql: &str) {
+        let entry = entry();
+        cache.put(sql, &entry.diagnostics, entry.parse_error.as_deref());
+    }
+
+    fn entry() -> Entry {
+        Entry {
+            diagnostics: vec![
+                Diagnostic::new("r", Severity::Error, 1, 2, "m".to_string())
+                    .with_end((1, 5))
+                    .with_span(1..4)
+                    .with_related(Related {
+                        start: (2, 1),
+                        end: None,
+                        span: None,
+                        message: "here".to_string(),
+                    })
+                    .with_help("fix it"),
+            ],
+            parse_error: Some("Syntax error".to_string()),
+        }
+    }
+
+    #[test]
+    fn round_trips_entries_by_content() {
+        let dir = tempdir().unwrap();
+        let cache = Cache::new(dir.path().join("cache"), &["1.0.0", "rules"]);
+        assert_eq!(cache.get("SELECT 1"), None);
+
+        put(&cache, "SELECT 1");
+        assert_eq!(cache.get("SELECT 1"), Some(entry()));
+        assert_eq!(cache.get("SELECT 2"), None);
+        assert!(dir.path().join("cache/CACHEDIR.TAG").is_file());
+    }
+
+    #[test]
+    fn a_different_key_misses() {
+        let dir = tempdir().unwrap();
+        put(&Cache::new(dir.path(), &["1.0.0", "rules"]), "SELECT 1");
+        assert_eq!(
+            Cache::new(dir.path(), &["1.0.1", "rules"]).get("SELECT 1"),
+            None
+        );
+        // Part boundaries count: ["ab", "c"] is not ["a", "bc"].
+        put(&Cache::new(dir.path(), &["ab", "c"]), "SELECT 1");
+        assert_eq!(Cache::new(dir.path(), &["a", "bc"]).get("SELECT 1"), None);
+    }
+
+    #[test]
+    fn prune_removes_entries_the_run_did_not_use() {
+        let dir = tempdir().unwrap();
+        let dir = dir.path().join("cache");
+        put(&Cache::new(&dir, &["k"]), "SELECT 1");
+        put(&Cache::new(&dir, &["k"]), "SELECT 2");
+
+        let cache = Cache::new(&dir, &["k"]);
+        assert!(cache.get("SELECT 1").is_some());
+        put(&cache, "SELECT 3");
+        cache.prune();
+        assert!(cache.get("SELECT 1").is_some());
+        assert!(cache.get("SELECT 3").is_some());
+        assert_eq!(cache.get("SELECT 2"), None);
+        assert!(
+            dir.join("CACHEDIR.TAG").is_file(),
+            "only entries are pruned"
+        );
+    }
+
+    #[test]
+    fn unreadable_entries_are_misses() {
+        let dir = tempdir().unwrap();
+        let cache = Cache::new(dir.path(), &["k"]);
+        put(&cache, "SELECT 1");
+        fs::write(cache.path_for("SELECT 1"), "not json").unwrap();
+        assert_eq!(cache.get("SELECT 1"), None);
+    }
+}
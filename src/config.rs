@@ -2,7 +2,14 @@ use std::collections::HashSet;
 use std::fmt;
 use std::path::{Path, PathBuf};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use crate::diagnostic::Severity;
+use crate::rules::apply_function_to_partition_column::ApplyFunctionToPartitionColumnOptions;
+use crate::rules::exact_aggregation::ExactAggregationOptions;
+use crate::rules::invalid_group_by::InvalidGroupByOptions;
+use crate::rules::nondeterministic_function::NondeterministicFunctionOptions;
+use crate::schema::SchemaConfig;
 
 /// Config file looked up in the current directory when `--config` is not given.
 pub const DEFAULT_CONFIG_FILE: &str = "bqvalid.toml";
@@ -11,12 +18,62 @@ pub const DEFAULT_CONFIG_FILE: &str = "bqvalid.toml";
 ///
 /// Unknown keys are rejected so typos surface as errors rather than being
 /// silently ignored.
-#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
     /// Rule IDs whose diagnostics are suppressed.
     #[serde(default)]
     pub ignore: Vec<String>,
+    /// Project-specific rules defined by a structural selector (see
+    /// [`crate::query`]), declared as `[[custom_rules]]` tables.
+    #[serde(default)]
+    pub custom_rules: Vec<CustomRuleConfig>,
+    /// Per-rule options, declared as `[rules.<rule_id>]` tables.
+    #[serde(default)]
+    pub rules: RuleOptions,
+    /// Directory of `.sql` files whose `CREATE FUNCTION` statements describe
+    /// the project's shared UDFs (see [`crate::udf`]). A relative path is
+    /// resolved against the config file's directory by [`Config::load`].
+    #[serde(default)]
+    pub udf_dir: Option<PathBuf>,
+    /// The columns of the project's tables, declared as `[schema."<table>"]`
+    /// tables (see [`crate::schema`]).
+    #[serde(default)]
+    pub schema: SchemaConfig,
+}
+
+/// Options for the configurable rules, one optional table per rule.
+///
+/// Each rule declares the schema of its own table; unknown rule ids and unknown
+/// option keys are both rejected, like the top-level keys.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleOptions {
+    #[serde(default)]
+    pub apply_function_to_partition_column: ApplyFunctionToPartitionColumnOptions,
+    #[serde(default)]
+    pub exact_aggregation: ExactAggregationOptions,
+    #[serde(default)]
+    pub invalid_group_by: InvalidGroupByOptions,
+    /// Also read as `[rules.use_current_date]`, the rule's former id.
+    #[serde(default, alias = "use_current_date")]
+    pub nondeterministic_function: NondeterministicFunctionOptions,
+}
+
+/// One `[[custom_rules]]` entry: every node matching `selector` is reported
+/// with `message` under the rule id `id`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomRuleConfig {
+    pub id: String,
+    pub selector: String,
+    pub message: String,
+    #[serde(default = "default_custom_severity")]
+    pub severity: Severity,
+}
+
+const fn default_custom_severity() -> Severity {
+    Severity::Warning
 }
 
 /// Failure while loading a config file: either the file could not be read or
@@ -51,10 +108,15 @@ impl Config {
         toml::from_str(text)
     }
 
-    /// Read and parse the config file at `path`.
+    /// Read and parse the config file at `path`. Relative paths in the file
+    /// are resolved against its directory.
     pub fn load(path: &Path) -> Result<Self, ConfigError> {
         let text = std::fs::read_to_string(path).map_err(ConfigError::Read)?;
-        Self::from_toml(&text).map_err(ConfigError::Parse)
+        let mut config = Self::from_toml(&text).map_err(ConfigError::Parse)?;
+        if let (Some(dir), Some(base)) = (config.udf_dir.as_mut(), path.parent()) {
+            *dir = base.join(&*dir);
+        }
+        Ok(config)
     }
 }
 
@@ -119,6 +181,9 @@ pub fn unknown_ignore_ids(ignore: &[String], known: &HashSet<&str>) -> Vec<Strin
 )]
 mod tests {
     use super::*;
+    use crate::diagnostic::FunctionLevel;
+    use crate::schema::{Column, ColumnMode};
+    use crate::types::SqlType;
     use std::fs;
     use tempfile::tempdir;
 
@@ -149,6 +214,153 @@ mod tests {
         assert!(err.is_err(), "unknown key should be rejected");
     }
 
+    #[test]
+    fn from_toml_parses_per_rule_options() {
+        let cfg = Config::from_toml(
+            r#"
+[rules.apply_function_to_partition_column]
+extra_functions = ["parse_date"]
+
+[rules.exact_aggregation]
+level = "warning"
+large_tables = ["analytics.events"]
+
+[rules.invalid_group_by]
+extra_aggregates = ["HLL_COUNT.MERGE", "my_udf_agg"]
+
+[rules.nondeterministic_function]
+functions = { RAND = "off", CURRENT_DATE = "error" }
+allow_in_written_values = false
+"#,
+        )
+        .expect("valid toml");
+        assert_eq!(
+            cfg.rules.apply_function_to_partition_column.extra_functions,
+            vec!["parse_date".to_string()]
+        );
+        assert!(
+            cfg.rules
+                .apply_function_to_partition_column
+                .extra_cast_types
+                .is_empty()
+        );
+        assert_eq!(cfg.rules.exact_aggregation.level, FunctionLevel::Warning);
+        assert_eq!(
+            cfg.rules.exact_aggregation.large_tables,
+            vec!["analytics.events".to_string()]
+        );
+        assert_eq!(cfg.rules.invalid_group_by.extra_aggregates.len(), 2);
+        let nondeterministic = &cfg.rules.nondeterministic_function;
+        assert_eq!(
+            nondeterministic.functions.get("RAND"),
+            Some(&FunctionLevel::Off)
+        );
+        assert_eq!(
+            nondeterministic.functions.get("CURRENT_DATE"),
+            Some(&FunctionLevel::Error)
+        );
+        assert!(!nondeterministic.allow_in_written_values);
+    }
+
+    #[test]
+    fn from_toml_parses_table_schemas() {
+        let cfg = Config::from_toml(
+            r#"
+[schema."analytics.events"]
+created_at = { type = "TIMESTAMP", mode = "REQUIRED" }
+event_date = { type = "date" }
+"#,
+        )
+        .expect("valid toml");
+        let events = &cfg.schema["analytics.events"];
+        assert_eq!(
+            events["created_at"],
+            Column {
+                ty: SqlType::Timestamp,
+                mode: ColumnMode::Required
+            }
+        );
+        assert_eq!(events["event_date"].mode, ColumnMode::Nullable);
+    }
+
+    #[test]
+    fn exact_aggregation_is_off_unless_configured() {
+        let cfg = Config::from_toml("").expect("empty is valid");
+        assert_eq!(cfg.rules.exact_aggregation.level, FunctionLevel::Off);
+    }
+
+    #[test]
+    fn from_toml_rejects_unknown_column_types_and_modes() {
+        let err = Config::from_toml("[schema.t]\nx = { type = \"INSTANT\" }");
+        assert!(err.is_err(), "unknown type names should be rejected");
+        let err = Config::from_toml("[schema.t]\nx = { type = \"DATE\", mode = \"OPTIONAL\" }");
+        assert!(err.is_err(), "unknown modes should be rejected");
+    }
+
+    #[test]
+    fn from_toml_reads_options_under_a_former_rule_id() {
+        let cfg =
+            Config::from_toml("[rules.use_current_date]\nalso_flag = [\"CURRENT_TIMESTAMP\"]")
+                .expect("valid toml");
+        let nondeterministic = &cfg.rules.nondeterministic_function;
+        assert_eq!(
+            nondeterministic.also_flag,
+            vec!["CURRENT_TIMESTAMP".to_string()]
+        );
+        assert!(
+            nondeterministic.allow_in_written_values,
+            "defaults still apply"
+        );
+    }
+
+    #[test]
+    fn from_toml_rejects_unknown_rule_options() {
+        // A typo'd option key and a rule without options are both errors.
+        assert!(Config::from_toml("[rules.invalid_group_by]\nextra_aggregate = [\"x\"]").is_err());
+        assert!(Config::from_toml("[rules.unused_column_in_cte]\nenabled = true").is_err());
+    }
+
+    #[test]
+    fn from_toml_parses_custom_rules() {
+        let cfg = Config::from_toml(
+            r#"
+[[custom_rules]]
+id = "no_regexp_in_where"
+selector = "ASTWhereClause ASTFunctionCall[name=REGEXP_CONTAINS]"
+message = "Avoid REGEXP_CONTAINS in filters"
+
+[[custom_rules]]
+id = "no_select_star"
+selector = "ASTStar"
+message = "List columns explicitly"
+severity = "error"
+"#,
+        )
+        .expect("valid toml");
+        assert_eq!(cfg.custom_rules.len(), 2);
+        assert_eq!(cfg.custom_rules[0].id, "no_regexp_in_where");
+        assert_eq!(
+            cfg.custom_rules[0].severity,
+            Severity::Warning,
+            "severity defaults to warning"
+        );
+        assert_eq!(cfg.custom_rules[1].severity, Severity::Error);
+    }
+
+    #[test]
+    fn from_toml_rejects_unknown_custom_rule_keys() {
+        let err = Config::from_toml(
+            r#"
+[[custom_rules]]
+id = "x"
+selector = "ASTStar"
+message = "m"
+selecter = "typo"
+"#,
+        );
+        assert!(err.is_err(), "unknown custom rule key should be rejected");
+    }
+
     #[test]
     fn load_reads_and_parses_a_file() {
         let dir = tempdir().unwrap();
@@ -159,6 +371,15 @@ mod tests {
         assert_eq!(cfg.ignore, vec!["unnecessary_order_by".to_string()]);
     }
 
+    #[test]
+    fn load_resolves_udf_dir_against_the_config_directory() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("bqvalid.toml");
+        fs::write(&path, "udf_dir = \"sql/udfs\"").unwrap();
+        let cfg = Config::load(&path).expect("loads");
+        assert_eq!(cfg.udf_dir, Some(dir.path().join("sql/udfs")));
+    }
+
     #[test]
     fn load_missing_file_is_a_read_error() {
         let dir = tempdir().unwrap();
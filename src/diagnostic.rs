@@ -1,47 +1,246 @@
+use std::borrow::Cow;
 use std::fmt::Display;
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast::NodeRef;
 
 /// Severity of a diagnostic.
 ///
 /// `Error` marks a query that BigQuery would reject at runtime; `Warning` marks
-/// a performance or maintainability problem that still runs. The human-readable
-/// output does not print the severity yet — it is stored so machine-readable
-/// formats and per-rule control can use it.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// a performance or maintainability problem that still runs. The `plain`
+/// output does not print the severity; the `pretty` and machine-readable
+/// formats do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Severity {
     Warning,
     Error,
 }
 
+impl Severity {
+    /// The lowercase name used in configs and machine-readable output; SARIF
+    /// levels share the same vocabulary.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+}
+
+/// How a configurable finding is reported: not at all, or with a severity.
+/// Rule options set it for a whole rule (`level = "off"`) or per function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FunctionLevel {
+    Off,
+    Warning,
+    Error,
+}
+
+impl FunctionLevel {
+    /// The severity findings are reported at, `None` when they are not.
+    #[must_use]
+    pub const fn severity(self) -> Option<Severity> {
+        match self {
+            Self::Off => None,
+            Self::Warning => Some(Severity::Warning),
+            Self::Error => Some(Severity::Error),
+        }
+    }
+}
+
+/// What a column number counts within its line.
+///
+/// Positions are computed in bytes, which is what the parser reports and what
+/// byte-oriented tools expect. Editors count UTF-16 code units (LSP, VS Code)
+/// or characters, which differ from bytes on lines with multibyte identifiers,
+/// literals or comments; [`Diagnostic::convert_columns`] re-expresses the
+/// columns for them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColumnUnit {
+    /// UTF-8 bytes. Default.
+    #[default]
+    Byte,
+    /// UTF-16 code units.
+    Utf16,
+    /// Unicode scalar values (characters).
+    Char,
+}
+
+impl ColumnUnit {
+    /// Convert the 1-based byte column `col` within `line` to this unit. A
+    /// column inside a multibyte character counts that whole character, and
+    /// one past the end of the line (an exclusive range end) stays one past it.
+    #[must_use]
+    pub fn convert(self, line: &str, col: usize) -> usize {
+        let byte = col.saturating_sub(1);
+        let width = |c: char| match self {
+            Self::Byte => c.len_utf8(),
+            Self::Utf16 => c.len_utf16(),
+            Self::Char => 1,
+        };
+        let units = line
+            .char_indices()
+            .take_while(|&(i, _)| i < byte)
+            .fold(0_usize, |units, (_, c)| units.saturating_add(width(c)));
+        units
+            .saturating_add(byte.saturating_sub(line.len()))
+            .saturating_add(1)
+    }
+}
+
+/// A replacement a rule proposes for part of the source: `text` replaces
+/// everything from `start` up to (not including) `end`.
+///
+/// Positions are 1-based `(row, col)` pairs, like [`Diagnostic`]'s.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Suggestion {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub text: String,
+}
+
+/// A secondary place in the source that explains a diagnostic, such as the
+/// `GROUP BY` clause a column is missing from. Positions follow the same
+/// conventions as [`Diagnostic`]'s.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Related {
+    pub start: (usize, usize),
+    pub end: Option<(usize, usize)>,
+    pub span: Option<Range<usize>>,
+    pub message: String,
+}
+
+impl Related {
+    /// A related location covering `node`.
+    pub fn for_node(node: &NodeRef<'_>, message: impl Into<String>) -> Self {
+        Self {
+            start: one_based(node.start_position()),
+            end: Some(one_based(node.end_position())),
+            span: Some(node.byte_range()),
+            message: message.into(),
+        }
+    }
+}
+
+const fn one_based(point: crate::ast::Point) -> (usize, usize) {
+    (point.row.saturating_add(1), point.column.saturating_add(1))
+}
+
 /// Represents a diagnostic, such as a full scan error.
 ///
-/// rows and columns are 1-based.
+/// rows and columns are 1-based, and columns count bytes until
+/// [`Self::convert_columns`] says otherwise. The end position, when the rule
+/// knows it, is exclusive. The byte span is the range of the source the
+/// diagnostic covers, unaffected by the column unit.
+///
+/// Besides its own location a diagnostic can point at [`Related`] locations
+/// and carry `notes` (background) and a `help` line (what to do about it).
+///
+/// Diagnostics serialize with serde so the result cache can store them.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Diagnostic {
-    rule_id: &'static str,
+    rule_id: Cow<'static, str>,
     severity: Severity,
     row: usize,
     col: usize,
     message: String,
+    end: Option<(usize, usize)>,
+    span: Option<Range<usize>>,
+    suggestions: Vec<Suggestion>,
+    related: Vec<Related>,
+    notes: Vec<String>,
+    help: Option<String>,
 }
 
 impl Diagnostic {
-    pub const fn new(
-        rule_id: &'static str,
+    /// Built-in rules pass their `&'static str` id; config-defined rules pass
+    /// an owned `String`.
+    pub fn new(
+        rule_id: impl Into<Cow<'static, str>>,
         severity: Severity,
         row: usize,
         col: usize,
         message: String,
     ) -> Self {
         Self {
-            rule_id,
+            rule_id: rule_id.into(),
             severity,
             row,
             col,
             message,
+            end: None,
+            span: None,
+            suggestions: Vec::new(),
+            related: Vec::new(),
+            notes: Vec::new(),
+            help: None,
         }
     }
 
-    pub const fn rule_id(&self) -> &'static str {
-        self.rule_id
+    /// A diagnostic covering `node`: it starts and ends where the node does
+    /// and carries the node's byte span.
+    pub fn for_node(
+        rule_id: impl Into<Cow<'static, str>>,
+        severity: Severity,
+        node: &NodeRef<'_>,
+        message: String,
+    ) -> Self {
+        let (row, col) = one_based(node.start_position());
+        Self::new(rule_id, severity, row, col, message)
+            .with_end(one_based(node.end_position()))
+            .with_span(node.byte_range())
+    }
+
+    /// Set the exclusive 1-based `(row, col)` end of the reported range,
+    /// typically the end of the offending node.
+    #[must_use]
+    pub const fn with_end(mut self, end: (usize, usize)) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    /// Set the byte range of the source the diagnostic covers.
+    #[must_use]
+    pub fn with_span(mut self, span: Range<usize>) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// Attach a replacement that fixes the problem.
+    #[must_use]
+    pub fn with_suggestion(mut self, suggestion: Suggestion) -> Self {
+        self.suggestions.push(suggestion);
+        self
+    }
+
+    /// Point at a secondary location that explains the problem.
+    #[must_use]
+    pub fn with_related(mut self, related: Related) -> Self {
+        self.related.push(related);
+        self
+    }
+
+    /// Add a note giving background on the problem.
+    #[must_use]
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Say how to fix the problem.
+    #[must_use]
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn rule_id(&self) -> &str {
+        &self.rule_id
     }
 
     pub const fn severity(&self) -> Severity {
@@ -59,6 +258,58 @@ impl Diagnostic {
     pub const fn col(&self) -> usize {
         self.col
     }
+
+    pub const fn end(&self) -> Option<(usize, usize)> {
+        self.end
+    }
+
+    pub fn span(&self) -> Option<Range<usize>> {
+        self.span.clone()
+    }
+
+    pub fn suggestions(&self) -> &[Suggestion] {
+        &self.suggestions
+    }
+
+    pub fn related(&self) -> &[Related] {
+        &self.related
+    }
+
+    pub fn notes(&self) -> &[String] {
+        &self.notes
+    }
+
+    pub fn help(&self) -> Option<&str> {
+        self.help.as_deref()
+    }
+
+    /// Re-express the byte columns of the start, end, suggestions and related
+    /// locations in
+    /// `unit`. `lines` is the source split on `\n`, which the rows index
+    /// (1-based). Call it once: the columns are assumed to be bytes.
+    pub fn convert_columns(&mut self, lines: &[&str], unit: ColumnUnit) {
+        if unit == ColumnUnit::Byte {
+            return;
+        }
+        let convert = |(row, col): (usize, usize)| {
+            let line = row
+                .checked_sub(1)
+                .and_then(|i| lines.get(i))
+                .copied()
+                .unwrap_or_default();
+            (row, unit.convert(line, col))
+        };
+        (self.row, self.col) = convert((self.row, self.col));
+        self.end = self.end.map(convert);
+        for suggestion in &mut self.suggestions {
+            suggestion.start = convert(suggestion.start);
+            suggestion.end = convert(suggestion.end);
+        }
+        for related in &mut self.related {
+            related.start = convert(related.start);
+            related.end = related.end.map(convert);
+        }
+    }
 }
 
 impl Display for Diagnostic {
@@ -68,6 +319,12 @@ impl Display for Diagnostic {
 }
 
 #[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::indexing_slicing,
+    clippy::arithmetic_side_effects,
+    reason = "test code"
+)]
 mod tests {
     use super::*;
 
@@ -81,6 +338,78 @@ mod tests {
         assert_eq!(d.message(), "boom");
     }
 
+    #[test]
+    fn end_and_suggestions_are_optional() {
+        let d = Diagnostic::new("some_rule", Severity::Warning, 3, 5, "boom".to_string());
+        assert_eq!(d.end(), None);
+        assert!(d.suggestions().is_empty());
+
+        let fix = Suggestion {
+            start: (3, 5),
+            end: (3, 9),
+            text: "bang".to_string(),
+        };
+        let d = d.with_end((3, 9)).with_suggestion(fix.clone());
+        assert_eq!(d.end(), Some((3, 9)));
+        assert_eq!(d.suggestions(), [fix]);
+    }
+
+    #[test]
+    fn column_units_count_bytes_utf16_units_or_characters() {
+        // `é` is 2 bytes / 1 UTF-16 unit, `😀` is 4 bytes / 2 UTF-16 units.
+        let line = "é😀x";
+        let x = line.find('x').unwrap() + 1;
+        assert_eq!(ColumnUnit::Byte.convert(line, x), 7);
+        assert_eq!(ColumnUnit::Utf16.convert(line, x), 4);
+        assert_eq!(ColumnUnit::Char.convert(line, x), 3);
+        // One past the end stays one past the end.
+        assert_eq!(ColumnUnit::Char.convert(line, line.len() + 1), 4);
+        assert_eq!(ColumnUnit::Char.convert(line, 1), 1);
+    }
+
+    #[test]
+    fn convert_columns_rewrites_start_end_and_suggestions_but_not_the_span() {
+        let source = "SELECT 1\nSELECT 'é', x";
+        let lines: Vec<&str> = source.split('\n').collect();
+        let x = source.rfind('x').unwrap();
+        let mut d = Diagnostic::new("r", Severity::Warning, 2, 14, "m".to_string())
+            .with_end((2, 15))
+            .with_span(x..x + 1)
+            .with_suggestion(Suggestion {
+                start: (2, 14),
+                end: (2, 15),
+                text: "y".to_string(),
+            });
+        d.convert_columns(&lines, ColumnUnit::Char);
+        assert_eq!((d.row(), d.col()), (2, 13));
+        assert_eq!(d.end(), Some((2, 14)));
+        assert_eq!(d.span(), Some(x..x + 1));
+        assert_eq!(d.suggestions()[0].start, (2, 13));
+        assert_eq!(d.suggestions()[0].end, (2, 14));
+    }
+
+    #[test]
+    fn related_locations_notes_and_help_accumulate() {
+        let related = Related {
+            start: (1, 10),
+            end: Some((1, 12)),
+            span: Some(9..11),
+            message: "here".to_string(),
+        };
+        let d = Diagnostic::new("r", Severity::Warning, 1, 1, "m".to_string())
+            .with_related(related.clone())
+            .with_note("first")
+            .with_note("second")
+            .with_help("do this");
+        assert_eq!(d.related(), [related]);
+        assert_eq!(d.notes(), ["first", "second"]);
+        assert_eq!(d.help(), Some("do this"));
+
+        let bare = Diagnostic::new("r", Severity::Warning, 1, 1, "m".to_string());
+        assert!(bare.related().is_empty() && bare.notes().is_empty());
+        assert_eq!(bare.help(), None);
+    }
+
     #[test]
     fn severity_is_preserved() {
         let d = Diagnostic::new("some_rule", Severity::Error, 1, 1, "bad".to_string());
@@ -0,0 +1,241 @@
+//! Printing an [`Ast`] for rule authors (`bqvalid ast`).
+//!
+//! Rules match on ZetaSQL kind names such as `ASTAliasedQuery`, so writing one
+//! starts with seeing the tree a query parses to. Every format shows, per node,
+//! its kind, byte range, 1-based start/end position and a snippet of its source
+//! text; they differ only in layout:
+//!
+//! - `text`: one node per line, indented two spaces per level.
+//! - `json`: an array with one nested object per tree, for tooling.
+//! - `sexp`: an indented S-expression, handy for pasting into tests.
+
+use std::io::{self, Write};
+
+use serde_json::{Value, json};
+
+use crate::ast::{Ast, NodeRef, Point};
+
+/// Layout selected via `bqvalid ast --format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DumpFormat {
+    /// One indented line per node. Default.
+    Text,
+    /// A JSON array of nested node objects.
+    Json,
+    /// An indented S-expression.
+    Sexp,
+}
+
+/// Snippets longer than this many characters are cut and end in `...`.
+const SNIPPET_CHARS: usize = 40;
+
+/// Write the subtrees rooted at `nodes` (one per statement, or the single node
+/// picked with `--at`) to `out` in `format`.
+///
+/// # Errors
+/// Returns any error from writing to `out`.
+pub fn write_trees<W: Write>(
+    out: &mut W,
+    nodes: &[NodeRef<'_>],
+    sql: &str,
+    format: DumpFormat,
+) -> io::Result<()> {
+    match format {
+        DumpFormat::Text => nodes
+            .iter()
+            .try_for_each(|node| write_text(out, node, sql, 0)),
+        DumpFormat::Sexp => nodes.iter().try_for_each(|node| {
+            write_sexp(out, node, sql, 0)?;
+            writeln!(out)
+        }),
+        DumpFormat::Json => {
+            let trees: Vec<Value> = nodes.iter().map(|node| to_json(node, sql)).collect();
+            serde_json::to_writer_pretty(&mut *out, &trees).map_err(io::Error::other)?;
+            writeln!(out)
+        }
+    }
+}
+
+/// The innermost node at the 1-based `line`:`column` across the statements of
+/// a script, where `column` counts bytes like the diagnostics do.
+#[must_use]
+pub fn node_at(asts: &[Ast], line: usize, column: usize) -> Option<NodeRef<'_>> {
+    let point = Point {
+        row: line.checked_sub(1)?,
+        column: column.checked_sub(1)?,
+    };
+    asts.iter().find_map(|ast| ast.node_at(point))
+}
+
+fn write_text<W: Write>(
+    out: &mut W,
+    node: &NodeRef<'_>,
+    sql: &str,
+    depth: usize,
+) -> io::Result<()> {
+    writeln!(
+        out,
+        "{:indent$}{}",
+        "",
+        describe(node, sql),
+        indent = depth.saturating_mul(2)
+    )?;
+    node.children()
+        .iter()
+        .try_for_each(|child| write_text(out, child, sql, depth.saturating_add(1)))
+}
+
+fn write_sexp<W: Write>(
+    out: &mut W,
+    node: &NodeRef<'_>,
+    sql: &str,
+    depth: usize,
+) -> io::Result<()> {
+    write!(
+        out,
+        "{:indent$}({}",
+        "",
+        describe(node, sql),
+        indent = depth.saturating_mul(2)
+    )?;
+    for child in node.children() {
+        writeln!(out)?;
+        write_sexp(out, &child, sql, depth.saturating_add(1))?;
+    }
+    write!(out, ")")
+}
+
+/// `kind start..end line:col-line:col "snippet"`, shared by text and sexp.
+fn describe(node: &NodeRef<'_>, sql: &str) -> String {
+    let (start, end) = one_based_span(node);
+    format!(
+        "{} {}..{} {}:{}-{}:{} {:?}",
+        node.kind(),
+        node.start_byte(),
+        node.end_byte(),
+        start.row,
+        start.column,
+        end.row,
+        end.column,
+        snippet(node.text(sql).unwrap_or_default()),
+    )
+}
+
+fn to_json(node: &NodeRef<'_>, sql: &str) -> Value {
+    let (start, end) = one_based_span(node);
+    let children: Vec<Value> = node
+        .children()
+        .iter()
+        .map(|child| to_json(child, sql))
+        .collect();
+    json!({
+        "kind": node.kind(),
+        "range": { "start": node.start_byte(), "end": node.end_byte() },
+        "start": { "line": start.row, "column": start.column },
+        "end": { "line": end.row, "column": end.column },
+        "text": snippet(node.text(sql).unwrap_or_default()),
+        "children": children,
+    })
+}
+
+/// The node's start and (exclusive) end position, both 1-based.
+fn one_based_span(node: &NodeRef<'_>) -> (Point, Point) {
+    let one_based = |point: Point| Point {
+        row: point.row.saturating_add(1),
+        column: point.column.saturating_add(1),
+    };
+    (
+        one_based(node.start_position()),
+        one_based(node.end_position()),
+    )
+}
+
+/// The first line of `text`, cut to [`SNIPPET_CHARS`] characters; `...` marks
+/// anything dropped.
+fn snippet(text: &str) -> String {
+    let first = text.lines().next().unwrap_or_default();
+    let mut snippet: String = first.chars().take(SNIPPET_CHARS).collect();
+    if snippet.len() < text.len() {
+        snippet.push_str("...");
+    }
+    snippet
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::indexing_slicing,
+    clippy::arithmetic_side_effects,
+    reason = "test code"
+)]
+mod tests {
+    use super::*;
+    use crate::rules::helpers::parse_sql;
+
+    fn dump(nodes: &[NodeRef<'_>], sql: &str, format: DumpFormat) -> String {
+        let mut out = Vec::new();
+        write_trees(&mut out, nodes, sql, format).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn text_indents_children_under_their_parent() {
+        let sql = "select a from t";
+        let ast = parse_sql(sql);
+        let text = dump(&[ast.root()], sql, DumpFormat::Text);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], r#"ASTQueryStatement 7..15 1:8-1:16 "a from t""#);
+        assert!(
+            lines.contains(&r#"            ASTIdentifier 7..8 1:8-1:9 "a""#),
+            "dump was:\n{text}"
+        );
+    }
+
+    #[test]
+    fn sexp_closes_each_node_after_its_children() {
+        let sql = "select a from t";
+        let ast = parse_sql(sql);
+        let ident = ast
+            .pre_order()
+            .into_iter()
+            .find(|n| n.kind() == "ASTPathExpression" && n.text(sql) == Some("a"))
+            .unwrap();
+        assert_eq!(
+            dump(&[ident], sql, DumpFormat::Sexp),
+            "(ASTPathExpression 7..8 1:8-1:9 \"a\"\n  (ASTIdentifier 7..8 1:8-1:9 \"a\"))\n"
+        );
+    }
+
+    #[test]
+    fn json_nests_children_and_reports_positions() {
+        let sql = "select a\nfrom t";
+        let ast = parse_sql(sql);
+        let doc: Value = serde_json::from_str(&dump(&[ast.root()], sql, DumpFormat::Json)).unwrap();
+        let root = &doc[0];
+        assert_eq!(root["kind"], "ASTQueryStatement");
+        assert_eq!(root["range"], json!({ "start": 7, "end": 15 }));
+        assert_eq!(root["start"], json!({ "line": 1, "column": 8 }));
+        assert_eq!(root["end"], json!({ "line": 2, "column": 7 }));
+        assert_eq!(root["text"], "a...");
+        assert_eq!(root["children"][0]["kind"], "ASTQuery");
+    }
+
+    #[test]
+    fn node_at_takes_one_based_positions() {
+        let sql = "select a\nfrom t";
+        let asts = vec![parse_sql(sql)];
+        let node = node_at(&asts, 2, 6).unwrap();
+        assert_eq!(node.kind(), "ASTIdentifier");
+        assert_eq!(node.text(sql), Some("t"));
+        assert!(node_at(&asts, 0, 1).is_none());
+    }
+
+    #[test]
+    fn snippet_keeps_the_first_line_and_truncates() {
+        assert_eq!(snippet("count(a)"), "count(a)");
+        assert_eq!(snippet("count(\n  a\n)"), "count(...");
+        assert_eq!(snippet(&"x".repeat(50)), format!("{}...", "x".repeat(40)));
+    }
+}
@@ -0,0 +1,208 @@
+//! Rendering rule metadata for `bqvalid rules` and `bqvalid explain`.
+
+use std::io::{self, Write};
+
+use serde_json::{Value, json};
+
+use crate::rules::Rule;
+
+/// Layout selected via `bqvalid rules --format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ListFormat {
+    /// One aligned `id  severity  category  name` line per rule, with `off` as
+    /// the severity of opt-in rules. Default.
+    Text,
+    /// A JSON document with a `rules` array carrying every metadata field.
+    Json,
+}
+
+/// Write the list of `rules` in `format`.
+///
+/// # Errors
+/// Returns any error from writing to `out`.
+pub fn write_rule_list<W: Write>(
+    out: &mut W,
+    rules: &[Box<dyn Rule>],
+    format: ListFormat,
+) -> io::Result<()> {
+    match format {
+        ListFormat::Text => {
+            let width = rules.iter().map(|rule| rule.id().len()).max().unwrap_or(0);
+            for rule in rules {
+                let meta = rule.metadata();
+                writeln!(
+                    out,
+                    "{:width$}  {:7}  {:15}  {}",
+                    rule.id(),
+                    if meta.default_enabled {
+                        meta.default_severity.as_str()
+                    } else {
+                        "off"
+                    },
+                    meta.category.as_str(),
+                    meta.name,
+                )?;
+            }
+            Ok(())
+        }
+        ListFormat::Json => {
+            let entries: Vec<Value> = rules.iter().map(|rule| rule_json(rule.as_ref())).collect();
+            serde_json::to_writer_pretty(&mut *out, &json!({ "rules": entries }))
+                .map_err(io::Error::other)?;
+            writeln!(out)
+        }
+    }
+}
+
+/// Write the long help for `rule`: its metadata, description and examples.
+///
+/// # Errors
+/// Returns any error from writing to `out`.
+pub fn write_explanation<W: Write>(out: &mut W, rule: &dyn Rule) -> io::Result<()> {
+    let meta = rule.metadata();
+    writeln!(out, "{}: {}", rule.id(), meta.name)?;
+    writeln!(out)?;
+    if meta.default_enabled {
+        writeln!(out, "Severity: {}", meta.default_severity.as_str())?;
+    } else {
+        writeln!(
+            out,
+            "Severity: off by default, {} when enabled",
+            meta.default_severity.as_str()
+        )?;
+    }
+    writeln!(out, "Category: {}", meta.category.as_str())?;
+    writeln!(out, "Fixable: {}", if meta.fixable { "yes" } else { "no" })?;
+    writeln!(out)?;
+    writeln!(out, "{}", meta.summary)?;
+    writeln!(out)?;
+    writeln!(out, "{}", meta.description)?;
+    for (label, example) in [("Bad", meta.bad_example), ("Good", meta.good_example)] {
+        if let Some(example) = example {
+            writeln!(out)?;
+            writeln!(out, "{}:", label)?;
+            for line in example.lines() {
+                writeln!(out, "    {}", line)?;
+            }
+        }
+    }
+    if let Some(url) = meta.doc_url {
+        writeln!(out)?;
+        writeln!(out, "See {}", url)?;
+    }
+    Ok(())
+}
+
+fn rule_json(rule: &dyn Rule) -> Value {
+    let meta = rule.metadata();
+    json!({
+        "id": rule.id(),
+        "name": meta.name,
+        "default_severity": meta.default_severity.as_str(),
+        "default_enabled": meta.default_enabled,
+        "category": meta.category.as_str(),
+        "summary": meta.summary,
+        "description": meta.description,
+        "bad_example": meta.bad_example,
+        "good_example": meta.good_example,
+        "doc_url": meta.doc_url,
+        "fixable": meta.fixable,
+    })
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::indexing_slicing,
+    clippy::arithmetic_side_effects,
+    reason = "test code"
+)]
+mod tests {
+    use super::*;
+    use crate::rules::all_rules;
+    use crate::rules::exact_aggregation::ExactAggregation;
+    use crate::rules::nondeterministic_function::NondeterministicFunction;
+
+    #[test]
+    fn text_list_has_one_aligned_line_per_rule() {
+        let rules = all_rules();
+        let mut out = Vec::new();
+        write_rule_list(&mut out, &rules, ListFormat::Text).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), rules.len());
+        let group_by = lines
+            .iter()
+            .find(|line| line.starts_with("invalid_group_by "))
+            .unwrap();
+        assert!(group_by.contains("  error    correctness      Invalid GROUP BY usage"));
+        let exact = lines
+            .iter()
+            .find(|line| line.starts_with("exact_aggregation "))
+            .unwrap();
+        assert!(
+            exact.contains("  off      performance"),
+            "opt-in rules list as off"
+        );
+        // Every severity column starts at the same offset.
+        let offsets: Vec<usize> = lines
+            .iter()
+            .map(|line| {
+                line.find("  warning")
+                    .or_else(|| line.find("  error"))
+                    .or_else(|| line.find("  off"))
+                    .unwrap()
+            })
+            .collect();
+        assert!(offsets.windows(2).all(|w| w[0] == w[1]), "{out}");
+    }
+
+    #[test]
+    fn json_list_carries_every_metadata_field() {
+        let mut out = Vec::new();
+        write_rule_list(&mut out, &all_rules(), ListFormat::Json).unwrap();
+        let doc: Value = serde_json::from_slice(&out).unwrap();
+
+        let rule = doc["rules"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|rule| rule["id"] == "nondeterministic_function")
+            .unwrap();
+        assert_eq!(rule["name"], "Using a non-deterministic function");
+        assert_eq!(rule["default_severity"], "warning");
+        assert_eq!(rule["default_enabled"], true);
+        assert_eq!(rule["category"], "maintainability");
+        assert_eq!(rule["fixable"], false);
+        assert!(rule["bad_example"].is_string());
+        assert!(rule["doc_url"].is_string());
+    }
+
+    #[test]
+    fn explanation_includes_examples_and_doc_link() {
+        let mut out = Vec::new();
+        write_explanation(&mut out, &NondeterministicFunction::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with(
+            "nondeterministic_function: Using a non-deterministic function\n\nSeverity: warning\n"
+        ));
+        assert!(
+            out.contains("\nBad:\n    SELECT * FROM dataset.table WHERE dt = CURRENT_DATE()\n")
+        );
+        assert!(out.contains("\nGood:\n    SELECT * FROM dataset.table WHERE dt = @run_date\n"));
+        assert!(out.ends_with("docs/rules.md#using-a-non-deterministic-function\n"));
+    }
+
+    #[test]
+    fn explanation_says_when_a_rule_is_opt_in() {
+        let mut out = Vec::new();
+        write_explanation(&mut out, &ExactAggregation::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("\nSeverity: off by default, warning when enabled\n"));
+    }
+}
@@ -1,8 +1,15 @@
 pub mod ast;
+pub mod cache;
 pub mod config;
 pub mod diagnostic;
+pub mod dump;
+pub mod explain;
 pub mod output;
+pub mod query;
 pub mod rules;
+pub mod schema;
+pub mod types;
+pub mod udf;
 
 /// Build a googlesql (ZetaSQL) parser [`Module`](googlesql::Module).
 ///
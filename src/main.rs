@@ -1,18 +1,31 @@
-use bqvalid::ast::Ast;
+use bqvalid::ast::{Ast, NodeRef};
+use bqvalid::cache::{self, Cache};
 use bqvalid::config::{self, Config};
-use bqvalid::diagnostic::Diagnostic;
-use bqvalid::output::{self, FileResult, OutputFormat};
-use bqvalid::rules::{known_rule_ids, run_rules_ignoring};
-use clap::Parser;
+use bqvalid::diagnostic::{ColumnUnit, Diagnostic};
+use bqvalid::dump::{self, DumpFormat};
+use bqvalid::explain::{self, ListFormat};
+use bqvalid::output::{self, FileResult, FileStats, OutputFormat};
+use bqvalid::query::Selector;
+use bqvalid::rules::rule::LintContext;
+use bqvalid::rules::{
+    Rule, configured_rules, known_rule_ids, renamed_rule_id, run_rules_on_unparsed, run_rules_with,
+};
+use bqvalid::schema::SchemaCatalog;
+use bqvalid::udf::{Udf, UdfCatalog};
+use clap::{Parser, Subcommand};
 use clap_verbosity_flag::Verbosity;
 use googlesql::Module;
 use log::debug;
+use notify::{EventKind, RecursiveMode, Watcher};
 use rayon::prelude::*;
-use std::collections::HashSet;
+use std::cell::{OnceCell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::io::{self, Read, Stdin};
+use std::io::{self, IsTerminal, Read, Stdin, Write};
 use std::path::{Path, PathBuf};
 use std::process::ExitCode;
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime};
 use walkdir::{DirEntry, WalkDir};
 
 fn get_version() -> &'static str {
@@ -30,11 +43,47 @@ fn get_version() -> &'static str {
     version = get_version(),
 )]
 struct Args {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
     files: Vec<String>,
 
-    /// Output format for diagnostics.
-    #[clap(long, value_enum, default_value_t = OutputFormat::Plain)]
-    format: OutputFormat,
+    /// Output format for diagnostics. Defaults to `pretty` when stdout is a
+    /// terminal and `plain` otherwise.
+    #[clap(long, value_enum)]
+    format: Option<OutputFormat>,
+
+    /// What diagnostic columns count: `byte` (default), `utf16` code units as
+    /// editors and LSP clients expect, or `char`acters.
+    #[clap(long, value_enum, default_value_t = ColumnUnit::Byte)]
+    column_unit: ColumnUnit,
+
+    /// Print run statistics instead of diagnostics: files scanned and failing
+    /// to parse, problems per rule and severity, the top offending files and
+    /// parse/lint timing. As JSON with `--format json`, as a table otherwise
+    /// (the same as `--format summary`).
+    #[clap(long)]
+    statistics: bool,
+
+    /// Directory of the result cache, which lets unchanged files skip parsing.
+    /// Defaults to `.bqvalid_cache` in the current directory.
+    #[clap(long, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+
+    /// Lint every file afresh, neither reading nor writing the result cache.
+    #[clap(long)]
+    no_cache: bool,
+
+    /// After linting, remove the cache entries the run did not use. Only pass
+    /// it when linting the whole project, since the entries of every file left
+    /// out of the run go too.
+    #[clap(long, conflicts_with_all = ["no_cache", "watch"])]
+    prune_cache: bool,
+
+    /// Keep running: lint the given files, then re-lint the ones that change
+    /// and redraw the results. Stop with Ctrl-C.
+    #[clap(long)]
+    watch: bool,
 
     /// Rule id to ignore (suppress its diagnostics). Accepts a comma-separated
     /// list and is repeatable. When given, overrides the `ignore` list from the
@@ -51,6 +100,47 @@ struct Args {
     verbose: Verbosity,
 }
 
+/// Subcommands. Without one, bqvalid lints the given files (a file literally
+/// named like a subcommand can be passed as `./query`).
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print every node matching a structural selector, e.g.
+    /// `ASTWhereClause ASTFunctionCall[name=REGEXP_CONTAINS]`. Reads stdin when
+    /// no files are given.
+    Query {
+        /// The selector to match (see `docs/query.md`).
+        selector: String,
+
+        files: Vec<String>,
+    },
+    /// Print the parsed syntax tree with node kinds, byte ranges, 1-based
+    /// positions and source snippets.
+    Ast {
+        /// The SQL file to parse, or `-` for stdin.
+        file: String,
+
+        /// Layout of the printed tree.
+        #[clap(long, value_enum, default_value_t = DumpFormat::Text)]
+        format: DumpFormat,
+
+        /// Print only the subtree of the innermost node at this 1-based
+        /// position (columns count bytes, like diagnostics).
+        #[clap(long, value_name = "LINE:COLUMN", value_parser = parse_line_column)]
+        at: Option<(usize, usize)>,
+    },
+    /// List every available rule, including custom rules from the config.
+    Rules {
+        /// Layout of the list.
+        #[clap(long, value_enum, default_value_t = ListFormat::Text)]
+        format: ListFormat,
+    },
+    /// Print the description and examples for one rule.
+    Explain {
+        /// The rule id, as shown by `bqvalid rules`.
+        rule_id: String,
+    },
+}
+
 fn main() -> ExitCode {
     let stdin = io::stdin();
     let args = Args::parse();
@@ -59,35 +149,119 @@ fn main() -> ExitCode {
         .init();
     debug!("verbose mode");
 
-    let ignore = match resolve_ignore(args.config, args.ignore) {
-        Ok(ignore) => ignore,
+    match args.command {
+        Some(Command::Query { selector, files }) => return run_query(&stdin, &selector, files),
+        Some(Command::Ast { file, format, at }) => return run_ast(&stdin, &file, format, at),
+        Some(Command::Rules { format }) => {
+            return with_rules(args.config, |rules| {
+                let mut out = io::stdout().lock();
+                explain::write_rule_list(&mut out, rules, format)
+                    .map_err(|e| format!("writing output: {}", e))
+            });
+        }
+        Some(Command::Explain { rule_id }) => {
+            return with_rules(args.config, |rules| {
+                let id = renamed_rule_id(&rule_id).unwrap_or(&rule_id);
+                let rule = rules
+                    .iter()
+                    .find(|rule| rule.id() == id)
+                    .ok_or_else(|| format!("unknown rule id: {}", rule_id))?;
+                let mut out = io::stdout().lock();
+                explain::write_explanation(&mut out, rule.as_ref())
+                    .map_err(|e| format!("writing output: {}", e))
+            });
+        }
+        None => {}
+    }
+
+    let settings = match resolve_settings(args.config, args.ignore) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let rules = match rules_for(&settings) {
+        Ok(rules) => rules,
         Err(e) => {
             eprintln!("Error: {}", e);
             return ExitCode::FAILURE;
         }
     };
+    let udfs = settings
+        .config
+        .udf_dir
+        .as_deref()
+        .map_or_else(UdfCatalog::default, load_udf_catalog);
+    let schema = SchemaCatalog::new(&settings.config.schema);
+    let cache = (!args.no_cache)
+        .then(|| {
+            let dir = args
+                .cache_dir
+                .unwrap_or_else(|| PathBuf::from(cache::DEFAULT_DIR));
+            open_cache(dir, &settings, &rules, &udfs)
+        })
+        .flatten();
 
+    let on_terminal = io::stdout().is_terminal();
+    let format = args.format.unwrap_or(if on_terminal {
+        OutputFormat::Pretty
+    } else {
+        OutputFormat::Plain
+    });
     // Both inputs converge on `Vec<FileResult>`; `show_paths` only distinguishes
     // the two in the plain format (files prefix the path, stdin does not).
-    let (results, show_paths) = if args.files.is_empty() {
-        match analyse_stdin(&stdin, &ignore) {
-            Some(results) => (results, false),
+    let options = output::EmitOptions {
+        format,
+        version: get_version(),
+        rules: &rules,
+        show_paths: !args.files.is_empty(),
+        color: on_terminal && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+        column_unit: args.column_unit,
+        statistics: args.statistics,
+    };
+
+    if args.watch {
+        if args.files.is_empty() {
+            eprintln!("Error: --watch needs files or directories to watch");
+            return ExitCode::FAILURE;
+        }
+        return run_watch(
+            &args.files,
+            &udfs,
+            &schema,
+            cache.as_ref(),
+            &options,
+            on_terminal,
+        );
+    }
+
+    let mut results = if args.files.is_empty() {
+        match analyse_stdin(&stdin, &rules, &udfs, &schema) {
+            Some(results) => results,
             None => return ExitCode::FAILURE,
         }
     } else {
-        (analyse_paths(collect_targets(args.files), &ignore), true)
+        let results = analyse_paths(
+            collect_targets(args.files),
+            &rules,
+            &udfs,
+            &schema,
+            cache.as_ref(),
+        );
+        if args.prune_cache
+            && let Some(cache) = &cache
+        {
+            cache.prune();
+        }
+        results
     };
 
+    output::convert_columns(&mut results, args.column_unit);
+
     let mut out = io::stdout().lock();
     let mut err = io::stderr().lock();
-    match output::emit(
-        &results,
-        args.format,
-        get_version(),
-        show_paths,
-        &mut out,
-        &mut err,
-    ) {
+    match output::emit(&results, &options, &mut out, &mut err) {
         Ok(true) => ExitCode::FAILURE,
         Ok(false) => ExitCode::SUCCESS,
         Err(e) => {
@@ -97,24 +271,242 @@ fn main() -> ExitCode {
     }
 }
 
-/// Read SQL from stdin and analyse it as a single `<stdin>` result. Returns
-/// `None` (after logging to stderr) when the input cannot be read or the parser
-/// module fails to load, so the caller can exit with a failure code.
-fn analyse_stdin(stdin: &Stdin, ignore: &HashSet<String>) -> Option<Vec<FileResult>> {
+/// Every rule the config knows of, for `bqvalid rules` and `explain`: the
+/// registry with the config's options plus its custom rules. The ignore list
+/// only applies to linting, so ignored rules are still listed and explained.
+fn known_rules(config_path: Option<PathBuf>) -> Result<Vec<Box<dyn Rule>>, String> {
+    resolve_settings(config_path, Vec::new()).and_then(|settings| {
+        configured_rules(
+            &settings.config.rules,
+            &settings.config.custom_rules,
+            &HashSet::new(),
+        )
+    })
+}
+
+/// The rule set `settings` selects.
+fn rules_for(settings: &Settings) -> Result<Vec<Box<dyn Rule>>, String> {
+    configured_rules(
+        &settings.config.rules,
+        &settings.config.custom_rules,
+        &settings.ignore,
+    )
+}
+
+/// Collect the `CREATE FUNCTION` statements of every `.sql` file under `dir`
+/// (the config's `udf_dir`). Unreadable files are reported to stderr and
+/// skipped; they only narrow what the rules know about.
+fn load_udf_catalog(dir: &Path) -> UdfCatalog {
+    let mut catalog = UdfCatalog::default();
+    let Some(mut module) = new_module() else {
+        return catalog;
+    };
+    let mut paths = collect_targets(vec![dir.display().to_string()]);
+    paths.sort();
+    for path in paths {
+        match fs::read_to_string(&path) {
+            Ok(sql) => {
+                let asts = parse_script(&mut module, &sql);
+                catalog.extend(UdfCatalog::from_script(&asts, &sql));
+            }
+            Err(e) => eprintln!("Error reading UDF file {}: {}", path.display(), e),
+        }
+    }
+    debug!("loaded {} user-defined functions", catalog.len());
+    catalog
+}
+
+/// Load every known rule (see [`known_rules`]) and hand them to `f`, mapping
+/// either step's error to a failing exit code.
+fn with_rules(
+    config_path: Option<PathBuf>,
+    f: impl FnOnce(&[Box<dyn Rule>]) -> Result<(), String>,
+) -> ExitCode {
+    match known_rules(config_path).and_then(|rules| f(&rules)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Read all of stdin, logging to stderr and returning `None` on failure.
+fn read_stdin(stdin: &Stdin) -> Option<String> {
     let mut sql = String::new();
     let read_result = stdin.lock().read_to_string(&mut sql);
     if let Err(e) = read_result {
         eprintln!("Error reading stdin: {}", e);
         return None;
     }
+    Some(sql)
+}
+
+/// Read SQL from stdin and analyse it as a single `<stdin>` result. Returns
+/// `None` (after logging to stderr) when the input cannot be read or the parser
+/// module fails to load, so the caller can exit with a failure code.
+fn analyse_stdin(
+    stdin: &Stdin,
+    rules: &[Box<dyn Rule>],
+    udfs: &UdfCatalog,
+    schema: &SchemaCatalog,
+) -> Option<Vec<FileResult>> {
+    let sql = read_stdin(stdin)?;
     let mut module = new_module()?;
+    let (diagnostics, stats) = analyse_sql_googlesql(&mut module, &sql, rules, udfs, schema);
     Some(vec![FileResult {
         path: PathBuf::from("<stdin>"),
-        diagnostics: analyse_sql_googlesql(&mut module, &sql, ignore),
+        diagnostics,
         read_error: None,
+        source: sql,
+        stats,
     }])
 }
 
+/// Run `bqvalid query`: print every node matching `selector` in the given
+/// files (or stdin), one `path:row:col-row:col: kind: text` line per match.
+/// Unreadable files are reported to stderr and fail the run, like linting.
+fn run_query(stdin: &Stdin, selector: &str, files: Vec<String>) -> ExitCode {
+    let selector = match Selector::parse(selector) {
+        Ok(selector) => selector,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let Some(mut module) = new_module() else {
+        return ExitCode::FAILURE;
+    };
+
+    let mut out = io::stdout().lock();
+    let mut failed = false;
+    let written = if files.is_empty() {
+        match read_stdin(stdin) {
+            Some(sql) => write_matches(&mut out, &mut module, &selector, None, &sql),
+            None => return ExitCode::FAILURE,
+        }
+    } else {
+        collect_targets(files)
+            .into_iter()
+            .try_for_each(|path| match fs::read_to_string(&path) {
+                Ok(sql) => write_matches(&mut out, &mut module, &selector, Some(&path), &sql),
+                Err(e) => {
+                    eprintln!("{}: Error reading file: {}", path.display(), e);
+                    failed = true;
+                    Ok(())
+                }
+            })
+    };
+    match written {
+        Ok(()) if !failed => ExitCode::SUCCESS,
+        Ok(()) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("Error writing output: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Run `bqvalid ast`: print the tree of every statement in `file` (`-` for
+/// stdin), or only the subtree at `at` when given.
+fn run_ast(stdin: &Stdin, file: &str, format: DumpFormat, at: Option<(usize, usize)>) -> ExitCode {
+    let sql = if file == "-" {
+        match read_stdin(stdin) {
+            Some(sql) => sql,
+            None => return ExitCode::FAILURE,
+        }
+    } else {
+        match fs::read_to_string(file) {
+            Ok(sql) => sql,
+            Err(e) => {
+                eprintln!("{}: Error reading file: {}", file, e);
+                return ExitCode::FAILURE;
+            }
+        }
+    };
+    let Some(mut module) = new_module() else {
+        return ExitCode::FAILURE;
+    };
+
+    let asts = parse_script(&mut module, &sql);
+    let nodes = match at {
+        Some((line, column)) => match dump::node_at(&asts, line, column) {
+            Some(node) => vec![node],
+            None => {
+                eprintln!("Error: no node at {}:{}", line, column);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => asts.iter().map(Ast::root).collect(),
+    };
+
+    let mut out = io::stdout().lock();
+    match dump::write_trees(&mut out, &nodes, &sql, format) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error writing output: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Parse a `LINE:COLUMN` argument into its two 1-based numbers.
+fn parse_line_column(s: &str) -> Result<(usize, usize), String> {
+    let (line, column) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected LINE:COLUMN, got {}", s))?;
+    let number = |part: &str| match part.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("expected a positive number, got {}", part)),
+    };
+    Ok((number(line)?, number(column)?))
+}
+
+/// Write one [`format_match`] line per node of `sql` matched by `selector`.
+fn write_matches<W: Write>(
+    out: &mut W,
+    module: &mut Module,
+    selector: &Selector,
+    path: Option<&Path>,
+    sql: &str,
+) -> io::Result<()> {
+    for ast in parse_script(module, sql) {
+        for node in selector.select(&ast, sql) {
+            writeln!(out, "{}", format_match(path, &node, sql))?;
+        }
+    }
+    Ok(())
+}
+
+/// One `bqvalid query` result line: the 1-based span, the node kind and the
+/// first line of its source text (path-prefixed when reading files).
+fn format_match(path: Option<&Path>, node: &NodeRef<'_>, sql: &str) -> String {
+    let start = node.start_position();
+    let end = node.end_position();
+    let text = node.text(sql).unwrap_or_default();
+    let mut lines = text.lines();
+    let first = lines.next().unwrap_or_default();
+    let ellipsis = if lines.next().is_some() { " ..." } else { "" };
+    let span = format!(
+        "{}:{}-{}:{}",
+        start.row.saturating_add(1),
+        start.column.saturating_add(1),
+        end.row.saturating_add(1),
+        end.column.saturating_add(1),
+    );
+    match path {
+        Some(path) => format!(
+            "{}:{}: {}: {}{}",
+            path.display(),
+            span,
+            node.kind(),
+            first,
+            ellipsis
+        ),
+        None => format!("{}: {}: {}{}", span, node.kind(), first, ellipsis),
+    }
+}
+
 /// Expand the CLI file arguments into the set of `.sql` files to analyse,
 /// walking directories recursively. Walk errors are logged to stderr and
 /// skipped.
@@ -159,106 +551,370 @@ fn new_module() -> Option<Module> {
     }
 }
 
+thread_local! {
+    /// This thread's parser module, built on first use and kept for the life of
+    /// the thread. `None` means building it failed, which was reported.
+    static MODULE: OnceCell<Option<RefCell<Module>>> = const { OnceCell::new() };
+}
+
 /// Analyse many files in parallel. Each worker thread builds its parser module
-/// once (via `map_init`) and reuses it across the files it handles, so the
-/// module is initialized per thread rather than per file. A `None` module means
-/// initialization failed and was already reported; that thread's files then
-/// yield no diagnostics. Results are sorted by path so the output is stable
-/// regardless of scheduling.
-///
-/// The `Module` is large, so it is boxed to keep the per-item closure state
-/// small.
-fn analyse_paths(paths: Vec<PathBuf>, ignore: &HashSet<String>) -> Vec<FileResult> {
+/// once, on first use, and keeps it in a thread local, so the module is
+/// initialized per thread rather than per file, and a long-lived process such
+/// as `--watch` reuses it across runs. A thread whose module failed to
+/// initialize (already reported) yields no diagnostics for its files. With a
+/// `cache`, files whose content it has a result for are not parsed at all.
+/// Results are sorted by path so the output is stable regardless of
+/// scheduling.
+fn analyse_paths(
+    paths: Vec<PathBuf>,
+    rules: &[Box<dyn Rule>],
+    udfs: &UdfCatalog,
+    schema: &SchemaCatalog,
+    cache: Option<&Cache>,
+) -> Vec<FileResult> {
     let mut results: Vec<FileResult> = paths
         .par_iter()
-        .map_init(
-            || new_module().map(Box::new),
-            |module, path| match fs::read_to_string(path) {
-                Ok(sql) => {
-                    let diagnostics = module.as_mut().map_or_else(Vec::new, |module| {
-                        analyse_sql_googlesql(module.as_mut(), &sql, ignore)
-                    });
-                    FileResult {
-                        path: path.clone(),
-                        diagnostics,
-                        read_error: None,
-                    }
-                }
-                Err(e) => FileResult {
+        .map(|path| match fs::read_to_string(path) {
+            Ok(sql) => {
+                let (diagnostics, stats) = analyse_file(&sql, rules, udfs, schema, cache);
+                FileResult {
                     path: path.clone(),
-                    diagnostics: Vec::new(),
-                    read_error: Some(e.to_string()),
-                },
+                    diagnostics,
+                    read_error: None,
+                    source: sql,
+                    stats,
+                }
+            }
+            Err(e) => FileResult {
+                path: path.clone(),
+                diagnostics: Vec::new(),
+                read_error: Some(e.to_string()),
+                source: String::new(),
+                stats: FileStats::default(),
             },
-        )
+        })
         .collect();
     results.sort_by(|a, b| a.path.cmp(&b.path));
     results
 }
 
-/// Analyse `sql` with the googlesql (ZetaSQL) backend.
-///
-/// ZetaSQL parses one statement at a time and cannot recover past a syntax
-/// error, so `parse_statements` returns every statement it parsed before
-/// stopping plus the error that stopped it. We run the rules over each parsed
-/// statement (byte offsets are relative to the whole script, so positions stay
-/// correct) and report the halting error, if any, to stderr, still emitting
-/// diagnostics for the statements it could parse.
-fn analyse_sql_googlesql(
-    module: &mut Module,
+/// Render `results` for `--watch`, clearing the screen first on a terminal.
+fn redraw(
+    results: &[FileResult],
+    options: &output::EmitOptions<'_>,
+    on_terminal: bool,
+) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    let mut err = io::stderr().lock();
+    if on_terminal {
+        // Clear the screen and move the cursor home.
+        write!(out, "\x1b[2J\x1b[H")?;
+        // Flush before `emit` writes to stderr, so the clear cannot wipe
+        // the read and parse errors.
+        out.flush()?;
+    }
+    output::emit(results, options, &mut out, &mut err)?;
+    out.flush()
+}
+
+/// Lint one file's `sql` with this thread's module, or take the result from
+/// `cache` when it has one, parse error included. Fresh
+/// results are stored in the cache; nothing is stored when the module failed
+/// to initialize.
+fn analyse_file(
     sql: &str,
-    ignore: &HashSet<String>,
-) -> Vec<Diagnostic> {
-    let parsed = match module.parse_statements(sql) {
-        Ok(parsed) => parsed,
+    rules: &[Box<dyn Rule>],
+    udfs: &UdfCatalog,
+    schema: &SchemaCatalog,
+    cache: Option<&Cache>,
+) -> (Vec<Diagnostic>, FileStats) {
+    if let Some(entry) = cache.and_then(|cache| cache.get(sql)) {
+        let stats = FileStats {
+            parse_error: entry.parse_error,
+            cached: true,
+            ..FileStats::default()
+        };
+        return (entry.diagnostics, stats);
+    }
+    let result = MODULE.with(|module| {
+        module
+            .get_or_init(|| new_module().map(RefCell::new))
+            .as_ref()
+            .map(|module| analyse_sql_googlesql(&mut module.borrow_mut(), sql, rules, udfs, schema))
+    });
+    let Some((diagnostics, stats)) = result else {
+        return Default::default();
+    };
+    if let Some(cache) = cache {
+        cache.put(sql, &diagnostics, stats.parse_error.as_deref());
+    }
+    (diagnostics, stats)
+}
+
+/// The result cache in `dir`, keyed by everything besides a file's content that
+/// decides its diagnostics: the bqvalid version, the rule set, the config
+/// (rule options and custom rules) and the known user-defined functions. The
+/// config and functions enter the key as JSON, whose shape only changes with
+/// the types themselves, and the crate version covers those changes.
+/// Development builds share a version, so pass `--no-cache` while changing the
+/// rules themselves. `None`, and no caching, if the key cannot be serialized.
+fn open_cache(
+    dir: PathBuf,
+    settings: &Settings,
+    rules: &[Box<dyn Rule>],
+    udfs: &UdfCatalog,
+) -> Option<Cache> {
+    let mut rule_ids: Vec<&str> = rules.iter().map(|rule| rule.id()).collect();
+    rule_ids.sort_unstable();
+    let mut functions: Vec<&Udf> = udfs.functions().collect();
+    functions.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    let key = serde_json::to_string(&settings.config)
+        .and_then(|config| serde_json::to_string(&functions).map(|functions| (config, functions)));
+    match key {
+        Ok((config, functions)) => Some(Cache::new(
+            dir,
+            &[
+                env!("CARGO_PKG_VERSION"),
+                get_version(),
+                &rule_ids.join(","),
+                &config,
+                &functions,
+            ],
+        )),
+        Err(e) => {
+            debug!("cache disabled, cannot serialize its key: {}", e);
+            None
+        }
+    }
+}
+
+/// How long the file system has to stay quiet before `--watch` re-lints, so a
+/// burst of events from one save triggers a single run.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// What `--watch` compares to tell whether a target changed: its modification
+/// time and length, or `None` when it cannot be read.
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Record the [`Stamp`] of every target in `stamps`, forgetting the paths that
+/// are no longer targets, and return the targets that are new or changed.
+fn update_stamps(stamps: &mut HashMap<PathBuf, Stamp>, targets: Vec<PathBuf>) -> Vec<PathBuf> {
+    let current: HashSet<&PathBuf> = targets.iter().collect();
+    stamps.retain(|path, _| current.contains(path));
+    let mut changed = Vec::new();
+    for path in targets {
+        let stamp = stamp(&path);
+        if stamps.get(&path) != Some(&stamp) {
+            stamps.insert(path.clone(), stamp);
+            changed.push(path);
+        }
+    }
+    changed
+}
+
+/// Run `--watch`: lint the targets under `files`, then, whenever the file
+/// system reports a change under them, re-lint only the targets that are new
+/// or whose [`Stamp`] changed, drop the ones that disappeared and redraw every
+/// result (clearing the screen first on a terminal). Runs until interrupted or
+/// the watcher fails.
+fn run_watch(
+    files: &[String],
+    udfs: &UdfCatalog,
+    schema: &SchemaCatalog,
+    cache: Option<&Cache>,
+    options: &output::EmitOptions<'_>,
+    on_terminal: bool,
+) -> ExitCode {
+    let (sender, events) = mpsc::channel();
+    let mut watcher = match notify::recommended_watcher(sender) {
+        Ok(watcher) => watcher,
         Err(e) => {
-            eprintln!("Error parsing SQL input: {}", e);
-            return Vec::new();
+            eprintln!("Error starting the file watcher: {}", e);
+            return ExitCode::FAILURE;
         }
     };
-    if let Some(error) = parsed.error() {
-        eprintln!("Error parsing SQL input: {}", error);
+    for file in files {
+        if let Err(e) = watcher.watch(Path::new(file), RecursiveMode::Recursive) {
+            eprintln!("Error watching {}: {}", file, e);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let mut stamps: HashMap<PathBuf, Stamp> = HashMap::new();
+    let mut results: Vec<FileResult> = Vec::new();
+    loop {
+        let changed = update_stamps(&mut stamps, collect_targets(files.to_vec()));
+        debug!("re-linting {} changed file(s)", changed.len());
+        let stale: HashSet<&PathBuf> = changed.iter().collect();
+        results.retain(|r| stamps.contains_key(&r.path) && !stale.contains(&r.path));
+        let mut fresh = analyse_paths(changed, options.rules, udfs, schema, cache);
+        output::convert_columns(&mut fresh, options.column_unit);
+        results.extend(fresh);
+        results.sort_by(|a, b| a.path.cmp(&b.path));
+
+        if let Err(e) = redraw(&results, options, on_terminal) {
+            eprintln!("Error writing output: {}", e);
+            return ExitCode::FAILURE;
+        }
+
+        // Wait for a relevant event, then for the burst it starts to settle.
+        loop {
+            match events.recv() {
+                Ok(Ok(event)) if !matches!(event.kind, EventKind::Access(_)) => break,
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => eprintln!("Error watching files: {}", e),
+                Err(_) => return ExitCode::FAILURE,
+            }
+        }
+        while events.recv_timeout(WATCH_DEBOUNCE).is_ok() {}
     }
+}
+
+/// Analyse `sql` with the googlesql (ZetaSQL) backend, running `rules` over
+/// each statement [`parse_script`] recovers. The rules see the functions in
+/// `udfs` plus those the script itself declares, and the tables in `schema`.
+/// Also reports the error that stopped the parser and the time spent parsing
+/// and linting.
+fn analyse_sql_googlesql(
+    module: &mut Module,
+    sql: &str,
+    rules: &[Box<dyn Rule>],
+    udfs: &UdfCatalog,
+    schema: &SchemaCatalog,
+) -> (Vec<Diagnostic>, FileStats) {
+    let started = Instant::now();
+    let (asts, parse_error) = parse_script_with_error(module, sql);
+    let parse_time = started.elapsed();
+    let started = Instant::now();
+    let local = UdfCatalog::from_script(&asts, sql);
+    let merged;
+    let udfs = if local.is_empty() {
+        udfs
+    } else {
+        let mut catalog = udfs.clone();
+        catalog.extend(local);
+        merged = catalog;
+        &merged
+    };
+    let cx = LintContext { sql, udfs, schema };
+    let mut diagnostics: Vec<Diagnostic> = asts
+        .iter()
+        .flat_map(|ast| run_rules_with(ast, &cx, rules))
+        .collect();
+    let parse_error = parse_error.map(|error| {
+        let parsed_end = asts.last().map_or(0, |ast| ast.root().end_byte());
+        let unparsed = run_rules_on_unparsed(parsed_end..sql.len(), &cx, rules);
+        // Name what the rules found where the parser stopped, rather than
+        // leaving only its generic syntax error.
+        let error = match unparsed.first() {
+            Some(found) => format!("{} ({})", found.message(), error),
+            None => error,
+        };
+        diagnostics.extend(unparsed);
+        error
+    });
+    let stats = FileStats {
+        parse_error,
+        cached: false,
+        parse_time,
+        lint_time: started.elapsed(),
+    };
+    (diagnostics, stats)
+}
 
-    let mut diagnostics = Vec::new();
-    for statement in parsed.statements() {
-        let ast = Ast::from_googlesql_root(statement.root(), sql);
-        diagnostics.extend(run_rules_ignoring(&ast, sql, ignore));
+/// Parse `sql` into one [`Ast`] per statement.
+///
+/// ZetaSQL parses one statement at a time and cannot recover past a syntax
+/// error, so `parse_statements` returns every statement it parsed before
+/// stopping plus the error that stopped it. Each parsed statement becomes its
+/// own tree (byte offsets are relative to the whole script, so positions stay
+/// correct); the halting error, if any, is reported to stderr and the
+/// statements before it are still returned.
+fn parse_script(module: &mut Module, sql: &str) -> Vec<Ast> {
+    let (asts, error) = parse_script_with_error(module, sql);
+    if let Some(error) = error {
+        eprintln!("Error parsing SQL input: {}", error);
     }
-    diagnostics
+    asts
 }
 
-/// Resolve the effective set of ignored rule ids from the config file and CLI.
-/// Discovers the config by walking up from the current directory to the git
-/// repository root, lets a non-empty CLI `--ignore` override it, and warns
-/// about ids that match no known rule. Returns an error string when the config
-/// cannot be loaded.
-fn resolve_ignore(
+/// [`parse_script`], returning the error that stopped the parser instead of
+/// reporting it.
+fn parse_script_with_error(module: &mut Module, sql: &str) -> (Vec<Ast>, Option<String>) {
+    let parsed = match module.parse_statements(sql) {
+        Ok(parsed) => parsed,
+        Err(e) => return (Vec::new(), Some(e.to_string())),
+    };
+    let error = parsed.error().map(|error| error.to_string());
+
+    let asts = parsed
+        .statements()
+        .iter()
+        .map(|statement| Ast::from_googlesql_root(statement.root(), sql))
+        .collect();
+    (asts, error)
+}
+
+/// Effective settings for a lint run: the loaded config file (or the default
+/// when none was found) and the ignore list after CLI overrides.
+struct Settings {
+    config: Config,
+    ignore: HashSet<String>,
+}
+
+/// Resolve the effective settings from the config file and CLI. Discovers the
+/// config by walking up from the current directory to the git repository root,
+/// lets a non-empty CLI `--ignore` override its ignore list, and warns about
+/// ignored ids that match no known rule. Returns an error string when the
+/// config cannot be loaded.
+fn resolve_settings(
     config_path: Option<PathBuf>,
     cli_ignore: Vec<String>,
-) -> Result<HashSet<String>, String> {
+) -> Result<Settings, String> {
     let cwd = std::env::current_dir()
         .map_err(|e| format!("cannot determine current directory: {}", e))?;
-    resolve_ignore_in(&cwd, config_path, cli_ignore)
+    resolve_settings_in(&cwd, config_path, cli_ignore)
 }
 
-/// Core of [`resolve_ignore`], parameterized by the directory used to discover
-/// the default config file so it can be exercised without touching the process
-/// working directory.
-fn resolve_ignore_in(
+/// Core of [`resolve_settings`], parameterized by the directory used to
+/// discover the default config file so it can be exercised without touching
+/// the process working directory.
+fn resolve_settings_in(
     cwd: &Path,
     config_path: Option<PathBuf>,
     cli_ignore: Vec<String>,
-) -> Result<HashSet<String>, String> {
-    let config = match config::discover_config(config_path, cwd) {
+) -> Result<Settings, String> {
+    let mut config = match config::discover_config(config_path, cwd) {
         Some(path) => Config::load(&path).map_err(|e| e.to_string())?,
         None => Config::default(),
     };
-    let ignore = config::effective_ignore(cli_ignore, config.ignore);
-    for id in config::unknown_ignore_ids(&ignore, &known_rule_ids()) {
+    let ignore: Vec<String> =
+        config::effective_ignore(cli_ignore, std::mem::take(&mut config.ignore))
+            .into_iter()
+            .map(|id| match renamed_rule_id(&id) {
+                Some(new_id) => {
+                    eprintln!("Warning: rule id {} was renamed to {}", id, new_id);
+                    new_id.to_string()
+                }
+                None => id,
+            })
+            .collect();
+    // Custom rules are addressable by id just like the built-in ones.
+    let mut known = known_rule_ids();
+    known.extend(config.custom_rules.iter().map(|rule| rule.id.clone()));
+    let known: HashSet<&str> = known.iter().map(String::as_str).collect();
+    for id in config::unknown_ignore_ids(&ignore, &known) {
         eprintln!("Warning: unknown rule id in ignore list: {}", id);
     }
-    Ok(ignore.into_iter().collect())
+    Ok(Settings {
+        config,
+        ignore: ignore.into_iter().collect(),
+    })
 }
 
 #[cfg(test)]
@@ -272,6 +928,8 @@ fn resolve_ignore_in(
 )]
 mod tests {
     use super::*;
+    use bqvalid::rules::all_rules;
+    use rstest::rstest;
     use std::fs::{self, File};
     use tempfile::tempdir;
 
@@ -279,13 +937,14 @@ mod tests {
     /// how the binary drives `analyse_sql_googlesql`.
     fn analyse(sql: &str) -> Vec<Diagnostic> {
         let mut module = new_module().expect("googlesql module builds");
-        analyse_sql_googlesql(&mut module, sql, &HashSet::new())
+        let (udfs, schema) = (UdfCatalog::default(), SchemaCatalog::default());
+        analyse_sql_googlesql(&mut module, sql, &all_rules(), &udfs, &schema).0
     }
 
     #[test]
     fn flags_current_date_end_to_end() {
         // The whole pipeline (ZetaSQL parse -> neutral AST -> rules) must surface
-        // the use_current_date rule with a 1-based position.
+        // the nondeterministic_function rule with a 1-based position.
         let diagnostics = analyse("SELECT CURRENT_DATE() FROM t");
         assert_eq!(diagnostics.len(), 1);
         assert_eq!(diagnostics[0].row(), 1);
@@ -329,7 +988,7 @@ mod tests {
 
     #[test]
     fn multiple_messages_in_single_sql_file() {
-        // A single statement that trips more than one rule (use_current_date and
+        // A single statement that trips more than one rule (nondeterministic_function and
         // compare_table_suffix_with_subquery) yields multiple diagnostics.
         let sql = "\
 select
@@ -364,15 +1023,18 @@ where
         // parse must stay independent (no state leaking between calls), so a clean
         // query after a dirty one still yields nothing.
         let mut module = new_module().expect("googlesql module builds");
-        let dirty = analyse_sql_googlesql(&mut module, "SELECT CURRENT_DATE()", &HashSet::new());
-        let clean = analyse_sql_googlesql(&mut module, "SELECT id FROM users", &HashSet::new());
+        let rules = all_rules();
+        let (udfs, schema) = (UdfCatalog::default(), SchemaCatalog::default());
+        let mut analyse = |sql| analyse_sql_googlesql(&mut module, sql, &rules, &udfs, &schema).0;
+        let dirty = analyse("SELECT CURRENT_DATE()");
+        let clean = analyse("SELECT id FROM users");
         assert!(!dirty.is_empty(), "dirty query should produce diagnostics");
         assert!(clean.is_empty(), "clean query should produce none");
     }
 
     #[test]
     fn analyse_sql_aggregates_multiple_rules_from_a_single_query() {
-        // Triggers both use_current_date and compare_table_suffix_with_subquery,
+        // Triggers both nondeterministic_function and compare_table_suffix_with_subquery,
         // proving analysis fans a query out across every rule and merges results.
         let sql = "SELECT CURRENT_DATE() AS d \
                    FROM t \
@@ -425,7 +1087,8 @@ where
             dir.path().join("b.sql"),
         ];
 
-        let results = analyse_paths(paths, &HashSet::new());
+        let (udfs, schema) = (UdfCatalog::default(), SchemaCatalog::default());
+        let results = analyse_paths(paths, &all_rules(), &udfs, &schema, None);
 
         let ordered: Vec<PathBuf> = results.iter().map(|r| r.path.clone()).collect();
         let mut expected = ordered.clone();
@@ -439,13 +1102,39 @@ where
         );
     }
 
+    #[test]
+    fn analyse_paths_reuses_cached_results_for_unchanged_content() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("a.sql");
+        fs::write(&path, "SELECT CURRENT_DATE()").unwrap();
+        let cache = Cache::new(dir.path().join("cache"), &["test"]);
+        let rules = all_rules();
+        let (udfs, schema) = (UdfCatalog::default(), SchemaCatalog::default());
+
+        let fresh = analyse_paths(vec![path.clone()], &rules, &udfs, &schema, Some(&cache));
+        assert!(!fresh[0].diagnostics.is_empty());
+        assert!(cache.get("SELECT CURRENT_DATE()").is_some());
+
+        let cached = analyse_paths(vec![path], &rules, &udfs, &schema, Some(&cache));
+        assert_eq!(cached[0].diagnostics, fresh[0].diagnostics);
+        // A hit is not parsed, so no time is spent on it.
+        assert!(cached[0].stats.cached);
+        assert!(!fresh[0].stats.cached);
+    }
+
     #[test]
     fn analyse_paths_records_read_errors_for_missing_files() {
         // A path that cannot be read surfaces as a read_error, not a silent drop.
         let dir = tempdir().unwrap();
         let missing = dir.path().join("does_not_exist.sql");
 
-        let results = analyse_paths(vec![missing.clone()], &HashSet::new());
+        let results = analyse_paths(
+            vec![missing.clone()],
+            &all_rules(),
+            &UdfCatalog::default(),
+            &SchemaCatalog::default(),
+            None,
+        );
 
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].path, missing);
@@ -458,14 +1147,16 @@ where
         let dir = tempdir().unwrap();
         fs::write(
             dir.path().join("bqvalid.toml"),
-            "ignore = [\"use_current_date\"]",
+            "ignore = [\"nondeterministic_function\"]",
         )
         .unwrap();
 
-        let ignore = resolve_ignore_in(dir.path(), None, Vec::new()).expect("loads config");
+        let ignore = resolve_settings_in(dir.path(), None, Vec::new())
+            .expect("loads config")
+            .ignore;
         assert_eq!(
             ignore,
-            std::iter::once("use_current_date".to_string()).collect()
+            std::iter::once("nondeterministic_function".to_string()).collect()
         );
     }
 
@@ -474,12 +1165,13 @@ where
         let dir = tempdir().unwrap();
         fs::write(
             dir.path().join("bqvalid.toml"),
-            "ignore = [\"use_current_date\"]",
+            "ignore = [\"nondeterministic_function\"]",
         )
         .unwrap();
 
-        let ignore = resolve_ignore_in(dir.path(), None, vec!["invalid_group_by".to_string()])
-            .expect("loads config");
+        let ignore = resolve_settings_in(dir.path(), None, vec!["invalid_group_by".to_string()])
+            .expect("loads config")
+            .ignore;
         assert_eq!(
             ignore,
             std::iter::once("invalid_group_by".to_string()).collect(),
@@ -487,10 +1179,24 @@ where
         );
     }
 
+    #[test]
+    fn resolve_ignore_maps_former_rule_ids() {
+        let dir = tempdir().unwrap();
+        let ignore = resolve_settings_in(dir.path(), None, vec!["use_current_date".to_string()])
+            .expect("a former id is not an error")
+            .ignore;
+        assert_eq!(
+            ignore,
+            std::iter::once("nondeterministic_function".to_string()).collect()
+        );
+    }
+
     #[test]
     fn resolve_ignore_is_empty_without_config_or_cli() {
         let dir = tempdir().unwrap();
-        let ignore = resolve_ignore_in(dir.path(), None, Vec::new()).expect("no config is fine");
+        let ignore = resolve_settings_in(dir.path(), None, Vec::new())
+            .expect("no config is fine")
+            .ignore;
         assert!(ignore.is_empty());
     }
 
@@ -499,14 +1205,14 @@ where
         let args = Args::try_parse_from([
             "bqvalid",
             "--ignore",
-            "use_current_date,unnecessary_order_by",
+            "nondeterministic_function,unnecessary_order_by",
             "x.sql",
         ])
         .expect("parses");
         assert_eq!(
             args.ignore,
             vec![
-                "use_current_date".to_string(),
+                "nondeterministic_function".to_string(),
                 "unnecessary_order_by".to_string()
             ]
         );
@@ -525,7 +1231,7 @@ where
         let path = dir.path().join("custom.toml");
         fs::write(&path, "ignore = not-a-list").unwrap();
 
-        let err = resolve_ignore_in(dir.path(), Some(path), Vec::new());
+        let err = resolve_settings_in(dir.path(), Some(path), Vec::new());
         assert!(err.is_err(), "a malformed config must be a hard error");
     }
 
@@ -536,7 +1242,7 @@ where
         let dir = tempdir().unwrap();
         let missing = dir.path().join("does_not_exist.toml");
 
-        let err = resolve_ignore_in(dir.path(), Some(missing), Vec::new());
+        let err = resolve_settings_in(dir.path(), Some(missing), Vec::new());
         assert!(err.is_err(), "a missing explicit config must be an error");
     }
 
@@ -546,16 +1252,207 @@ where
         // or turned into an error: downstream it simply matches no rule.
         let dir = tempdir().unwrap();
 
-        let ignore = resolve_ignore_in(
+        let ignore = resolve_settings_in(
             dir.path(),
             None,
-            vec!["use_current_date".to_string(), "not_a_rule".to_string()],
+            vec![
+                "nondeterministic_function".to_string(),
+                "not_a_rule".to_string(),
+            ],
         )
-        .expect("unknown ids are not an error");
+        .expect("unknown ids are not an error")
+        .ignore;
 
-        let expected: HashSet<String> = ["use_current_date".to_string(), "not_a_rule".to_string()]
-            .into_iter()
-            .collect();
+        let expected: HashSet<String> = [
+            "nondeterministic_function".to_string(),
+            "not_a_rule".to_string(),
+        ]
+        .into_iter()
+        .collect();
         assert_eq!(ignore, expected, "unknown ids are retained, not dropped");
     }
+
+    #[test]
+    fn query_subcommand_is_parsed_before_files() {
+        let args = Args::try_parse_from(["bqvalid", "query", "ASTStar", "a.sql", "b.sql"])
+            .expect("parses");
+        match args.command {
+            Some(Command::Query { selector, files }) => {
+                assert_eq!(selector, "ASTStar");
+                assert_eq!(files, vec!["a.sql".to_string(), "b.sql".to_string()]);
+            }
+            other => panic!("expected the query subcommand, got {other:?}"),
+        }
+
+        let lint = Args::try_parse_from(["bqvalid", "a.sql"]).expect("parses");
+        assert!(lint.command.is_none());
+        assert_eq!(lint.files, vec!["a.sql".to_string()]);
+    }
+
+    #[test]
+    fn write_matches_prints_the_span_kind_and_text() {
+        let mut module = new_module().expect("googlesql module builds");
+        let selector = Selector::parse("ASTFunctionCall[name=REGEXP_CONTAINS]").unwrap();
+        let sql = "SELECT 1\nFROM t\nWHERE REGEXP_CONTAINS(b, 'y')";
+
+        let mut out = Vec::new();
+        write_matches(
+            &mut out,
+            &mut module,
+            &selector,
+            Some(Path::new("a.sql")),
+            sql,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "a.sql:3:7-3:30: ASTFunctionCall: REGEXP_CONTAINS(b, 'y')\n"
+        );
+    }
+
+    #[test]
+    fn format_match_elides_all_but_the_first_line() {
+        let mut module = new_module().expect("googlesql module builds");
+        let sql = "SELECT COUNT(\n  a\n) FROM t";
+        let ast = parse_script(&mut module, sql).remove(0);
+        let call = ast
+            .pre_order()
+            .into_iter()
+            .find(|n| n.kind() == "ASTFunctionCall")
+            .unwrap();
+
+        assert_eq!(
+            format_match(None, &call, sql),
+            "1:8-3:2: ASTFunctionCall: COUNT( ..."
+        );
+    }
+
+    #[test]
+    fn custom_rules_from_the_config_are_run() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("bqvalid.toml"),
+            r#"
+[[custom_rules]]
+id = "no_regexp_in_where"
+selector = "ASTWhereClause ASTFunctionCall[name=REGEXP_CONTAINS]"
+message = "Avoid REGEXP_CONTAINS in filters"
+"#,
+        )
+        .unwrap();
+
+        let settings = resolve_settings_in(dir.path(), None, Vec::new()).expect("loads config");
+        let rules = configured_rules(
+            &settings.config.rules,
+            &settings.config.custom_rules,
+            &settings.ignore,
+        )
+        .unwrap();
+        let mut module = new_module().expect("googlesql module builds");
+        let diagnostics = analyse_sql_googlesql(
+            &mut module,
+            "SELECT a FROM t WHERE REGEXP_CONTAINS(a, 'x')",
+            &rules,
+            &UdfCatalog::default(),
+            &SchemaCatalog::default(),
+        )
+        .0;
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].rule_id(), "no_regexp_in_where");
+    }
+
+    #[test]
+    fn udfs_from_the_udf_dir_and_the_script_are_both_known() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("aggs.sql"),
+            "CREATE AGGREGATE FUNCTION ds.shared_agg(x INT64) AS (SUM(x));",
+        )
+        .unwrap();
+        let udfs = load_udf_catalog(dir.path());
+        assert_eq!(udfs.len(), 1);
+
+        let mut module = new_module().expect("googlesql module builds");
+        let sql = "CREATE TEMP AGGREGATE FUNCTION local_agg(x INT64) AS (SUM(x));\n\
+                   SELECT k, ds.shared_agg(v), local_agg(v) FROM t GROUP BY k";
+        let schema = SchemaCatalog::default();
+        let diagnostics = analyse_sql_googlesql(&mut module, sql, &all_rules(), &udfs, &schema).0;
+        assert!(
+            diagnostics
+                .iter()
+                .all(|d| d.rule_id() != "invalid_group_by")
+        );
+    }
+
+    #[test]
+    fn update_stamps_reports_new_and_changed_targets_and_forgets_removed_ones() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.sql");
+        let b = dir.path().join("b.sql");
+        fs::write(&a, "SELECT 1").unwrap();
+        fs::write(&b, "SELECT 2").unwrap();
+        let mut stamps = HashMap::new();
+
+        let mut changed = update_stamps(&mut stamps, vec![a.clone(), b.clone()]);
+        changed.sort();
+        assert_eq!(changed, [a.clone(), b.clone()]);
+        assert!(update_stamps(&mut stamps, vec![a.clone(), b.clone()]).is_empty());
+
+        // A different length changes the stamp even within the mtime's
+        // resolution.
+        fs::write(&a, "SELECT 10").unwrap();
+        assert_eq!(
+            update_stamps(&mut stamps, vec![a.clone(), b.clone()]),
+            std::slice::from_ref(&a)
+        );
+
+        assert!(update_stamps(&mut stamps, vec![a.clone()]).is_empty());
+        assert!(!stamps.contains_key(&b));
+    }
+
+    #[test]
+    fn ast_subcommand_accepts_a_format_and_position() {
+        let args = Args::try_parse_from(["bqvalid", "ast", "--format", "sexp", "--at", "3:7", "-"])
+            .expect("parses");
+        match args.command {
+            Some(Command::Ast { file, format, at }) => {
+                assert_eq!(file, "-");
+                assert_eq!(format, DumpFormat::Sexp);
+                assert_eq!(at, Some((3, 7)));
+            }
+            other => panic!("expected the ast subcommand, got {other:?}"),
+        }
+    }
+
+    #[rstest]
+    #[case("3:7", Ok((3, 7)))]
+    #[case("3", Err("expected LINE:COLUMN, got 3".to_string()))]
+    #[case("0:1", Err("expected a positive number, got 0".to_string()))]
+    #[case("3:x", Err("expected a positive number, got x".to_string()))]
+    fn line_column_arguments_are_one_based(
+        #[case] arg: &str,
+        #[case] expected: Result<(usize, usize), String>,
+    ) {
+        assert_eq!(parse_line_column(arg), expected);
+    }
+
+    #[test]
+    fn rules_and_explain_subcommands_are_parsed() {
+        let args = Args::try_parse_from(["bqvalid", "rules", "--format", "json"]).expect("parses");
+        assert!(matches!(
+            args.command,
+            Some(Command::Rules {
+                format: ListFormat::Json
+            })
+        ));
+
+        let args = Args::try_parse_from(["bqvalid", "explain", "nondeterministic_function"])
+            .expect("parses");
+        match args.command {
+            Some(Command::Explain { rule_id }) => assert_eq!(rule_id, "nondeterministic_function"),
+            other => panic!("expected the explain subcommand, got {other:?}"),
+        }
+    }
 }
@@ -2,25 +2,60 @@
 //!
 //! `emit()` is the single entry point for both the stdin and files paths: it
 //! writes lint diagnostics to `out` in the selected format and the tool's own
-//! read failures to `err`. The `plain` format keeps the stdin/files
+//! read and parse failures to `err`. The `pretty` format (the default on a terminal)
+//! lives in [`pretty`]. The `plain` format keeps the stdin/files
 //! distinction via the `show_paths` flag (files prefix each line with the
 //! path; stdin does not). The `json`/`sarif` formats build an aggregated
-//! document for the whole run.
+//! document for the whole run, and the `checkstyle`/`junit` XML formats also
+//! report unreadable files, since the dashboards that ingest them never see
+//! stderr. So do the `html` and `markdown` reports, in [`html`] and
+//! [`markdown`].
 
-use crate::diagnostic::{Diagnostic, Severity};
+use crate::diagnostic::{ColumnUnit, Diagnostic, Severity};
+use crate::rules::Rule;
 use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap};
 use std::io::{self, Write};
+use std::ops::Range;
 use std::path::PathBuf;
+use std::time::Duration;
+
+mod html;
+mod markdown;
+mod pretty;
+mod statistics;
 
 /// Output format selected via `--format`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
 pub enum OutputFormat {
-    /// Human-readable `row:col: message` (optionally path-prefixed). Default.
+    /// Human-readable `row:col: message` (optionally path-prefixed). Default
+    /// when stdout is not a terminal.
     Plain,
+    /// Source excerpts with the offending range underlined, severities in
+    /// color and a per-rule summary. Default on a terminal.
+    Pretty,
     /// A single JSON document with a flat `diagnostics` array.
     Json,
     /// SARIF 2.1.0, for GitHub code scanning and editor integrations.
     Sarif,
+    /// Checkstyle XML, one `<file>` per input, for Jenkins and similar.
+    Checkstyle,
+    /// JUnit XML, one test case per input that fails when it has problems.
+    Junit,
+    /// GitHub Actions workflow commands, shown as inline annotations.
+    Github,
+    /// GitLab Code Quality JSON, shown inline on merge requests.
+    Gitlab,
+    /// reviewdog diagnostic JSON (rdjson), with ranges and fix suggestions.
+    Rdjson,
+    /// A self-contained HTML report grouped by directory, file and rule.
+    Html,
+    /// A Markdown report for pull request comments: a per-rule summary table
+    /// and collapsible per-file details.
+    Markdown,
+    /// Run statistics instead of diagnostics: file and problem counts, counts
+    /// per rule, the top offending files and parse/lint timing.
+    Summary,
 }
 
 /// One file's diagnostics, as seen by the machine-readable formatters. For the
@@ -32,31 +67,87 @@ pub struct FileDiagnostics<'a> {
 
 /// Result of analysing a single input. For the stdin path the `path` is a
 /// placeholder such as `<stdin>`. `read_error` is set when the file could not
-/// be read; in that case `diagnostics` is empty.
+/// be read; in that case `diagnostics` and `source` are empty.
 pub struct FileResult {
     pub path: PathBuf,
     pub diagnostics: Vec<Diagnostic>,
     pub read_error: Option<String>,
+    /// The analysed SQL, which the `pretty` format quotes.
+    pub source: String,
+    /// How the analysis went, for `--statistics`.
+    pub stats: FileStats,
+}
+
+/// Parse outcome and timings of analysing one input.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileStats {
+    /// The error that stopped the parser, if any; the statements before it are
+    /// still linted.
+    pub parse_error: Option<String>,
+    /// The result came from the cache, so the file was neither parsed nor
+    /// linted and its times are zero.
+    pub cached: bool,
+    pub parse_time: Duration,
+    pub lint_time: Duration,
+}
+
+/// How [`emit`] renders a run.
+pub struct EmitOptions<'a> {
+    pub format: OutputFormat,
+    /// The tool version reported by SARIF.
+    pub version: &'a str,
+    /// The rule set the run used; SARIF and rdjson describe the rules that
+    /// produced results from their metadata.
+    pub rules: &'a [Box<dyn Rule>],
+    /// Only affects the `plain` format: the files path prefixes each line with
+    /// the file path, while the stdin path (a single result) does not. The
+    /// other formats always carry the path.
+    pub show_paths: bool,
+    /// Color the `pretty` format with ANSI escapes.
+    pub color: bool,
+    /// The unit the columns were converted to (see [`convert_columns`]),
+    /// declared by SARIF as its `columnKind`.
+    pub column_unit: ColumnUnit,
+    /// Write the run statistics instead of the diagnostics: as JSON for the
+    /// `json` format and as the `summary` table otherwise.
+    pub statistics: bool,
+}
+
+/// Re-express the columns of every diagnostic in `results` in `unit`, using
+/// each result's source. Byte spans are left alone.
+pub fn convert_columns(results: &mut [FileResult], unit: ColumnUnit) {
+    if unit == ColumnUnit::Byte {
+        return;
+    }
+    for result in results {
+        let lines: Vec<&str> = result.source.split('\n').collect();
+        for diagnostic in &mut result.diagnostics {
+            diagnostic.convert_columns(&lines, unit);
+        }
+    }
 }
 
-/// Render `results` in the selected `format`.
+/// Render `results` as `options` select.
 ///
 /// Lint diagnostics go to `out` (stdout, pipeable), while the tool's own read
-/// failures go to `err` (stderr), so the two never mix on the same pipe.
-/// Returns `true` when any diagnostic or read error was seen, so the caller can
-/// pick the exit code.
-///
-/// `show_paths` only affects the `plain` format: the files path prefixes each
-/// line with the file path, while the stdin path (a single result) does not.
-/// The machine formats always carry the path per diagnostic.
+/// and parse failures go to `err` (stderr), so the two never mix on the same
+/// pipe. Returns `true` when any diagnostic or read error was seen, so the
+/// caller can pick the exit code.
 pub fn emit<O: Write, E: Write>(
     results: &[FileResult],
-    format: OutputFormat,
-    version: &str,
-    show_paths: bool,
+    options: &EmitOptions<'_>,
     out: &mut O,
     err: &mut E,
 ) -> io::Result<bool> {
+    let EmitOptions {
+        format,
+        version,
+        rules,
+        show_paths,
+        color,
+        column_unit,
+        statistics,
+    } = *options;
     let mut has_problem = false;
     for result in results {
         if let Some(read_error) = &result.read_error {
@@ -68,63 +159,89 @@ pub fn emit<O: Write, E: Write>(
             )?;
             has_problem = true;
         }
+        if let Some(parse_error) = &result.stats.parse_error {
+            writeln!(
+                err,
+                "{}: Error parsing SQL input: {}",
+                result.path.display(),
+                parse_error
+            )?;
+        }
         if !result.diagnostics.is_empty() {
             has_problem = true;
         }
     }
 
+    // `path.display()` yields a temporary, so materialize the path strings
+    // first and borrow them into the format views.
+    let paths: Vec<String> = results
+        .iter()
+        .map(|r| r.path.display().to_string())
+        .collect();
+    let views: Vec<FileDiagnostics> = results
+        .iter()
+        .zip(&paths)
+        .map(|(r, path)| FileDiagnostics {
+            path,
+            diagnostics: &r.diagnostics,
+        })
+        .collect();
+
+    if statistics || format == OutputFormat::Summary {
+        if format == OutputFormat::Json {
+            statistics::write_json(out, results)?;
+        } else {
+            statistics::write_table(out, results)?;
+        }
+        return Ok(has_problem);
+    }
+
     match format {
         OutputFormat::Plain => write_plain(out, results, show_paths)?,
-        OutputFormat::Json | OutputFormat::Sarif => {
-            // `path.display()` yields a temporary, so materialize the path
-            // strings first and borrow them into the format views.
-            let paths: Vec<String> = results
-                .iter()
-                .map(|r| r.path.display().to_string())
-                .collect();
-            let views: Vec<FileDiagnostics> = results
-                .iter()
-                .zip(&paths)
-                .map(|(r, path)| FileDiagnostics {
-                    path,
-                    diagnostics: &r.diagnostics,
-                })
-                .collect();
-            match format {
-                OutputFormat::Json => write_json(out, &views)?,
-                OutputFormat::Sarif => write_sarif(out, &views, version)?,
-                OutputFormat::Plain => {}
-            }
-        }
+        OutputFormat::Pretty => pretty::write_pretty(out, results, color)?,
+        OutputFormat::Json => write_json(out, &views)?,
+        OutputFormat::Sarif => write_sarif(out, &views, version, rules, column_unit)?,
+        OutputFormat::Checkstyle => write_checkstyle(out, results)?,
+        OutputFormat::Junit => write_junit(out, results)?,
+        OutputFormat::Github => write_github(out, &views)?,
+        OutputFormat::Gitlab => write_gitlab(out, &views)?,
+        OutputFormat::Rdjson => write_rdjson(out, &views, rules)?,
+        OutputFormat::Html => html::write_html(out, results)?,
+        OutputFormat::Markdown => markdown::write_markdown(out, results)?,
+        // Handled above.
+        OutputFormat::Summary => {}
     }
 
     Ok(has_problem)
 }
 
 /// Write lint diagnostics in the human-readable `plain` format. When
-/// `show_paths` is set each line is prefixed with the file path.
+/// `show_paths` is set each line is prefixed with the file path. A
+/// diagnostic's related locations, notes and help follow it on indented lines.
 fn write_plain<W: Write>(out: &mut W, results: &[FileResult], show_paths: bool) -> io::Result<()> {
     for result in results {
+        let prefix = if show_paths {
+            format!("{}: ", result.path.display())
+        } else {
+            String::new()
+        };
         for diagnostic in &result.diagnostics {
-            if show_paths {
-                writeln!(out, "{}: {}", result.path.display(), diagnostic)?;
-            } else {
-                writeln!(out, "{}", diagnostic)?;
+            writeln!(out, "{}{}", prefix, diagnostic)?;
+            for related in diagnostic.related() {
+                let (row, col) = related.start;
+                writeln!(out, "  {}{}:{}: {}", prefix, row, col, related.message)?;
+            }
+            for note in diagnostic.notes() {
+                writeln!(out, "  note: {}", note)?;
+            }
+            if let Some(help) = diagnostic.help() {
+                writeln!(out, "  help: {}", help)?;
             }
         }
     }
     Ok(())
 }
 
-/// Lowercase severity string shared by JSON (`severity`) and SARIF (`level`).
-/// SARIF levels are drawn from the same vocabulary (`warning`, `error`).
-const fn severity_str(severity: Severity) -> &'static str {
-    match severity {
-        Severity::Warning => "warning",
-        Severity::Error => "error",
-    }
-}
-
 /// Serialize `value` as pretty JSON followed by a trailing newline.
 fn write_json_value<W: Write>(out: &mut W, value: &Value) -> io::Result<()> {
     serde_json::to_writer_pretty(&mut *out, value).map_err(io::Error::other)?;
@@ -133,8 +250,11 @@ fn write_json_value<W: Write>(out: &mut W, value: &Value) -> io::Result<()> {
 
 /// Write all diagnostics as a single JSON document with a flat `diagnostics` array.
 ///
-/// Each entry carries its file path, rule id, severity and 1-based coordinates
-/// so CI tooling can consume them without parsing the plain text.
+/// Each entry carries its file path, rule id, severity, 1-based coordinates
+/// (`end_row`/`end_col` exclusive, `null` when unknown) and byte span
+/// (`start_byte`/`end_byte`), plus its `related` locations in the same shape,
+/// its `notes` and its `help` (`null` when none), so CI tooling can consume
+/// them without parsing the plain text.
 pub fn write_json<W: Write>(out: &mut W, files: &[FileDiagnostics]) -> io::Result<()> {
     let diagnostics: Vec<Value> = files
         .iter()
@@ -143,10 +263,25 @@ pub fn write_json<W: Write>(out: &mut W, files: &[FileDiagnostics]) -> io::Resul
                 json!({
                     "path": file.path,
                     "rule_id": d.rule_id(),
-                    "severity": severity_str(d.severity()),
+                    "severity": d.severity().as_str(),
                     "row": d.row(),
                     "col": d.col(),
+                    "end_row": d.end().map(|(row, _)| row),
+                    "end_col": d.end().map(|(_, col)| col),
+                    "start_byte": d.span().map(|span| span.start),
+                    "end_byte": d.span().map(|span| span.end),
                     "message": d.message(),
+                    "related": d.related().iter().map(|r| json!({
+                        "row": r.start.0,
+                        "col": r.start.1,
+                        "end_row": r.end.map(|(row, _)| row),
+                        "end_col": r.end.map(|(_, col)| col),
+                        "start_byte": r.span.as_ref().map(|span| span.start),
+                        "end_byte": r.span.as_ref().map(|span| span.end),
+                        "message": r.message,
+                    })).collect::<Vec<_>>(),
+                    "notes": d.notes(),
+                    "help": d.help(),
                 })
             })
         })
@@ -156,27 +291,65 @@ pub fn write_json<W: Write>(out: &mut W, files: &[FileDiagnostics]) -> io::Resul
 }
 
 /// Write all diagnostics as a SARIF 2.1.0 document. `version` is the tool
-/// version reported in `tool.driver.version`.
+/// version reported in `tool.driver.version`; `rules` supplies the metadata for
+/// the `tool.driver.rules` entries. `column_unit` becomes the run's
+/// `columnKind`; SARIF has no kind for byte columns, so it is left out for
+/// those.
 pub fn write_sarif<W: Write>(
     out: &mut W,
     files: &[FileDiagnostics],
     version: &str,
+    rules: &[Box<dyn Rule>],
+    column_unit: ColumnUnit,
 ) -> io::Result<()> {
     let results: Vec<Value> = files
         .iter()
         .flat_map(|file| {
             file.diagnostics.iter().map(move |d| {
-                json!({
+                let mut result = json!({
                     "ruleId": d.rule_id(),
-                    "level": severity_str(d.severity()),
+                    "level": d.severity().as_str(),
                     "message": { "text": d.message() },
                     "locations": [{
                         "physicalLocation": {
                             "artifactLocation": { "uri": file.path },
-                            "region": { "startLine": d.row(), "startColumn": d.col() },
+                            "region": sarif_region((d.row(), d.col()), d.end(), d.span()),
                         }
                     }],
-                })
+                });
+                let related: Vec<Value> = d
+                    .related()
+                    .iter()
+                    .enumerate()
+                    .map(|(id, r)| {
+                        json!({
+                            "id": id,
+                            "message": { "text": r.message },
+                            "physicalLocation": {
+                                "artifactLocation": { "uri": file.path },
+                                "region": sarif_region(r.start, r.end, r.span.clone()),
+                            },
+                        })
+                    })
+                    .collect();
+                if let Some(fields) = result.as_object_mut() {
+                    if !related.is_empty() {
+                        fields.insert("relatedLocations".to_string(), Value::Array(related));
+                    }
+                    // SARIF results have no slot for notes or help, so they go
+                    // in the property bag.
+                    let mut properties = serde_json::Map::new();
+                    if !d.notes().is_empty() {
+                        properties.insert("notes".to_string(), json!(d.notes()));
+                    }
+                    if let Some(help) = d.help() {
+                        properties.insert("help".to_string(), json!(help));
+                    }
+                    if !properties.is_empty() {
+                        fields.insert("properties".to_string(), Value::Object(properties));
+                    }
+                }
+                result
             })
         })
         .collect();
@@ -188,9 +361,9 @@ pub fn write_sarif<W: Write>(
         .collect();
     rule_ids.sort_unstable();
     rule_ids.dedup();
-    let rules: Vec<Value> = rule_ids.iter().map(|id| json!({ "id": id })).collect();
+    let rules: Vec<Value> = rule_ids.iter().map(|id| sarif_rule(id, rules)).collect();
 
-    let doc = json!({
+    let mut doc = json!({
         "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
         "version": "2.1.0",
         "runs": [{
@@ -205,14 +378,370 @@ pub fn write_sarif<W: Write>(
             "results": results,
         }],
     });
+    let column_kind = match column_unit {
+        ColumnUnit::Byte => None,
+        ColumnUnit::Utf16 => Some("utf16CodeUnits"),
+        ColumnUnit::Char => Some("unicodeCodePoints"),
+    };
+    if let (Some(kind), Some(run)) = (
+        column_kind,
+        doc.pointer_mut("/runs/0").and_then(Value::as_object_mut),
+    ) {
+        run.insert("columnKind".to_string(), json!(kind));
+    }
+
+    write_json_value(out, &doc)
+}
+
+/// A SARIF `region` from a start, an end when known and a byte span.
+fn sarif_region(
+    (line, column): (usize, usize),
+    end: Option<(usize, usize)>,
+    span: Option<Range<usize>>,
+) -> Value {
+    let mut region = json!({ "startLine": line, "startColumn": column });
+    if let Some(fields) = region.as_object_mut() {
+        if let Some((line, column)) = end {
+            fields.insert("endLine".to_string(), json!(line));
+            fields.insert("endColumn".to_string(), json!(column));
+        }
+        if let Some(span) = span {
+            fields.insert("byteOffset".to_string(), json!(span.start));
+            fields.insert("byteLength".to_string(), json!(span.len()));
+        }
+    }
+    region
+}
+
+/// The `tool.driver.rules` entry for `id`: descriptions, help link, default
+/// level (and whether the rule is on by default) and category tag from the
+/// rule's metadata, or just the id when no rule
+/// in the set has it.
+fn sarif_rule(id: &str, rules: &[Box<dyn Rule>]) -> Value {
+    let Some(rule) = rules.iter().find(|rule| rule.id() == id) else {
+        return json!({ "id": id });
+    };
+    let meta = rule.metadata();
+    let mut entry = json!({
+        "id": id,
+        "name": meta.name,
+        "shortDescription": { "text": meta.summary },
+        "fullDescription": { "text": meta.description },
+        "defaultConfiguration": {
+            "enabled": meta.default_enabled,
+            "level": meta.default_severity.as_str(),
+        },
+        "properties": { "tags": [meta.category.as_str()] },
+    });
+    if let (Some(url), Some(fields)) = (meta.doc_url, entry.as_object_mut()) {
+        fields.insert("helpUri".to_string(), json!(url));
+    }
+    entry
+}
+
+/// Write the results as a Checkstyle XML report: one `<file>` per result, with
+/// an `<error>` per diagnostic whose `source` is the rule id. An unreadable
+/// file gets a single `error`-severity entry with `source="bqvalid"`.
+pub fn write_checkstyle<W: Write>(out: &mut W, results: &[FileResult]) -> io::Result<()> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(out, r#"<checkstyle version="4.3">"#)?;
+    for result in results {
+        writeln!(
+            out,
+            r#"  <file name="{}">"#,
+            xml_escape(&result.path.display().to_string())
+        )?;
+        if let Some(read_error) = &result.read_error {
+            writeln!(
+                out,
+                r#"    <error line="1" severity="error" message="{}" source="bqvalid"/>"#,
+                xml_escape(&format!("Error reading file: {}", read_error))
+            )?;
+        }
+        for d in &result.diagnostics {
+            writeln!(
+                out,
+                r#"    <error line="{}" column="{}" severity="{}" message="{}" source="{}"/>"#,
+                d.row(),
+                d.col(),
+                d.severity().as_str(),
+                xml_escape(d.message()),
+                xml_escape(d.rule_id()),
+            )?;
+        }
+        writeln!(out, "  </file>")?;
+    }
+    writeln!(out, "</checkstyle>")
+}
+
+/// Write the results as a JUnit XML report with one test case per file. A file
+/// with diagnostics fails with one `row:col: [rule_id] message` line each; an
+/// unreadable file fails with the read error.
+pub fn write_junit<W: Write>(out: &mut W, results: &[FileResult]) -> io::Result<()> {
+    let tests = results.len();
+    let failures = results
+        .iter()
+        .filter(|r| r.read_error.is_some() || !r.diagnostics.is_empty())
+        .count();
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<testsuites name="bqvalid" tests="{}" failures="{}">"#,
+        tests, failures
+    )?;
+    writeln!(
+        out,
+        r#"  <testsuite name="bqvalid" tests="{}" failures="{}" errors="0">"#,
+        tests, failures
+    )?;
+    for result in results {
+        let name = xml_escape(&result.path.display().to_string());
+        if let Some(read_error) = &result.read_error {
+            writeln!(out, r#"    <testcase classname="bqvalid" name="{}">"#, name)?;
+            writeln!(
+                out,
+                r#"      <failure type="read_error" message="{}"/>"#,
+                xml_escape(&format!("Error reading file: {}", read_error))
+            )?;
+            writeln!(out, "    </testcase>")?;
+        } else if result.diagnostics.is_empty() {
+            writeln!(
+                out,
+                r#"    <testcase classname="bqvalid" name="{}"/>"#,
+                name
+            )?;
+        } else {
+            let count = result.diagnostics.len();
+            let body: Vec<String> = result
+                .diagnostics
+                .iter()
+                .map(|d| format!("{}:{}: [{}] {}", d.row(), d.col(), d.rule_id(), d.message()))
+                .collect();
+            writeln!(out, r#"    <testcase classname="bqvalid" name="{}">"#, name)?;
+            writeln!(
+                out,
+                r#"      <failure type="lint" message="{} problem{}">{}</failure>"#,
+                count,
+                if count == 1 { "" } else { "s" },
+                xml_escape(&body.join("\n"))
+            )?;
+            writeln!(out, "    </testcase>")?;
+        }
+    }
+    writeln!(out, "  </testsuite>")?;
+    writeln!(out, "</testsuites>")
+}
+
+/// Write one GitHub Actions `::error`/`::warning` workflow command per
+/// diagnostic, titled with the rule id, so a workflow run annotates the
+/// offending lines without code scanning.
+pub fn write_github<W: Write>(out: &mut W, files: &[FileDiagnostics]) -> io::Result<()> {
+    for file in files {
+        for d in file.diagnostics {
+            writeln!(
+                out,
+                "::{} file={},line={},col={},title={}::{}",
+                d.severity().as_str(),
+                github_property(file.path),
+                d.row(),
+                d.col(),
+                github_property(d.rule_id()),
+                github_data(d.message()),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Escape a workflow command's message.
+fn github_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escape a workflow command property value, which additionally cannot hold
+/// the `:` and `,` delimiters.
+fn github_property(text: &str) -> String {
+    github_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// Write all diagnostics as a GitLab Code Quality report: a JSON array with one
+/// issue per diagnostic. Errors map to `major` and warnings to `minor`.
+///
+/// The fingerprint leaves the position out, so a finding keeps it when lines
+/// are added above it and GitLab does not report it as fixed and new again.
+/// Identical findings in one file are told apart by their occurrence.
+pub fn write_gitlab<W: Write>(out: &mut W, files: &[FileDiagnostics]) -> io::Result<()> {
+    let mut issues = Vec::new();
+    for file in files {
+        let mut occurrences: HashMap<(&str, &str), usize> = HashMap::new();
+        for d in file.diagnostics {
+            let occurrence = occurrences.entry((d.rule_id(), d.message())).or_default();
+            *occurrence = occurrence.saturating_add(1);
+            issues.push(json!({
+                "description": d.message(),
+                "check_name": d.rule_id(),
+                "fingerprint": fingerprint(&[
+                    file.path,
+                    d.rule_id(),
+                    d.message(),
+                    &occurrence.to_string(),
+                ]),
+                "severity": match d.severity() {
+                    Severity::Error => "major",
+                    Severity::Warning => "minor",
+                },
+                "location": {
+                    "path": file.path,
+                    "lines": { "begin": d.row() },
+                },
+            }));
+        }
+    }
+
+    write_json_value(out, &Value::Array(issues))
+}
 
+/// A stable hex digest of `parts` (64-bit FNV-1a, parts separated by a NUL),
+/// so the same finding keeps its fingerprint across runs and toolchains.
+fn fingerprint(parts: &[&str]) -> String {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    let mut hash = OFFSET_BASIS;
+    for (i, part) in parts.iter().enumerate() {
+        let separator: &[u8] = if i == 0 { b"" } else { b"\0" };
+        for byte in separator.iter().chain(part.as_bytes()) {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(PRIME);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+/// Write all diagnostics as a reviewdog rdjson document. Each diagnostic's
+/// `code` carries the rule id (and its docs link when `rules` has one), its
+/// `range` spans the offending node, a rule's fixes become `suggestions` and
+/// its related locations `related_locations`.
+pub fn write_rdjson<W: Write>(
+    out: &mut W,
+    files: &[FileDiagnostics],
+    rules: &[Box<dyn Rule>],
+) -> io::Result<()> {
+    let diagnostics: Vec<Value> = files
+        .iter()
+        .flat_map(|file| {
+            file.diagnostics.iter().map(move |d| {
+                let mut code = json!({ "value": d.rule_id() });
+                let doc_url = rules
+                    .iter()
+                    .find(|rule| rule.id() == d.rule_id())
+                    .and_then(|rule| rule.metadata().doc_url);
+                if let (Some(url), Some(fields)) = (doc_url, code.as_object_mut()) {
+                    fields.insert("url".to_string(), json!(url));
+                }
+                let suggestions: Vec<Value> = d
+                    .suggestions()
+                    .iter()
+                    .map(|fix| {
+                        json!({
+                            "range": rdjson_range(fix.start, Some(fix.end)),
+                            "text": fix.text,
+                        })
+                    })
+                    .collect();
+                json!({
+                    "message": d.message(),
+                    "location": {
+                        "path": file.path,
+                        "range": rdjson_range((d.row(), d.col()), d.end()),
+                    },
+                    "severity": match d.severity() {
+                        Severity::Error => "ERROR",
+                        Severity::Warning => "WARNING",
+                    },
+                    "code": code,
+                    "suggestions": suggestions,
+                    "related_locations": d.related().iter().map(|r| json!({
+                        "message": r.message,
+                        "location": {
+                            "path": file.path,
+                            "range": rdjson_range(r.start, r.end),
+                        },
+                    })).collect::<Vec<_>>(),
+                })
+            })
+        })
+        .collect();
+
+    let doc = json!({
+        "source": {
+            "name": "bqvalid",
+            "url": "https://github.com/hirosassa/bqvalid",
+        },
+        "diagnostics": diagnostics,
+    });
     write_json_value(out, &doc)
 }
 
+/// An rdjson `Range` from 1-based positions; `end` is exclusive and omitted
+/// when unknown.
+fn rdjson_range(start: (usize, usize), end: Option<(usize, usize)>) -> Value {
+    let position = |(line, column): (usize, usize)| json!({ "line": line, "column": column });
+    match end {
+        Some(end) => json!({ "start": position(start), "end": position(end) }),
+        None => json!({ "start": position(start) }),
+    }
+}
+
+/// The number of errors and warnings each rule reported across `results`, by
+/// rule id.
+fn rule_counts(results: &[FileResult]) -> BTreeMap<&str, (usize, usize)> {
+    let mut counts: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for diagnostic in results.iter().flat_map(|result| &result.diagnostics) {
+        let (errors, warnings) = counts.entry(diagnostic.rule_id()).or_default();
+        let count = match diagnostic.severity() {
+            Severity::Error => errors,
+            Severity::Warning => warnings,
+        };
+        *count = count.saturating_add(1);
+    }
+    counts
+}
+
+/// `count` followed by `noun`, pluralized with an `s` unless `count` is one.
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("{} {}", count, noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+/// Escape `text` for use in XML attribute values and character data. Newlines
+/// are kept as character references so they survive attribute normalization.
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 #[cfg(test)]
 #[allow(clippy::unwrap_used, clippy::indexing_slicing, reason = "test code")]
 mod tests {
     use super::*;
+    use crate::diagnostic::{Related, Suggestion};
+    use crate::rules::all_rules;
 
     fn sample() -> Vec<Diagnostic> {
         vec![
@@ -241,7 +770,7 @@ mod tests {
 
     fn render_sarif(files: &[FileDiagnostics], version: &str) -> Value {
         let mut buf = Vec::new();
-        write_sarif(&mut buf, files, version).unwrap();
+        write_sarif(&mut buf, files, version, &all_rules(), ColumnUnit::Byte).unwrap();
         serde_json::from_slice(&buf).unwrap()
     }
 
@@ -360,6 +889,42 @@ mod tests {
         assert_eq!(ids, vec!["invalid_group_by", "use_current_date"]);
     }
 
+    #[test]
+    fn sarif_rules_carry_descriptions_help_and_default_level() {
+        let diags = vec![
+            Diagnostic::new("invalid_group_by", Severity::Error, 1, 1, "m".to_string()),
+            Diagnostic::new("not_a_rule", Severity::Warning, 1, 1, "m".to_string()),
+        ];
+        let files = vec![FileDiagnostics {
+            path: "a.sql",
+            diagnostics: &diags,
+        }];
+        let doc = render_sarif(&files, "1.2.3");
+
+        let rules = &doc["runs"][0]["tool"]["driver"]["rules"];
+        let group_by = &rules[0];
+        assert_eq!(group_by["id"], "invalid_group_by");
+        assert_eq!(
+            group_by["shortDescription"]["text"],
+            "A selected column is neither grouped by nor aggregated."
+        );
+        assert!(group_by["fullDescription"]["text"].is_string());
+        assert!(
+            group_by["helpUri"]
+                .as_str()
+                .unwrap()
+                .ends_with("docs/rules.md#invalid-group-by-usage")
+        );
+        assert_eq!(
+            group_by["defaultConfiguration"],
+            json!({ "enabled": true, "level": "error" })
+        );
+        assert_eq!(group_by["properties"]["tags"], json!(["correctness"]));
+
+        // An id the rule set does not know is still listed, without metadata.
+        assert_eq!(rules[1], json!({ "id": "not_a_rule" }));
+    }
+
     #[test]
     fn emit_plain_without_paths_writes_bare_diagnostics() {
         // The stdin path (show_paths = false) emits `row:col: message` with no
@@ -371,15 +936,23 @@ mod tests {
                 Diagnostic::new("test_rule", Severity::Warning, 2, 3, "second".to_string()),
             ],
             read_error: None,
+            source: String::new(),
+            stats: Default::default(),
         }];
 
         let mut out = Vec::new();
         let mut err = Vec::new();
         let has_problem = emit(
             &results,
-            OutputFormat::Plain,
-            "1.2.3",
-            false,
+            &EmitOptions {
+                format: OutputFormat::Plain,
+                version: "1.2.3",
+                rules: &all_rules(),
+                show_paths: false,
+                color: false,
+                column_unit: ColumnUnit::Byte,
+                statistics: false,
+            },
             &mut out,
             &mut err,
         )
@@ -412,11 +985,15 @@ mod tests {
                     "some warning".to_string(),
                 )],
                 read_error: None,
+                source: String::new(),
+                stats: Default::default(),
             },
             FileResult {
                 path: PathBuf::from("missing.sql"),
                 diagnostics: Vec::new(),
                 read_error: Some("no such file".to_string()),
+                source: String::new(),
+                stats: Default::default(),
             },
         ];
 
@@ -424,9 +1001,15 @@ mod tests {
         let mut err = Vec::new();
         let has_problem = emit(
             &results,
-            OutputFormat::Plain,
-            "1.2.3",
-            true,
+            &EmitOptions {
+                format: OutputFormat::Plain,
+                version: "1.2.3",
+                rules: &all_rules(),
+                show_paths: true,
+                color: false,
+                column_unit: ColumnUnit::Byte,
+                statistics: false,
+            },
             &mut out,
             &mut err,
         )
@@ -463,15 +1046,23 @@ mod tests {
             path: PathBuf::from("clean.sql"),
             diagnostics: Vec::new(),
             read_error: None,
+            source: String::new(),
+            stats: Default::default(),
         }];
 
         let mut out = Vec::new();
         let mut err = Vec::new();
         let has_problem = emit(
             &results,
-            OutputFormat::Plain,
-            "1.2.3",
-            true,
+            &EmitOptions {
+                format: OutputFormat::Plain,
+                version: "1.2.3",
+                rules: &all_rules(),
+                show_paths: true,
+                color: false,
+                column_unit: ColumnUnit::Byte,
+                statistics: false,
+            },
             &mut out,
             &mut err,
         )
@@ -482,6 +1073,44 @@ mod tests {
         assert!(err.is_empty());
     }
 
+    #[test]
+    fn emit_writes_parse_errors_to_stderr() {
+        let results = vec![FileResult {
+            path: PathBuf::from("broken.sql"),
+            diagnostics: Vec::new(),
+            read_error: None,
+            source: "SELECT FROM".to_string(),
+            stats: FileStats {
+                parse_error: Some("Syntax error: Unexpected keyword FROM".to_string()),
+                ..FileStats::default()
+            },
+        }];
+
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        emit(
+            &results,
+            &EmitOptions {
+                format: OutputFormat::Plain,
+                version: "1.2.3",
+                rules: &all_rules(),
+                show_paths: true,
+                color: false,
+                column_unit: ColumnUnit::Byte,
+                statistics: false,
+            },
+            &mut out,
+            &mut err,
+        )
+        .unwrap();
+
+        assert!(out.is_empty());
+        assert_eq!(
+            String::from_utf8(err).unwrap(),
+            "broken.sql: Error parsing SQL input: Syntax error: Unexpected keyword FROM\n"
+        );
+    }
+
     #[test]
     fn emit_json_writes_document_to_stdout_and_read_errors_to_stderr() {
         // A machine format aggregates diagnostics into a single JSON document on
@@ -498,11 +1127,15 @@ mod tests {
                     "some warning".to_string(),
                 )],
                 read_error: None,
+                source: String::new(),
+                stats: Default::default(),
             },
             FileResult {
                 path: PathBuf::from("missing.sql"),
                 diagnostics: Vec::new(),
                 read_error: Some("no such file".to_string()),
+                source: String::new(),
+                stats: Default::default(),
             },
         ];
 
@@ -510,9 +1143,15 @@ mod tests {
         let mut err = Vec::new();
         let has_problem = emit(
             &results,
-            OutputFormat::Json,
-            "1.2.3",
-            true,
+            &EmitOptions {
+                format: OutputFormat::Json,
+                version: "1.2.3",
+                rules: &all_rules(),
+                show_paths: true,
+                color: false,
+                column_unit: ColumnUnit::Byte,
+                statistics: false,
+            },
             &mut out,
             &mut err,
         )
@@ -545,15 +1184,23 @@ mod tests {
                 "bad".to_string(),
             )],
             read_error: None,
+            source: String::new(),
+            stats: Default::default(),
         }];
 
         let mut out = Vec::new();
         let mut err = Vec::new();
         let has_problem = emit(
             &results,
-            OutputFormat::Sarif,
-            "1.2.3",
-            true,
+            &EmitOptions {
+                format: OutputFormat::Sarif,
+                version: "1.2.3",
+                rules: &all_rules(),
+                show_paths: true,
+                color: false,
+                column_unit: ColumnUnit::Byte,
+                statistics: false,
+            },
             &mut out,
             &mut err,
         )
@@ -573,15 +1220,23 @@ mod tests {
             path: PathBuf::from("clean.sql"),
             diagnostics: Vec::new(),
             read_error: None,
+            source: String::new(),
+            stats: Default::default(),
         }];
 
         let mut out = Vec::new();
         let mut err = Vec::new();
         let has_problem = emit(
             &results,
-            OutputFormat::Json,
-            "1.2.3",
-            true,
+            &EmitOptions {
+                format: OutputFormat::Json,
+                version: "1.2.3",
+                rules: &all_rules(),
+                show_paths: true,
+                color: false,
+                column_unit: ColumnUnit::Byte,
+                statistics: false,
+            },
             &mut out,
             &mut err,
         )
@@ -593,4 +1248,421 @@ mod tests {
         assert!(doc["diagnostics"].as_array().unwrap().is_empty());
         assert!(err.is_empty());
     }
+
+    fn xml_results() -> Vec<FileResult> {
+        vec![
+            FileResult {
+                path: PathBuf::from("a&b.sql"),
+                diagnostics: sample(),
+                read_error: None,
+                source: String::new(),
+                stats: Default::default(),
+            },
+            FileResult {
+                path: PathBuf::from("clean.sql"),
+                diagnostics: Vec::new(),
+                read_error: None,
+                source: String::new(),
+                stats: Default::default(),
+            },
+            FileResult {
+                path: PathBuf::from("missing.sql"),
+                diagnostics: Vec::new(),
+                read_error: Some("no such file".to_string()),
+                source: String::new(),
+                stats: Default::default(),
+            },
+        ]
+    }
+
+    #[test]
+    fn checkstyle_lists_each_file_with_escaped_errors() {
+        let mut out = Vec::new();
+        write_checkstyle(&mut out, &xml_results()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<checkstyle"));
+        assert!(out.contains(r#"<file name="a&amp;b.sql">"#));
+        assert!(out.contains(
+            r#"<error line="2" column="3" severity="warning" message="Don&apos;t use CURRENT_DATE" source="use_current_date"/>"#
+        ));
+        assert!(
+            out.contains(r#"severity="error" message="Not in GROUP BY" source="invalid_group_by""#)
+        );
+        assert!(out.contains("<file name=\"clean.sql\">\n  </file>"));
+        assert!(out.contains(
+            r#"<error line="1" severity="error" message="Error reading file: no such file" source="bqvalid"/>"#
+        ));
+        assert!(out.ends_with("</checkstyle>\n"));
+    }
+
+    #[test]
+    fn junit_fails_files_with_diagnostics_or_read_errors() {
+        let mut out = Vec::new();
+        write_junit(&mut out, &xml_results()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains(r#"<testsuites name="bqvalid" tests="3" failures="2">"#));
+        assert!(out.contains(
+            "<failure type=\"lint\" message=\"2 problems\">\
+             2:3: [use_current_date] Don&apos;t use CURRENT_DATE&#10;\
+             5:1: [invalid_group_by] Not in GROUP BY</failure>"
+        ));
+        assert!(out.contains(r#"<testcase classname="bqvalid" name="clean.sql"/>"#));
+        assert!(out.contains(
+            r#"<failure type="read_error" message="Error reading file: no such file"/>"#
+        ));
+    }
+
+    #[test]
+    fn emit_checkstyle_flags_a_problem_and_still_reports_read_errors_on_stderr() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let has_problem = emit(
+            &xml_results(),
+            &EmitOptions {
+                format: OutputFormat::Checkstyle,
+                version: "1.2.3",
+                rules: &all_rules(),
+                show_paths: true,
+                color: false,
+                column_unit: ColumnUnit::Byte,
+                statistics: false,
+            },
+            &mut out,
+            &mut err,
+        )
+        .unwrap();
+
+        assert!(has_problem);
+        assert!(String::from_utf8(out).unwrap().contains("<checkstyle"));
+        assert!(
+            String::from_utf8(err)
+                .unwrap()
+                .contains("Error reading file")
+        );
+    }
+
+    #[test]
+    fn github_emits_one_escaped_workflow_command_per_diagnostic() {
+        let diags = vec![
+            Diagnostic::new(
+                "use_current_date",
+                Severity::Warning,
+                2,
+                3,
+                "100% bad\nreally".to_string(),
+            ),
+            Diagnostic::new("invalid_group_by", Severity::Error, 5, 1, "x".to_string()),
+        ];
+        let files = vec![FileDiagnostics {
+            path: "dir,1/a:b.sql",
+            diagnostics: &diags,
+        }];
+        let mut out = Vec::new();
+        write_github(&mut out, &files).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "::warning file=dir%2C1/a%3Ab.sql,line=2,col=3,title=use_current_date::100%25 bad%0Areally\n\
+             ::error file=dir%2C1/a%3Ab.sql,line=5,col=1,title=invalid_group_by::x\n"
+        );
+    }
+
+    #[test]
+    fn gitlab_emits_code_quality_issues_with_stable_unique_fingerprints() {
+        let diags = sample();
+        let files = vec![FileDiagnostics {
+            path: "a.sql",
+            diagnostics: &diags,
+        }];
+        let render = || {
+            let mut buf = Vec::new();
+            write_gitlab(&mut buf, &files).unwrap();
+            serde_json::from_slice::<Value>(&buf).unwrap()
+        };
+        let doc = render();
+
+        let issues = doc.as_array().unwrap();
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0]["check_name"], "use_current_date");
+        assert_eq!(issues[0]["description"], "Don't use CURRENT_DATE");
+        assert_eq!(issues[0]["severity"], "minor");
+        assert_eq!(issues[0]["location"]["path"], "a.sql");
+        assert_eq!(issues[0]["location"]["lines"]["begin"], 2);
+        assert_eq!(issues[1]["severity"], "major");
+
+        let fingerprint = issues[0]["fingerprint"].as_str().unwrap();
+        assert_eq!(fingerprint.len(), 16);
+        assert_ne!(issues[0]["fingerprint"], issues[1]["fingerprint"]);
+        assert_eq!(render(), doc, "fingerprints are stable across runs");
+    }
+
+    #[test]
+    fn gitlab_fingerprints_survive_moves_and_tell_repeats_apart() {
+        let fingerprints = |rows: &[usize]| {
+            let diags: Vec<Diagnostic> = rows
+                .iter()
+                .map(|&row| {
+                    Diagnostic::new(
+                        "use_current_date",
+                        Severity::Warning,
+                        row,
+                        1,
+                        "Don't use CURRENT_DATE".to_string(),
+                    )
+                })
+                .collect();
+            let files = vec![FileDiagnostics {
+                path: "a.sql",
+                diagnostics: &diags,
+            }];
+            let mut buf = Vec::new();
+            write_gitlab(&mut buf, &files).unwrap();
+            let doc = serde_json::from_slice::<Value>(&buf).unwrap();
+            doc.as_array()
+                .unwrap()
+                .iter()
+                .map(|issue| issue["fingerprint"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let before = fingerprints(&[2, 5]);
+        assert_ne!(before[0], before[1]);
+        assert_eq!(fingerprints(&[4, 9]), before);
+    }
+
+    #[test]
+    fn rdjson_carries_code_range_and_suggestions() {
+        let diags = vec![
+            Diagnostic::new(
+                "unnecessary_order_by",
+                Severity::Warning,
+                1,
+                34,
+                "Unnecessary ORDER BY".to_string(),
+            )
+            .with_end((1, 45))
+            .with_suggestion(Suggestion {
+                start: (1, 33),
+                end: (1, 45),
+                text: String::new(),
+            }),
+            Diagnostic::new("custom_rule", Severity::Error, 2, 1, "m".to_string()),
+        ];
+        let files = vec![FileDiagnostics {
+            path: "a.sql",
+            diagnostics: &diags,
+        }];
+        let mut buf = Vec::new();
+        write_rdjson(&mut buf, &files, &all_rules()).unwrap();
+        let doc: Value = serde_json::from_slice(&buf).unwrap();
+
+        assert_eq!(doc["source"]["name"], "bqvalid");
+        let first = &doc["diagnostics"][0];
+        assert_eq!(first["message"], "Unnecessary ORDER BY");
+        assert_eq!(first["severity"], "WARNING");
+        assert_eq!(first["location"]["path"], "a.sql");
+        assert_eq!(
+            first["location"]["range"],
+            json!({ "start": { "line": 1, "column": 34 }, "end": { "line": 1, "column": 45 } })
+        );
+        assert_eq!(first["code"]["value"], "unnecessary_order_by");
+        assert!(
+            first["code"]["url"]
+                .as_str()
+                .unwrap()
+                .ends_with("#unnecessary-order-by-in-cte-or-subquery")
+        );
+        assert_eq!(
+            first["suggestions"],
+            json!([{
+                "range": { "start": { "line": 1, "column": 33 }, "end": { "line": 1, "column": 45 } },
+                "text": "",
+            }])
+        );
+
+        // No end, no suggestions and no docs link for an unknown rule.
+        let second = &doc["diagnostics"][1];
+        assert_eq!(second["severity"], "ERROR");
+        assert_eq!(
+            second["location"]["range"],
+            json!({ "start": { "line": 2, "column": 1 } })
+        );
+        assert_eq!(second["code"], json!({ "value": "custom_rule" }));
+        assert_eq!(second["suggestions"], json!([]));
+    }
+
+    #[test]
+    fn sarif_regions_carry_the_end_and_byte_span_and_the_run_its_column_kind() {
+        let diags = vec![
+            Diagnostic::new("use_current_date", Severity::Warning, 2, 3, "m".to_string())
+                .with_end((2, 15))
+                .with_span(9..21),
+        ];
+        let files = vec![FileDiagnostics {
+            path: "a.sql",
+            diagnostics: &diags,
+        }];
+        let mut buf = Vec::new();
+        write_sarif(&mut buf, &files, "1.2.3", &all_rules(), ColumnUnit::Utf16).unwrap();
+        let doc: Value = serde_json::from_slice(&buf).unwrap();
+
+        assert_eq!(doc["runs"][0]["columnKind"], "utf16CodeUnits");
+        assert_eq!(
+            doc["runs"][0]["results"][0]["locations"][0]["physicalLocation"]["region"],
+            json!({
+                "startLine": 2,
+                "startColumn": 3,
+                "endLine": 2,
+                "endColumn": 15,
+                "byteOffset": 9,
+                "byteLength": 12,
+            })
+        );
+
+        // Byte columns have no SARIF column kind.
+        assert!(
+            render_sarif(&files, "1.2.3")["runs"][0]
+                .get("columnKind")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn json_carries_the_end_and_byte_span_or_nulls() {
+        let diags = vec![
+            Diagnostic::new("r", Severity::Warning, 2, 3, "m".to_string())
+                .with_end((2, 15))
+                .with_span(9..21),
+            Diagnostic::new("r", Severity::Warning, 1, 1, "m".to_string()),
+        ];
+        let files = vec![FileDiagnostics {
+            path: "a.sql",
+            diagnostics: &diags,
+        }];
+        let doc = render_json(&files);
+
+        let first = &doc["diagnostics"][0];
+        assert_eq!(first["end_row"], 2);
+        assert_eq!(first["end_col"], 15);
+        assert_eq!(first["start_byte"], 9);
+        assert_eq!(first["end_byte"], 21);
+        let second = &doc["diagnostics"][1];
+        assert!(second["end_row"].is_null() && second["start_byte"].is_null());
+    }
+
+    fn with_related() -> Vec<Diagnostic> {
+        vec![
+            Diagnostic::new("invalid_group_by", Severity::Error, 1, 14, "m".to_string())
+                .with_related(Related {
+                    start: (1, 26),
+                    end: Some((1, 39)),
+                    span: Some(25..38),
+                    message: "GROUP BY clause".to_string(),
+                })
+                .with_note("background")
+                .with_help("fix it"),
+        ]
+    }
+
+    #[test]
+    fn plain_lists_related_locations_notes_and_help_under_the_diagnostic() {
+        let results = vec![FileResult {
+            path: PathBuf::from("a.sql"),
+            diagnostics: with_related(),
+            read_error: None,
+            source: String::new(),
+            stats: Default::default(),
+        }];
+        let mut out = Vec::new();
+        write_plain(&mut out, &results, true).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "a.sql: 1:14: m\n  a.sql: 1:26: GROUP BY clause\n  note: background\n  help: fix it\n"
+        );
+    }
+
+    #[test]
+    fn related_locations_map_to_sarif_rdjson_and_json() {
+        let diags = with_related();
+        let files = vec![FileDiagnostics {
+            path: "a.sql",
+            diagnostics: &diags,
+        }];
+
+        let result = &render_sarif(&files, "1.2.3")["runs"][0]["results"][0];
+        assert_eq!(
+            result["relatedLocations"],
+            json!([{
+                "id": 0,
+                "message": { "text": "GROUP BY clause" },
+                "physicalLocation": {
+                    "artifactLocation": { "uri": "a.sql" },
+                    "region": {
+                        "startLine": 1,
+                        "startColumn": 26,
+                        "endLine": 1,
+                        "endColumn": 39,
+                        "byteOffset": 25,
+                        "byteLength": 13,
+                    },
+                },
+            }])
+        );
+        assert_eq!(
+            result["properties"],
+            json!({ "notes": ["background"], "help": "fix it" })
+        );
+
+        let mut buf = Vec::new();
+        write_rdjson(&mut buf, &files, &all_rules()).unwrap();
+        let doc: Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(
+            doc["diagnostics"][0]["related_locations"],
+            json!([{
+                "message": "GROUP BY clause",
+                "location": {
+                    "path": "a.sql",
+                    "range": { "start": { "line": 1, "column": 26 }, "end": { "line": 1, "column": 39 } },
+                },
+            }])
+        );
+
+        let entry = &render_json(&files)["diagnostics"][0];
+        assert_eq!(entry["related"][0]["col"], 26);
+        assert_eq!(entry["related"][0]["end_byte"], 38);
+        assert_eq!(entry["notes"], json!(["background"]));
+        assert_eq!(entry["help"], "fix it");
+
+        // Diagnostics without any leave the SARIF result alone.
+        let diags = sample();
+        let files = vec![FileDiagnostics {
+            path: "a.sql",
+            diagnostics: &diags,
+        }];
+        let result = &render_sarif(&files, "1.2.3")["runs"][0]["results"][0];
+        assert!(result.get("relatedLocations").is_none() && result.get("properties").is_none());
+    }
+
+    #[test]
+    fn convert_columns_uses_each_result_source() {
+        let mut results = vec![FileResult {
+            path: PathBuf::from("a.sql"),
+            diagnostics: vec![Diagnostic::new(
+                "r",
+                Severity::Warning,
+                1,
+                14,
+                "m".to_string(),
+            )],
+            read_error: None,
+            source: "SELECT 'é', x".to_string(),
+            stats: Default::default(),
+        }];
+        convert_columns(&mut results, ColumnUnit::Byte);
+        assert_eq!(results[0].diagnostics[0].col(), 14);
+        convert_columns(&mut results, ColumnUnit::Utf16);
+        assert_eq!(results[0].diagnostics[0].col(), 13);
+    }
 }
@@ -0,0 +1,301 @@
+//! The `html` format: a self-contained, single-file report for reading in a
+//! browser. It opens with the problem counts and a table of them per rule, then
+//! lists the findings grouped by directory, file and rule, each with the
+//! offending source line and its range highlighted. The stylesheet is inline so
+//! the report can be archived or attached as is.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::pretty::frame_range;
+use super::{FileResult, plural, rule_counts, xml_escape};
+use crate::diagnostic::{Diagnostic, Severity};
+
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2em; color: #1f2328; }
+h1 { font-size: 1.6em; }
+h2 { font-size: 1.3em; border-bottom: 1px solid #d0d7de; padding-bottom: .2em; }
+h3 { font-size: 1.1em; font-family: ui-monospace, monospace; }
+h4 { font-size: 1em; font-family: ui-monospace, monospace; margin: .8em 0 .4em; }
+.count { color: #656d76; font-weight: normal; font-size: .85em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #d0d7de; padding: .3em .8em; text-align: left; }
+td.number { text-align: right; }
+.diagnostic { margin: 0 0 1em 1em; }
+.location { font-family: ui-monospace, monospace; color: #656d76; }
+.severity { font-weight: bold; }
+.error .severity, .read-error { color: #cf222e; }
+.warning .severity { color: #9a6700; }
+pre { background: #f6f8fa; padding: .5em; margin: .3em 0; overflow-x: auto; }
+mark { background: #ffd8b5; }
+";
+
+/// Write `results` as a single HTML document. Files with neither diagnostics
+/// nor a read error are counted but not listed.
+pub fn write_html<W: Write>(out: &mut W, results: &[FileResult]) -> io::Result<()> {
+    let diagnostics = || results.iter().flat_map(|result| &result.diagnostics);
+    let total = diagnostics().count();
+    let errors = diagnostics()
+        .filter(|d| d.severity() == Severity::Error)
+        .count();
+    let with_problems: Vec<&FileResult> = results
+        .iter()
+        .filter(|r| r.read_error.is_some() || !r.diagnostics.is_empty())
+        .collect();
+
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, r#"<html lang="en">"#)?;
+    writeln!(out, "<head>")?;
+    writeln!(out, r#"<meta charset="utf-8">"#)?;
+    writeln!(out, "<title>bqvalid report</title>")?;
+    writeln!(out, "<style>\n{}</style>", STYLE)?;
+    writeln!(out, "</head>")?;
+    writeln!(out, "<body>")?;
+    writeln!(out, "<h1>bqvalid report</h1>")?;
+    writeln!(
+        out,
+        "<p>{} ({}, {}) in {} of {} scanned.</p>",
+        plural(total, "problem"),
+        plural(errors, "error"),
+        plural(total.saturating_sub(errors), "warning"),
+        plural(with_problems.len(), "file"),
+        plural(results.len(), "file"),
+    )?;
+
+    let counts = rule_counts(results);
+    if !counts.is_empty() {
+        writeln!(out, "<table>")?;
+        writeln!(
+            out,
+            "<tr><th>Rule</th><th>Errors</th><th>Warnings</th><th>Total</th></tr>"
+        )?;
+        for (rule_id, (errors, warnings)) in counts {
+            writeln!(
+                out,
+                r#"<tr><td><code>{}</code></td><td class="number">{}</td><td class="number">{}</td><td class="number">{}</td></tr>"#,
+                xml_escape(rule_id),
+                errors,
+                warnings,
+                errors.saturating_add(warnings),
+            )?;
+        }
+        writeln!(out, "</table>")?;
+    }
+
+    let mut directories: BTreeMap<String, Vec<&FileResult>> = BTreeMap::new();
+    for result in with_problems {
+        directories
+            .entry(directory_of(&result.path))
+            .or_default()
+            .push(result);
+    }
+    for (directory, files) in directories {
+        let count: usize = files.iter().map(|f| problem_count(f)).sum();
+        writeln!(out, "<section>")?;
+        writeln!(
+            out,
+            r#"<h2>{} <span class="count">{}</span></h2>"#,
+            xml_escape(&directory),
+            plural(count, "problem")
+        )?;
+        for file in files {
+            write_file(out, file)?;
+        }
+        writeln!(out, "</section>")?;
+    }
+
+    writeln!(out, "</body>")?;
+    writeln!(out, "</html>")
+}
+
+/// The directory a result is grouped under, `.` for a bare file name.
+fn directory_of(path: &Path) -> String {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.display().to_string(),
+        _ => ".".to_string(),
+    }
+}
+
+/// A file's diagnostics, plus one for an unreadable file.
+fn problem_count(result: &FileResult) -> usize {
+    result
+        .diagnostics
+        .len()
+        .saturating_add(usize::from(result.read_error.is_some()))
+}
+
+fn write_file<W: Write>(out: &mut W, result: &FileResult) -> io::Result<()> {
+    writeln!(out, "<section>")?;
+    writeln!(
+        out,
+        r#"<h3>{} <span class="count">{}</span></h3>"#,
+        xml_escape(&result.path.display().to_string()),
+        plural(problem_count(result), "problem")
+    )?;
+    if let Some(read_error) = &result.read_error {
+        writeln!(
+            out,
+            r#"<p class="read-error">Error reading file: {}</p>"#,
+            xml_escape(read_error)
+        )?;
+    }
+
+    let mut rules: BTreeMap<&str, Vec<&Diagnostic>> = BTreeMap::new();
+    for diagnostic in &result.diagnostics {
+        rules
+            .entry(diagnostic.rule_id())
+            .or_default()
+            .push(diagnostic);
+    }
+    let lines: Vec<&str> = result.source.lines().collect();
+    for (rule_id, diagnostics) in rules {
+        writeln!(
+            out,
+            r#"<h4>{} <span class="count">{}</span></h4>"#,
+            xml_escape(rule_id),
+            diagnostics.len()
+        )?;
+        for diagnostic in diagnostics {
+            write_diagnostic(out, (&result.source, &lines), diagnostic)?;
+        }
+    }
+    writeln!(out, "</section>")
+}
+
+fn write_diagnostic<W: Write>(
+    out: &mut W,
+    (source, lines): (&str, &[&str]),
+    diagnostic: &Diagnostic,
+) -> io::Result<()> {
+    let severity = diagnostic.severity().as_str();
+    writeln!(out, r#"<div class="diagnostic {}">"#, severity)?;
+    writeln!(
+        out,
+        r#"<p><span class="location">{}:{}</span> <span class="severity">{}</span> {}</p>"#,
+        diagnostic.row(),
+        diagnostic.col(),
+        severity,
+        xml_escape(diagnostic.message())
+    )?;
+    if let Some(snippet) = snippet(source, lines, diagnostic) {
+        writeln!(out, "<pre><code>{}</code></pre>", snippet)?;
+    }
+    writeln!(out, "</div>")
+}
+
+/// The escaped first line of `diagnostic`'s range with the range marked, or
+/// `None` when the line is not in the source.
+fn snippet(source: &str, lines: &[&str], diagnostic: &Diagnostic) -> Option<String> {
+    let range = frame_range(
+        source,
+        diagnostic.span(),
+        (diagnostic.row(), diagnostic.col()),
+        diagnostic.end(),
+    );
+    let (row, col) = range.start;
+    let line = lines.get(row.checked_sub(1)?)?;
+    let from = col.saturating_sub(1).min(line.len());
+    let to = if range.end.0 == row {
+        range.end.1.saturating_sub(1).clamp(from, line.len())
+    } else {
+        line.len()
+    };
+    match (line.get(..from), line.get(from..to), line.get(to..)) {
+        (Some(before), Some(marked), Some(after)) => Some(format!(
+            "{}<mark>{}</mark>{}",
+            xml_escape(before),
+            xml_escape(marked),
+            xml_escape(after)
+        )),
+        // A column inside a multibyte character: quote the line unmarked.
+        _ => Some(xml_escape(line)),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, reason = "test code")]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn render(results: &[FileResult]) -> String {
+        let mut out = Vec::new();
+        write_html(&mut out, results).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn result(path: &str, source: &str, diagnostics: Vec<Diagnostic>) -> FileResult {
+        FileResult {
+            path: PathBuf::from(path),
+            diagnostics,
+            read_error: None,
+            source: source.to_string(),
+            stats: Default::default(),
+        }
+    }
+
+    #[test]
+    fn groups_by_directory_file_and_rule_with_highlighted_snippets() {
+        let results = vec![
+            result(
+                "sql/b.sql",
+                "SELECT a < 1\nFROM t",
+                vec![
+                    Diagnostic::new("use_current_date", Severity::Warning, 1, 8, "m".to_string())
+                        .with_end((1, 9)),
+                ],
+            ),
+            result(
+                "a.sql",
+                "SELECT 1",
+                vec![Diagnostic::new(
+                    "invalid_group_by",
+                    Severity::Error,
+                    1,
+                    8,
+                    "x < y".to_string(),
+                )],
+            ),
+            result("sql/clean.sql", "SELECT 1", Vec::new()),
+        ];
+        let html = render(&results);
+
+        assert!(html.starts_with("<!DOCTYPE html>\n"));
+        assert!(html.contains("<style>"));
+        assert!(
+            html.contains("<p>2 problems (1 error, 1 warning) in 2 files of 3 files scanned.</p>")
+        );
+        assert!(html.contains(
+            r#"<tr><td><code>invalid_group_by</code></td><td class="number">1</td><td class="number">0</td><td class="number">1</td></tr>"#
+        ));
+        // Directories are sorted, and a bare file name lands under `.`.
+        let root = html
+            .find(r#"<h2>. <span class="count">1 problem</span></h2>"#)
+            .unwrap();
+        let sql = html
+            .find(r#"<h2>sql <span class="count">1 problem</span></h2>"#)
+            .unwrap();
+        assert!(root < sql);
+        assert!(html.contains(r#"<h3>sql/b.sql <span class="count">1 problem</span></h3>"#));
+        assert!(html.contains(r#"<h4>use_current_date <span class="count">1</span></h4>"#));
+        assert!(!html.contains("clean.sql"));
+        // Messages and source are escaped, and the range is marked.
+        assert!(html.contains(r#"<span class="severity">error</span> x &lt; y</p>"#));
+        assert!(html.contains("<pre><code>SELECT <mark>a</mark> &lt; 1</code></pre>"));
+        assert!(html.contains(r#"<div class="diagnostic error">"#));
+    }
+
+    #[test]
+    fn lists_unreadable_files() {
+        let results = vec![FileResult {
+            path: PathBuf::from("missing.sql"),
+            diagnostics: Vec::new(),
+            read_error: Some("no such file".to_string()),
+            source: String::new(),
+            stats: Default::default(),
+        }];
+        let html = render(&results);
+        assert!(html.contains(r#"<p class="read-error">Error reading file: no such file</p>"#));
+    }
+}
@@ -0,0 +1,206 @@
+//! The `markdown` format, sized for a pull request comment: the problem counts,
+//! a table of them per rule, then one collapsible `<details>` block per file
+//! listing its findings.
+//!
+//! ```text
+//! ## bqvalid
+//!
+//! **1 problem** (0 errors, 1 warning) in 1 of 2 files.
+//!
+//! | Rule | Errors | Warnings | Total |
+//! | --- | ---: | ---: | ---: |
+//! | `nondeterministic_function` | 0 | 1 | 1 |
+//!
+//! <details>
+//! <summary><code>sql/report.sql</code>: 1 problem</summary>
+//!
+//! | Location | Severity | Rule | Message |
+//! | --- | --- | --- | --- |
+//! | 2:3 | warning | `nondeterministic_function` | CURRENT_DATE is used! |
+//!
+//! </details>
+//! ```
+
+use std::io::{self, Write};
+
+use super::{FileResult, plural, rule_counts, xml_escape};
+use crate::diagnostic::Severity;
+
+/// Write `results` as a Markdown report. Files with neither diagnostics nor a
+/// read error are counted but not listed.
+pub fn write_markdown<W: Write>(out: &mut W, results: &[FileResult]) -> io::Result<()> {
+    let diagnostics = || results.iter().flat_map(|result| &result.diagnostics);
+    let total = diagnostics().count();
+    let errors = diagnostics()
+        .filter(|d| d.severity() == Severity::Error)
+        .count();
+    let with_problems: Vec<&FileResult> = results
+        .iter()
+        .filter(|r| r.read_error.is_some() || !r.diagnostics.is_empty())
+        .collect();
+
+    writeln!(out, "## bqvalid")?;
+    writeln!(out)?;
+    if with_problems.is_empty() {
+        writeln!(out, "No problems in {}.", plural(results.len(), "file"))?;
+        return Ok(());
+    }
+    writeln!(
+        out,
+        "**{}** ({}, {}) in {} of {}.",
+        plural(total, "problem"),
+        plural(errors, "error"),
+        plural(total.saturating_sub(errors), "warning"),
+        with_problems.len(),
+        plural(results.len(), "file"),
+    )?;
+
+    let counts = rule_counts(results);
+    if !counts.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "| Rule | Errors | Warnings | Total |")?;
+        writeln!(out, "| --- | ---: | ---: | ---: |")?;
+        for (rule_id, (errors, warnings)) in counts {
+            writeln!(
+                out,
+                "| `{}` | {} | {} | {} |",
+                rule_id,
+                errors,
+                warnings,
+                errors.saturating_add(warnings)
+            )?;
+        }
+    }
+
+    for result in with_problems {
+        let path = xml_escape(&result.path.display().to_string());
+        let count = result.diagnostics.len();
+        writeln!(out)?;
+        writeln!(out, "<details>")?;
+        if let Some(read_error) = &result.read_error {
+            writeln!(out, "<summary><code>{}</code>: unreadable</summary>", path)?;
+            writeln!(out)?;
+            writeln!(out, "Error reading file: {}", cell(read_error))?;
+        } else {
+            writeln!(
+                out,
+                "<summary><code>{}</code>: {}</summary>",
+                path,
+                plural(count, "problem")
+            )?;
+            writeln!(out)?;
+            writeln!(out, "| Location | Severity | Rule | Message |")?;
+            writeln!(out, "| --- | --- | --- | --- |")?;
+            for d in &result.diagnostics {
+                writeln!(
+                    out,
+                    "| {}:{} | {} | `{}` | {} |",
+                    d.row(),
+                    d.col(),
+                    d.severity().as_str(),
+                    d.rule_id(),
+                    cell(d.message())
+                )?;
+            }
+        }
+        writeln!(out)?;
+        writeln!(out, "</details>")?;
+    }
+    Ok(())
+}
+
+/// Make `text` safe inside a table cell: HTML is escaped (the cell sits in a
+/// `<details>` block, where GitHub renders HTML), pipes would end the cell and
+/// newlines the row.
+fn cell(text: &str) -> String {
+    xml_escape(text)
+        .replace("&#10;", "<br>")
+        .replace('|', "\\|")
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, reason = "test code")]
+mod tests {
+    use super::*;
+    use crate::diagnostic::Diagnostic;
+    use std::path::PathBuf;
+
+    fn render(results: &[FileResult]) -> String {
+        let mut out = Vec::new();
+        write_markdown(&mut out, results).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn summarizes_per_rule_and_details_each_file() {
+        let results = vec![
+            FileResult {
+                path: PathBuf::from("sql/report.sql"),
+                diagnostics: vec![Diagnostic::new(
+                    "nondeterministic_function",
+                    Severity::Warning,
+                    2,
+                    3,
+                    "CURRENT_DATE is used!".to_string(),
+                )],
+                read_error: None,
+                source: String::new(),
+                stats: Default::default(),
+            },
+            FileResult {
+                path: PathBuf::from("sql/clean.sql"),
+                diagnostics: Vec::new(),
+                read_error: None,
+                source: String::new(),
+                stats: Default::default(),
+            },
+        ];
+        assert_eq!(
+            render(&results),
+            "## bqvalid\n\
+             \n\
+             **1 problem** (0 errors, 1 warning) in 1 of 2 files.\n\
+             \n\
+             | Rule | Errors | Warnings | Total |\n\
+             | --- | ---: | ---: | ---: |\n\
+             | `nondeterministic_function` | 0 | 1 | 1 |\n\
+             \n\
+             <details>\n\
+             <summary><code>sql/report.sql</code>: 1 problem</summary>\n\
+             \n\
+             | Location | Severity | Rule | Message |\n\
+             | --- | --- | --- | --- |\n\
+             | 2:3 | warning | `nondeterministic_function` | CURRENT_DATE is used! |\n\
+             \n\
+             </details>\n"
+        );
+    }
+
+    #[test]
+    fn escapes_cells_and_lists_unreadable_files() {
+        assert_eq!(cell("a | b <c>\nd"), "a \\| b &lt;c&gt;<br>d");
+
+        let results = vec![FileResult {
+            path: PathBuf::from("missing.sql"),
+            diagnostics: Vec::new(),
+            read_error: Some("no such file".to_string()),
+            source: String::new(),
+            stats: Default::default(),
+        }];
+        let out = render(&results);
+        assert!(out.contains("<summary><code>missing.sql</code>: unreadable</summary>"));
+        assert!(out.contains("Error reading file: no such file\n"));
+    }
+
+    #[test]
+    fn a_clean_run_says_so() {
+        let results = vec![FileResult {
+            path: PathBuf::from("a.sql"),
+            diagnostics: Vec::new(),
+            read_error: None,
+            source: String::new(),
+            stats: Default::default(),
+        }];
+        assert_eq!(render(&results), "## bqvalid\n\nNo problems in 1 file.\n");
+    }
+}
@@ -0,0 +1,481 @@
+//! The `pretty` format: each diagnostic as a header naming its severity and
+//! rule, the location, and a code frame quoting the offending source lines with
+//! the reported range underlined, followed by a summary of the counts per
+//! severity and per rule.
+//!
+//! ```text
+//! warning[nondeterministic_function]: CURRENT_DATE is used!
+//!  --> sql/report.sql:2:3
+//!   |
+//! 2 |   current_date,
+//!   |   ^^^^^^^^^^^^
+//! ```
+//!
+//! The location line shows the columns in the unit they were reported in; the
+//! underline is placed from the diagnostic's byte span when it has one (and
+//! from its columns, taken as bytes, otherwise), then laid out in characters
+//! so it stays aligned under multibyte text, with tabs before it kept so it
+//! lines up in the terminal too.
+//!
+//! Related locations are quoted after the primary range, underlined with `-`
+//! and labelled with their message, and notes and help close the frame:
+//!
+//! ```text
+//! error[invalid_group_by]: Column 'col2' must appear in the GROUP BY clause ...
+//!  --> a.sql:1:14
+//!   |
+//! 1 | SELECT col1, col2 FROM t GROUP BY col1
+//!   |              ^^^^
+//!   |
+//! 1 | SELECT col1, col2 FROM t GROUP BY col1
+//!   |                          ------------- GROUP BY clause
+//!   = help: add 'col2' to the GROUP BY clause ...
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use super::{FileResult, plural};
+use crate::diagnostic::{Diagnostic, Severity};
+
+/// Lines of a multi-line range quoted before the frame is cut short.
+const MAX_FRAME_LINES: usize = 5;
+
+const RED: &str = "1;31";
+const YELLOW: &str = "1;33";
+const BLUE: &str = "1;34";
+const BOLD: &str = "1";
+
+/// Write `results` in the `pretty` format, with ANSI colors when `color` is
+/// set. Nothing is written for a clean run.
+pub fn write_pretty<W: Write>(out: &mut W, results: &[FileResult], color: bool) -> io::Result<()> {
+    let paint = Paint { color };
+    for result in results {
+        let lines: Vec<&str> = result.source.lines().collect();
+        for diagnostic in &result.diagnostics {
+            write_diagnostic(
+                out,
+                &paint,
+                &result.path.display().to_string(),
+                (&result.source, &lines),
+                diagnostic,
+            )?;
+        }
+    }
+    write_summary(out, &paint, results)
+}
+
+/// Wraps text in ANSI escapes, or leaves it alone when colors are off.
+struct Paint {
+    color: bool,
+}
+
+impl Paint {
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", style, text)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+const fn severity_style(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => RED,
+        Severity::Warning => YELLOW,
+    }
+}
+
+fn write_diagnostic<W: Write>(
+    out: &mut W,
+    paint: &Paint,
+    path: &str,
+    (source, lines): (&str, &[&str]),
+    diagnostic: &Diagnostic,
+) -> io::Result<()> {
+    let style = severity_style(diagnostic.severity());
+    writeln!(
+        out,
+        "{}: {}",
+        paint.paint(
+            style,
+            &format!(
+                "{}[{}]",
+                diagnostic.severity().as_str(),
+                diagnostic.rule_id()
+            )
+        ),
+        paint.paint(BOLD, diagnostic.message()),
+    )?;
+
+    let primary = frame_range(
+        source,
+        diagnostic.span(),
+        (diagnostic.row(), diagnostic.col()),
+        diagnostic.end(),
+    );
+    let related: Vec<_> = diagnostic
+        .related()
+        .iter()
+        .map(|related| {
+            (
+                frame_range(source, related.span.clone(), related.start, related.end),
+                related.message.as_str(),
+            )
+        })
+        .collect();
+    let gutter = std::iter::once(&primary)
+        .chain(related.iter().map(|(range, _)| range))
+        .map(|range| range.last_row().to_string().len())
+        .max()
+        .unwrap_or(1);
+    let bar = paint.paint(BLUE, "|");
+
+    writeln!(
+        out,
+        "{:gutter$}{} {}:{}:{}",
+        "",
+        paint.paint(BLUE, "-->"),
+        path,
+        diagnostic.row(),
+        diagnostic.col()
+    )?;
+    writeln!(out, "{:gutter$} {}", "", bar)?;
+    let frame = Frame {
+        paint,
+        lines,
+        gutter,
+    };
+    frame.write(out, &primary, '^', style, None)?;
+    for (range, message) in &related {
+        writeln!(out, "{:gutter$} {}", "", bar)?;
+        frame.write(out, range, '-', BLUE, Some(message))?;
+    }
+    let notes = diagnostic
+        .notes()
+        .iter()
+        .map(|note| ("note", note.as_str()));
+    for (kind, text) in notes.chain(diagnostic.help().map(|help| ("help", help))) {
+        writeln!(
+            out,
+            "{:gutter$} {} {}: {}",
+            "",
+            paint.paint(BLUE, "="),
+            paint.paint(BOLD, kind),
+            text
+        )?;
+    }
+    writeln!(out)
+}
+
+/// A range of source to quote: 1-based rows and byte columns, end exclusive.
+pub(super) struct FrameRange {
+    pub(super) start: (usize, usize),
+    pub(super) end: (usize, usize),
+}
+
+impl FrameRange {
+    /// The last row quoted, once long ranges are cut short.
+    fn last_row(&self) -> usize {
+        self.end.0.min(
+            self.start
+                .0
+                .saturating_add(MAX_FRAME_LINES.saturating_sub(1)),
+        )
+    }
+}
+
+/// The range to quote for a location: from its byte span when it has one, and
+/// from its columns, taken as bytes, otherwise. Without a known end, the single
+/// character at the start.
+pub(super) fn frame_range(
+    source: &str,
+    span: Option<std::ops::Range<usize>>,
+    start: (usize, usize),
+    end: Option<(usize, usize)>,
+) -> FrameRange {
+    let span = span.and_then(|span| {
+        Some((
+            byte_position(source, span.start)?,
+            byte_position(source, span.end)?,
+        ))
+    });
+    let ((row, col), end) = span.map_or((start, end), |(start, end)| (start, Some(end)));
+    let end = end
+        .filter(|&end| end > (row, col))
+        .unwrap_or((row, col.saturating_add(1)));
+    FrameRange {
+        start: (row, col),
+        end,
+    }
+}
+
+/// Writes the quoted lines of a code frame.
+struct Frame<'a> {
+    paint: &'a Paint,
+    lines: &'a [&'a str],
+    gutter: usize,
+}
+
+impl Frame<'_> {
+    /// Quote the lines of `range`, underlining it with `marker` in `style` and
+    /// ending the last underline with `label`.
+    fn write<W: Write>(
+        &self,
+        out: &mut W,
+        range: &FrameRange,
+        marker: char,
+        style: &str,
+        label: Option<&str>,
+    ) -> io::Result<()> {
+        let Self {
+            paint,
+            lines,
+            gutter,
+        } = *self;
+        let ((row, col), (end_row, end_col)) = (range.start, range.end);
+        let last_row = range.last_row();
+        let bar = paint.paint(BLUE, "|");
+        for line_no in row..=last_row {
+            let Some(line) = line_no.checked_sub(1).and_then(|i| lines.get(i)) else {
+                break;
+            };
+            writeln!(
+                out,
+                "{} {} {}",
+                paint.paint(BLUE, &format!("{:>gutter$}", line_no)),
+                bar,
+                line
+            )?;
+            let from = if line_no == row {
+                col.saturating_sub(1)
+            } else {
+                0
+            };
+            let to = if line_no == end_row {
+                end_col.saturating_sub(1)
+            } else {
+                line.len()
+            };
+            let (indent, width) = underline(line, from, to);
+            if width > 0 || line_no == row {
+                let mut marks = marker.to_string().repeat(width.max(1));
+                if let (Some(label), true) = (label, line_no == last_row) {
+                    marks = format!("{} {}", marks, label);
+                }
+                writeln!(
+                    out,
+                    "{:gutter$} {} {}{}",
+                    "",
+                    bar,
+                    indent,
+                    paint.paint(style, &marks)
+                )?;
+            }
+        }
+        if last_row < end_row {
+            writeln!(out, "{:gutter$} {} ...", "", bar)?;
+        }
+        Ok(())
+    }
+}
+
+/// The 1-based `(row, byte column)` of byte `offset` in `source`, or `None`
+/// when the offset is out of range or inside a character.
+fn byte_position(source: &str, offset: usize) -> Option<(usize, usize)> {
+    let before = source.get(..offset)?;
+    let line_start = before.rfind('\n').map_or(0, |i| i.saturating_add(1));
+    let row = before.matches('\n').count().saturating_add(1);
+    Some((row, offset.saturating_sub(line_start).saturating_add(1)))
+}
+
+/// The whitespace that positions an underline under bytes `from..to` of
+/// `line`, and the underline's width in characters. Offsets past the end of
+/// the line or inside a character are clamped.
+fn underline(line: &str, from: usize, to: usize) -> (String, usize) {
+    let char_start = |byte: usize| {
+        line.char_indices()
+            .map(|(i, _)| i)
+            .find(|&i| i >= byte)
+            .unwrap_or(line.len())
+    };
+    let from = char_start(from);
+    let to = char_start(to).max(from);
+    let indent = line
+        .get(..from)
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let width = line.get(from..to).unwrap_or_default().chars().count();
+    (indent, width)
+}
+
+/// The footer: the total with its split by severity, then one line per rule.
+fn write_summary<W: Write>(out: &mut W, paint: &Paint, results: &[FileResult]) -> io::Result<()> {
+    let diagnostics = || results.iter().flat_map(|result| &result.diagnostics);
+    let total = diagnostics().count();
+    if total == 0 {
+        return Ok(());
+    }
+    let errors = diagnostics()
+        .filter(|d| d.severity() == Severity::Error)
+        .count();
+    let warnings = total.saturating_sub(errors);
+    let mut per_rule: BTreeMap<&str, usize> = BTreeMap::new();
+    for diagnostic in diagnostics() {
+        let count = per_rule.entry(diagnostic.rule_id()).or_insert(0);
+        *count = count.saturating_add(1);
+    }
+
+    writeln!(
+        out,
+        "{} ({}, {})",
+        paint.paint(BOLD, &plural(total, "problem")),
+        paint.paint(RED, &plural(errors, "error")),
+        paint.paint(YELLOW, &plural(warnings, "warning")),
+    )?;
+    let width = per_rule.keys().map(|id| id.len()).max().unwrap_or(0);
+    for (rule_id, count) in per_rule {
+        writeln!(out, "  {:width$}  {}", rule_id, count)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::indexing_slicing,
+    clippy::arithmetic_side_effects,
+    reason = "test code"
+)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::Related;
+    use std::path::PathBuf;
+
+    fn render(source: &str, diagnostics: Vec<Diagnostic>, color: bool) -> String {
+        let results = vec![FileResult {
+            path: PathBuf::from("a.sql"),
+            diagnostics,
+            read_error: None,
+            source: source.to_string(),
+            stats: Default::default(),
+        }];
+        let mut out = Vec::new();
+        write_pretty(&mut out, &results, color).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn frames_the_line_and_underlines_the_range() {
+        let source = "select\n  current_date,\n  column_a\nfrom t";
+        let out = render(
+            source,
+            vec![
+                Diagnostic::new(
+                    "nondeterministic_function",
+                    Severity::Warning,
+                    2,
+                    3,
+                    "CURRENT_DATE is used!".to_string(),
+                )
+                .with_end((2, 15)),
+            ],
+            false,
+        );
+        assert_eq!(
+            out,
+            "warning[nondeterministic_function]: CURRENT_DATE is used!\n\
+             \x20--> a.sql:2:3\n\
+             \x20 |\n\
+             2 |   current_date,\n\
+             \x20 |   ^^^^^^^^^^^^\n\
+             \n\
+             1 problem (0 errors, 1 warning)\n\
+             \x20 nondeterministic_function  1\n"
+        );
+    }
+
+    #[test]
+    fn multi_line_ranges_underline_each_line_and_are_cut_short() {
+        let source = (1..=8)
+            .map(|i| format!("line{}", i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let out = render(
+            &source,
+            vec![Diagnostic::new("r", Severity::Error, 2, 3, "m".to_string()).with_end((8, 3))],
+            false,
+        );
+        assert!(out.contains("2 | line2\n  |   ^^^\n3 | line3\n  | ^^^^^\n"));
+        assert!(out.contains("6 | line6\n  | ^^^^^\n  | ...\n"));
+        assert!(!out.contains("line7"));
+        assert!(out.contains("1 problem (1 error, 0 warnings)\n  r  1\n"));
+    }
+
+    #[test]
+    fn underline_counts_characters_and_keeps_tabs() {
+        // `é` is two bytes, so `xyz` starts at byte 4 but under the fourth
+        // column.
+        assert_eq!(underline("\té xyz", 4, 7), ("\t  ".to_string(), 3));
+        // Offsets past the end of the line clamp to it.
+        assert_eq!(underline("ab", 5, 9), ("  ".to_string(), 0));
+    }
+
+    #[test]
+    fn the_span_places_the_underline_when_columns_are_not_bytes() {
+        let source = "SELECT 'é', x";
+        let x = source.find('x').unwrap();
+        // `é` is two bytes, so `x` is at character column 13 but byte column 14.
+        let out = render(
+            source,
+            vec![
+                Diagnostic::new("r", Severity::Warning, 1, 13, "m".to_string())
+                    .with_end((1, 14))
+                    .with_span(x..x + 1),
+            ],
+            false,
+        );
+        assert!(out.contains(" --> a.sql:1:13\n"));
+        assert!(out.contains("1 | SELECT 'é', x\n  |             ^\n"));
+    }
+
+    #[test]
+    fn related_locations_notes_and_help_follow_the_frame() {
+        let source = "SELECT a FROM t\nGROUP BY b";
+        let out = render(
+            source,
+            vec![
+                Diagnostic::new("r", Severity::Error, 1, 8, "m".to_string())
+                    .with_end((1, 9))
+                    .with_related(Related {
+                        start: (2, 1),
+                        end: Some((2, 11)),
+                        span: None,
+                        message: "grouped here".to_string(),
+                    })
+                    .with_note("background")
+                    .with_help("fix it"),
+            ],
+            false,
+        );
+        assert!(out.contains(
+            "1 | SELECT a FROM t\n  |        ^\n  |\n2 | GROUP BY b\n  | ---------- grouped here\n  = note: background\n  = help: fix it\n\n"
+        ));
+    }
+
+    #[test]
+    fn colors_only_when_enabled() {
+        let diagnostics = || vec![Diagnostic::new("r", Severity::Error, 1, 1, "m".to_string())];
+        let colored = render("x", diagnostics(), true);
+        assert!(colored.contains("\x1b[1;31merror[r]\x1b[0m"));
+        assert!(!render("x", diagnostics(), false).contains('\x1b'));
+    }
+
+    #[test]
+    fn a_clean_run_prints_nothing() {
+        assert_eq!(render("select 1", Vec::new(), false), "");
+    }
+}
@@ -0,0 +1,299 @@
+//! Run statistics for `--statistics` and `--format summary`: how many files
+//! were scanned and how many failed to parse, the diagnostics per rule and
+//! severity, the files with the most diagnostics, and the time spent parsing
+//! and linting. Files whose result came from the cache are counted apart and
+//! add nothing to the times, which cover the files actually analysed. Rendered
+//! as an aligned table or, for dashboards tracking quality over time, as JSON.
+
+use std::io::{self, Write};
+use std::time::Duration;
+
+use serde_json::{Value, json};
+
+use super::{FileResult, plural, rule_counts, write_json_value};
+use crate::diagnostic::Severity;
+
+/// Files listed under "top files".
+const TOP_FILES: usize = 10;
+
+/// The aggregated statistics of a run.
+struct Statistics<'a> {
+    files: usize,
+    cached: usize,
+    unreadable: usize,
+    parse_errors: usize,
+    with_problems: usize,
+    errors: usize,
+    warnings: usize,
+    /// `(rule id, errors, warnings)`, by rule id.
+    rules: Vec<(&'a str, usize, usize)>,
+    /// `(path, diagnostics)`, most diagnostics first, then by path.
+    top_files: Vec<(String, usize)>,
+    parse_time: Duration,
+    lint_time: Duration,
+}
+
+impl<'a> Statistics<'a> {
+    fn collect(results: &'a [FileResult]) -> Self {
+        let diagnostics = || results.iter().flat_map(|result| &result.diagnostics);
+        let errors = diagnostics()
+            .filter(|d| d.severity() == Severity::Error)
+            .count();
+        let mut top_files: Vec<(String, usize)> = results
+            .iter()
+            .filter(|result| !result.diagnostics.is_empty())
+            .map(|result| (result.path.display().to_string(), result.diagnostics.len()))
+            .collect();
+        top_files.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top_files.truncate(TOP_FILES);
+        Self {
+            files: results.len(),
+            cached: results.iter().filter(|r| r.stats.cached).count(),
+            unreadable: results.iter().filter(|r| r.read_error.is_some()).count(),
+            parse_errors: results
+                .iter()
+                .filter(|r| r.stats.parse_error.is_some())
+                .count(),
+            with_problems: results
+                .iter()
+                .filter(|r| r.read_error.is_some() || !r.diagnostics.is_empty())
+                .count(),
+            errors,
+            warnings: diagnostics().count().saturating_sub(errors),
+            rules: rule_counts(results)
+                .into_iter()
+                .map(|(rule_id, (errors, warnings))| (rule_id, errors, warnings))
+                .collect(),
+            top_files,
+            parse_time: results.iter().map(|r| r.stats.parse_time).sum(),
+            lint_time: results.iter().map(|r| r.stats.lint_time).sum(),
+        }
+    }
+}
+
+/// Write the statistics of `results` as an aligned table. Times are summed
+/// over the analysed files, so on many threads they exceed the wall-clock
+/// time; cached files add nothing to them.
+pub fn write_table<W: Write>(out: &mut W, results: &[FileResult]) -> io::Result<()> {
+    let stats = Statistics::collect(results);
+    let total = stats.errors.saturating_add(stats.warnings);
+    let rows = [
+        ("Files scanned", stats.files.to_string()),
+        ("Files from cache", stats.cached.to_string()),
+        ("Files with problems", stats.with_problems.to_string()),
+        ("Files with parse errors", stats.parse_errors.to_string()),
+        ("Unreadable files", stats.unreadable.to_string()),
+        (
+            "Problems",
+            format!(
+                "{} ({}, {})",
+                total,
+                plural(stats.errors, "error"),
+                plural(stats.warnings, "warning")
+            ),
+        ),
+        ("Parse time", millis_text(stats.parse_time)),
+        ("Lint time", millis_text(stats.lint_time)),
+    ];
+    let width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    for (label, value) in rows {
+        writeln!(out, "{:width$}  {}", label, value)?;
+    }
+
+    if !stats.rules.is_empty() {
+        let width = stats
+            .rules
+            .iter()
+            .map(|(rule_id, _, _)| rule_id.len())
+            .max()
+            .unwrap_or(0)
+            .max("Rule".len());
+        writeln!(out)?;
+        writeln!(
+            out,
+            "{:width$}  {:>6}  {:>8}  {:>5}",
+            "Rule", "Errors", "Warnings", "Total"
+        )?;
+        for (rule_id, errors, warnings) in &stats.rules {
+            writeln!(
+                out,
+                "{:width$}  {:>6}  {:>8}  {:>5}",
+                rule_id,
+                errors,
+                warnings,
+                errors.saturating_add(*warnings)
+            )?;
+        }
+    }
+
+    if !stats.top_files.is_empty() {
+        let width = stats
+            .top_files
+            .iter()
+            .map(|(path, _)| path.len())
+            .max()
+            .unwrap_or(0)
+            .max("Top files".len());
+        writeln!(out)?;
+        writeln!(out, "{:width$}  {:>8}", "Top files", "Problems")?;
+        for (path, count) in &stats.top_files {
+            writeln!(out, "{:width$}  {:>8}", path, count)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the statistics of `results` as a JSON document.
+pub fn write_json<W: Write>(out: &mut W, results: &[FileResult]) -> io::Result<()> {
+    let stats = Statistics::collect(results);
+    let rules: Vec<Value> = stats
+        .rules
+        .iter()
+        .map(|(rule_id, errors, warnings)| {
+            json!({
+                "rule_id": rule_id,
+                "errors": errors,
+                "warnings": warnings,
+                "total": errors.saturating_add(*warnings),
+            })
+        })
+        .collect();
+    let top_files: Vec<Value> = stats
+        .top_files
+        .iter()
+        .map(|(path, count)| json!({ "path": path, "diagnostics": count }))
+        .collect();
+    let doc = json!({
+        "files": {
+            "scanned": stats.files,
+            "cached": stats.cached,
+            "with_problems": stats.with_problems,
+            "parse_errors": stats.parse_errors,
+            "unreadable": stats.unreadable,
+        },
+        "diagnostics": {
+            "total": stats.errors.saturating_add(stats.warnings),
+            "errors": stats.errors,
+            "warnings": stats.warnings,
+        },
+        "rules": rules,
+        "top_files": top_files,
+        "timing": {
+            "parse_ms": millis(stats.parse_time),
+            "lint_ms": millis(stats.lint_time),
+        },
+    });
+    write_json_value(out, &doc)
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn millis_text(duration: Duration) -> String {
+    format!("{:.1} ms", millis(duration))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::indexing_slicing, reason = "test code")]
+mod tests {
+    use super::*;
+    use crate::diagnostic::Diagnostic;
+    use crate::output::FileStats;
+    use std::path::PathBuf;
+
+    fn results() -> Vec<FileResult> {
+        let diagnostic = |rule_id: &'static str, severity| {
+            Diagnostic::new(rule_id, severity, 1, 1, "m".to_string())
+        };
+        let result = |path: &str, diagnostics, stats| FileResult {
+            path: PathBuf::from(path),
+            diagnostics,
+            read_error: None,
+            source: String::new(),
+            stats,
+        };
+        vec![
+            result(
+                "a.sql",
+                vec![diagnostic("use_current_date", Severity::Warning)],
+                FileStats {
+                    parse_error: None,
+                    cached: false,
+                    parse_time: Duration::from_millis(3),
+                    lint_time: Duration::from_millis(1),
+                },
+            ),
+            result(
+                "b.sql",
+                vec![
+                    diagnostic("invalid_group_by", Severity::Error),
+                    diagnostic("use_current_date", Severity::Warning),
+                ],
+                FileStats {
+                    parse_error: Some("Syntax error".to_string()),
+                    cached: false,
+                    parse_time: Duration::from_millis(2),
+                    lint_time: Duration::from_millis(1),
+                },
+            ),
+            result(
+                "c.sql",
+                Vec::new(),
+                FileStats {
+                    cached: true,
+                    ..FileStats::default()
+                },
+            ),
+        ]
+    }
+
+    #[test]
+    fn table_lists_counts_rules_and_top_files() {
+        let mut out = Vec::new();
+        write_table(&mut out, &results()).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Files scanned            3\n\
+             Files from cache         1\n\
+             Files with problems      2\n\
+             Files with parse errors  1\n\
+             Unreadable files         0\n\
+             Problems                 3 (1 error, 2 warnings)\n\
+             Parse time               5.0 ms\n\
+             Lint time                2.0 ms\n\
+             \n\
+             Rule              Errors  Warnings  Total\n\
+             invalid_group_by       1         0      1\n\
+             use_current_date       0         2      2\n\
+             \n\
+             Top files  Problems\n\
+             b.sql             2\n\
+             a.sql             1\n"
+        );
+    }
+
+    #[test]
+    fn json_carries_the_same_figures() {
+        let mut out = Vec::new();
+        write_json(&mut out, &results()).unwrap();
+        let doc: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            doc["files"],
+            json!({ "scanned": 3, "cached": 1, "with_problems": 2, "parse_errors": 1, "unreadable": 0 })
+        );
+        assert_eq!(
+            doc["diagnostics"],
+            json!({ "total": 3, "errors": 1, "warnings": 2 })
+        );
+        assert_eq!(
+            doc["rules"][1],
+            json!({ "rule_id": "use_current_date", "errors": 0, "warnings": 2, "total": 2 })
+        );
+        assert_eq!(
+            doc["top_files"][0],
+            json!({ "path": "b.sql", "diagnostics": 2 })
+        );
+        assert_eq!(doc["timing"]["parse_ms"], 5.0);
+    }
+}
@@ -0,0 +1,572 @@
+//! A small selector language for structural queries over an [`Ast`].
+//!
+//! Selectors borrow their shape from CSS so they read naturally to anyone who
+//! has written one, but match ZetaSQL node kinds instead of HTML elements:
+//!
+//! ```text
+//! ASTWhereClause ASTFunctionCall[name=REGEXP_CONTAINS] > ASTPathExpression
+//! ```
+//!
+//! - A *kind* (`ASTFunctionCall`) matches nodes of that kind; `*` matches any
+//!   node.
+//! - An *attribute* filter (`[name=REGEXP_CONTAINS]`) narrows the match. `name`
+//!   is the function name of an `ASTFunctionCall` and the text of any other node
+//!   that is itself a name (identifiers, path expressions, aliases); `text` is
+//!   the node's full source text. Operators are `=`, `!=`, `^=` (prefix), `$=`
+//!   (suffix) and `*=` (substring). Comparisons ignore ASCII case because SQL
+//!   names do. Values may be quoted with `'` or `"` to include spaces or `]`.
+//! - Whitespace between two compounds means *descendant*, `>` means *child*.
+//! - A comma separates alternatives; a node matching any of them matches.
+//!
+//! The interpreter runs over the arena's [`NodeRef`] navigation only (`kind`,
+//! `parent`, `children`), so it sees exactly the tree the rules see.
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use crate::ast::{Ast, NodeRef};
+
+/// A parsed selector: one or more comma-separated alternatives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+    alternatives: Vec<Complex>,
+}
+
+/// A chain of compounds joined by combinators, e.g. `A B > C`.
+///
+/// Stored left to right; `steps[i]` joins `compounds[i]` to `compounds[i + 1]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Complex {
+    compounds: Vec<Compound>,
+    steps: Vec<Combinator>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    /// Whitespace: the left compound matches any ancestor.
+    Descendant,
+    /// `>`: the left compound matches the parent.
+    Child,
+}
+
+/// A kind test plus attribute filters, e.g. `ASTFunctionCall[name=COUNT]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Compound {
+    /// `None` for the `*` wildcard.
+    kind: Option<String>,
+    attributes: Vec<Attribute>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Attribute {
+    name: AttributeName,
+    op: AttributeOp,
+    value: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AttributeName {
+    Name,
+    Text,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AttributeOp {
+    Equals,
+    NotEquals,
+    Prefix,
+    Suffix,
+    Contains,
+}
+
+/// A selector that could not be parsed, with the byte offset into the
+/// selector text where parsing stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectorError {
+    offset: usize,
+    message: String,
+}
+
+impl SelectorError {
+    fn new(offset: usize, message: impl Into<String>) -> Self {
+        Self {
+            offset,
+            message: message.into(),
+        }
+    }
+
+    /// Byte offset into the selector text where the error was detected.
+    #[must_use]
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for SelectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid selector at offset {}: {}",
+            self.offset, self.message
+        )
+    }
+}
+
+impl std::error::Error for SelectorError {}
+
+impl Selector {
+    /// Parse a selector from its text form.
+    ///
+    /// # Errors
+    /// Returns a [`SelectorError`] pointing at the first character that does
+    /// not fit the grammar described in the module docs.
+    pub fn parse(text: &str) -> Result<Self, SelectorError> {
+        Parser::new(text).parse_selector()
+    }
+
+    /// Whether `node` is matched by this selector.
+    #[must_use]
+    pub fn matches(&self, node: NodeRef<'_>, sql: &str) -> bool {
+        self.alternatives.iter().any(|alt| alt.matches(node, sql))
+    }
+
+    /// Every node of `ast` matched by this selector, in pre-order.
+    #[must_use]
+    pub fn select<'a>(&self, ast: &'a Ast, sql: &str) -> Vec<NodeRef<'a>> {
+        ast.pre_order()
+            .into_iter()
+            .filter(|node| self.matches(*node, sql))
+            .collect()
+    }
+}
+
+impl Complex {
+    fn matches(&self, node: NodeRef<'_>, sql: &str) -> bool {
+        self.compounds
+            .len()
+            .checked_sub(1)
+            .is_some_and(|last| self.matches_at(node, last, sql))
+    }
+
+    /// Whether `node` matches the compound at `index` and everything left of it
+    /// is satisfied by `node`'s ancestors. Descendant steps backtrack over every
+    /// ancestor, so `A B C` finds an `A` above a `B` above the node even when a
+    /// nearer `B` has no `A` above it.
+    fn matches_at(&self, node: NodeRef<'_>, index: usize, sql: &str) -> bool {
+        let Some(compound) = self.compounds.get(index) else {
+            return false;
+        };
+        if !compound.matches(node, sql) {
+            return false;
+        }
+        let Some(prev) = index.checked_sub(1) else {
+            return true;
+        };
+        match self.steps.get(prev) {
+            Some(Combinator::Child) => node
+                .parent()
+                .is_some_and(|parent| self.matches_at(parent, prev, sql)),
+            Some(Combinator::Descendant) => {
+                let mut current = node.parent();
+                while let Some(ancestor) = current {
+                    if self.matches_at(ancestor, prev, sql) {
+                        return true;
+                    }
+                    current = ancestor.parent();
+                }
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+impl Compound {
+    fn matches(&self, node: NodeRef<'_>, sql: &str) -> bool {
+        if let Some(kind) = &self.kind
+            && node.kind() != kind
+        {
+            return false;
+        }
+        self.attributes.iter().all(|attr| attr.matches(node, sql))
+    }
+}
+
+impl Attribute {
+    fn matches(&self, node: NodeRef<'_>, sql: &str) -> bool {
+        let actual = match self.name {
+            AttributeName::Name => node_name(node, sql),
+            AttributeName::Text => node.text(sql),
+        };
+        // A node without the attribute matches no operator, not even `!=`, so
+        // `[name!=COUNT]` selects only named nodes.
+        let Some(actual) = actual else {
+            return false;
+        };
+        let actual = actual.to_ascii_lowercase();
+        let expected = self.value.to_ascii_lowercase();
+        match self.op {
+            AttributeOp::Equals => actual == expected,
+            AttributeOp::NotEquals => actual != expected,
+            AttributeOp::Prefix => actual.starts_with(&expected),
+            AttributeOp::Suffix => actual.ends_with(&expected),
+            AttributeOp::Contains => actual.contains(&expected),
+        }
+    }
+}
+
+/// The `name` attribute of `node`: the callee of a function call, the text of a
+/// node that is itself a name, or `None` for everything else.
+fn node_name<'s>(node: NodeRef<'_>, sql: &'s str) -> Option<&'s str> {
+    match node.kind() {
+        // The callee is the call's first child, an `ASTPathExpression`.
+        "ASTFunctionCall" => node.child(0).and_then(|callee| callee.text(sql)),
+        // An alias and a CTE name each wrap a single identifier.
+        "ASTAlias" | "ASTAliasedQuery" => node.child(0).and_then(|ident| ident.text(sql)),
+        "ASTIdentifier" | "ASTPathExpression" => node.text(sql),
+        _ => None,
+    }
+}
+
+/// Recursive-descent parser over the selector text.
+struct Parser<'t> {
+    text: &'t str,
+    chars: Peekable<CharIndices<'t>>,
+}
+
+impl<'t> Parser<'t> {
+    fn new(text: &'t str) -> Self {
+        Self {
+            text,
+            chars: text.char_indices().peekable(),
+        }
+    }
+
+    /// Byte offset of the next unread character (the text length at the end).
+    fn offset(&mut self) -> usize {
+        self.chars.peek().map_or(self.text.len(), |&(i, _)| i)
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    /// Skip whitespace, reporting whether any was skipped.
+    fn skip_whitespace(&mut self) -> bool {
+        let mut skipped = false;
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.chars.next();
+            skipped = true;
+        }
+        skipped
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SelectorError> {
+        let offset = self.offset();
+        match self.chars.next() {
+            Some((_, c)) if c == expected => Ok(()),
+            Some((_, c)) => Err(SelectorError::new(
+                offset,
+                format!("expected '{expected}', found '{c}'"),
+            )),
+            None => Err(SelectorError::new(
+                offset,
+                format!("expected '{expected}', found end of selector"),
+            )),
+        }
+    }
+
+    fn parse_selector(&mut self) -> Result<Selector, SelectorError> {
+        let mut alternatives = vec![self.parse_complex()?];
+        while self.peek() == Some(',') {
+            self.chars.next();
+            alternatives.push(self.parse_complex()?);
+        }
+        if let Some(c) = self.peek() {
+            return Err(SelectorError::new(
+                self.offset(),
+                format!("unexpected '{c}'"),
+            ));
+        }
+        Ok(Selector { alternatives })
+    }
+
+    fn parse_complex(&mut self) -> Result<Complex, SelectorError> {
+        self.skip_whitespace();
+        let mut compounds = vec![self.parse_compound()?];
+        let mut steps = Vec::new();
+        loop {
+            let spaced = self.skip_whitespace();
+            let step = match self.peek() {
+                Some('>') => {
+                    self.chars.next();
+                    self.skip_whitespace();
+                    Combinator::Child
+                }
+                // A comma or the end closes this alternative; trailing
+                // whitespace is not a descendant step.
+                Some(',') | None => break,
+                Some(_) if spaced => Combinator::Descendant,
+                Some(c) => {
+                    return Err(SelectorError::new(
+                        self.offset(),
+                        format!("unexpected '{c}'"),
+                    ));
+                }
+            };
+            steps.push(step);
+            compounds.push(self.parse_compound()?);
+        }
+        Ok(Complex { compounds, steps })
+    }
+
+    fn parse_compound(&mut self) -> Result<Compound, SelectorError> {
+        let kind = if self.peek() == Some('*') {
+            self.chars.next();
+            None
+        } else {
+            let start = self.offset();
+            let word = self.parse_word();
+            if word.is_empty() {
+                return Err(SelectorError::new(start, "expected a node kind or '*'"));
+            }
+            Some(word)
+        };
+        let mut attributes = Vec::new();
+        while self.peek() == Some('[') {
+            attributes.push(self.parse_attribute()?);
+        }
+        Ok(Compound { kind, attributes })
+    }
+
+    /// A run of identifier characters (`[A-Za-z0-9_.]`).
+    fn parse_word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+                word.push(c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        word
+    }
+
+    fn parse_attribute(&mut self) -> Result<Attribute, SelectorError> {
+        self.expect('[')?;
+        self.skip_whitespace();
+        let name_offset = self.offset();
+        let name = match self.parse_word().as_str() {
+            "name" => AttributeName::Name,
+            "text" => AttributeName::Text,
+            other => {
+                return Err(SelectorError::new(
+                    name_offset,
+                    format!("unknown attribute '{other}' (expected 'name' or 'text')"),
+                ));
+            }
+        };
+        self.skip_whitespace();
+        let op = self.parse_op()?;
+        self.skip_whitespace();
+        let value = self.parse_value()?;
+        self.skip_whitespace();
+        self.expect(']')?;
+        Ok(Attribute { name, op, value })
+    }
+
+    fn parse_op(&mut self) -> Result<AttributeOp, SelectorError> {
+        let offset = self.offset();
+        let op = match self.chars.next() {
+            Some((_, '=')) => return Ok(AttributeOp::Equals),
+            Some((_, '!')) => AttributeOp::NotEquals,
+            Some((_, '^')) => AttributeOp::Prefix,
+            Some((_, '$')) => AttributeOp::Suffix,
+            Some((_, '*')) => AttributeOp::Contains,
+            _ => {
+                return Err(SelectorError::new(
+                    offset,
+                    "expected one of '=', '!=', '^=', '$=', '*='",
+                ));
+            }
+        };
+        self.expect('=')?;
+        Ok(op)
+    }
+
+    /// A quoted string (`'...'` or `"..."`, no escapes) or a bare run of
+    /// characters up to the closing `]` or whitespace.
+    fn parse_value(&mut self) -> Result<String, SelectorError> {
+        let mut value = String::new();
+        match self.peek() {
+            Some(quote @ ('\'' | '"')) => {
+                let start = self.offset();
+                self.chars.next();
+                loop {
+                    match self.chars.next() {
+                        Some((_, c)) if c == quote => break,
+                        Some((_, c)) => value.push(c),
+                        None => return Err(SelectorError::new(start, "unterminated string")),
+                    }
+                }
+            }
+            _ => {
+                while let Some(c) = self.peek() {
+                    if c == ']' || c.is_whitespace() {
+                        break;
+                    }
+                    value.push(c);
+                    self.chars.next();
+                }
+                if value.is_empty() {
+                    return Err(SelectorError::new(self.offset(), "expected a value"));
+                }
+            }
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::indexing_slicing,
+    clippy::arithmetic_side_effects,
+    reason = "test code"
+)]
+mod tests {
+    use super::*;
+    use crate::rules::helpers::parse_sql;
+
+    /// The source text of every node `selector` matches in `sql`.
+    fn select_texts(selector: &str, sql: &str) -> Vec<String> {
+        let ast = parse_sql(sql);
+        let selector = Selector::parse(selector).expect("selector parses");
+        selector
+            .select(&ast, sql)
+            .iter()
+            .map(|n| n.text(sql).unwrap_or_default().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn kind_matches_every_node_of_that_kind() {
+        let texts = select_texts("ASTFunctionCall", "SELECT COUNT(a), MAX(b) FROM t");
+        assert_eq!(texts, vec!["COUNT(a)", "MAX(b)"]);
+    }
+
+    #[test]
+    fn name_attribute_matches_the_callee_case_insensitively() {
+        let texts = select_texts(
+            "ASTFunctionCall[name=count]",
+            "SELECT COUNT(a), MAX(b) FROM t",
+        );
+        assert_eq!(texts, vec!["COUNT(a)"]);
+    }
+
+    #[test]
+    fn descendant_combinator_limits_matches_to_the_clause() {
+        // Only the REGEXP_CONTAINS inside WHERE matches, not the one in SELECT.
+        let sql = "SELECT REGEXP_CONTAINS(a, 'x') FROM t WHERE REGEXP_CONTAINS(b, 'y')";
+        let texts = select_texts("ASTWhereClause ASTFunctionCall[name=REGEXP_CONTAINS]", sql);
+        assert_eq!(texts, vec!["REGEXP_CONTAINS(b, 'y')"]);
+    }
+
+    #[test]
+    fn child_combinator_requires_a_direct_parent() {
+        // The callee and the column `b` are both direct children of the call;
+        // the identifiers inside them are not.
+        let sql = "SELECT 1 FROM t WHERE REGEXP_CONTAINS(b, 'y')";
+        let texts = select_texts(
+            "ASTWhereClause ASTFunctionCall[name=REGEXP_CONTAINS] > ASTPathExpression",
+            sql,
+        );
+        assert_eq!(texts, vec!["REGEXP_CONTAINS", "b"]);
+
+        let none = select_texts("ASTWhereClause > ASTPathExpression", sql);
+        assert!(none.is_empty(), "got {none:?}");
+    }
+
+    #[test]
+    fn comma_separates_alternatives() {
+        let texts = select_texts(
+            "ASTFunctionCall[name=COUNT], ASTFunctionCall[name=MAX]",
+            "SELECT COUNT(a), MAX(b), MIN(c) FROM t",
+        );
+        assert_eq!(texts, vec!["COUNT(a)", "MAX(b)"]);
+    }
+
+    #[test]
+    fn wildcard_and_text_operators() {
+        let sql = "SELECT user_id, user_name, id FROM t";
+        assert_eq!(
+            select_texts("ASTSelectColumn > *[text^=user_]", sql),
+            vec!["user_id", "user_name"]
+        );
+        assert_eq!(
+            select_texts("ASTSelectColumn > *[text$=_name]", sql),
+            vec!["user_name"]
+        );
+        assert_eq!(
+            select_texts("ASTSelectColumn > ASTPathExpression[text*='r_i']", sql),
+            vec!["user_id"]
+        );
+    }
+
+    #[test]
+    fn quoted_values_may_contain_spaces() {
+        let texts = select_texts(
+            "ASTStringLiteral[text=\"'a b'\"]",
+            "SELECT 'a b', 'c' FROM t",
+        );
+        assert_eq!(texts, vec!["'a b'"]);
+    }
+
+    #[test]
+    fn descendant_steps_backtrack_over_ancestors() {
+        // The nearest ASTQuery above `y` is the scalar subquery's, whose parent
+        // is not an ASTTableSubquery; the match must still be found through the
+        // outer derived table's ASTQuery further up.
+        let sql = "SELECT 1 FROM (SELECT (SELECT y FROM v) AS x FROM u)";
+        let texts = select_texts(
+            "ASTTableSubquery > ASTQuery ASTSelectColumn > ASTPathExpression[name=y]",
+            sql,
+        );
+        assert_eq!(texts, vec!["y"]);
+    }
+
+    #[test]
+    fn parse_rejects_malformed_selectors() {
+        for bad in [
+            "",
+            "ASTSelect >",
+            "ASTSelect[",
+            "ASTSelect[foo=bar]",
+            "ASTSelect[name~bar]",
+            "ASTSelect[name=]",
+            "ASTSelect[name='x",
+            "ASTSelect,",
+            "ASTSelect ]",
+        ] {
+            assert!(Selector::parse(bad).is_err(), "{bad:?} should not parse");
+        }
+    }
+
+    #[test]
+    fn parse_error_reports_the_offset() {
+        let err = Selector::parse("ASTSelect[foo=bar]").unwrap_err();
+        assert_eq!(err.offset(), 10);
+        assert!(err.to_string().contains("unknown attribute 'foo'"), "{err}");
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_ignored() {
+        let a = Selector::parse("  ASTSelect  >  ASTSelectList  ").unwrap();
+        let b = Selector::parse("ASTSelect>ASTSelectList").unwrap();
+        assert_eq!(a, b);
+    }
+}
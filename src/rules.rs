@@ -1,10 +1,24 @@
 pub mod apply_function_to_partition_column;
 pub mod compare_table_suffix_with_subquery;
+pub mod custom;
+pub mod exact_aggregation;
 pub mod helpers;
 pub mod invalid_group_by;
+pub mod legacy_syntax;
+pub mod missing_join_condition;
+pub mod non_equi_join;
+pub mod nondeterministic_function;
+pub mod not_in_nullable_subquery;
 pub mod rule;
+pub mod set_operation_column_mismatch;
+pub mod temporal_type_mismatch;
 pub mod unnecessary_order_by;
+pub mod unnecessary_union_distinct;
+pub mod unqualified_column_reference;
 pub mod unused_column_in_cte;
-pub mod use_current_date;
+pub mod window_function_misuse;
 
-pub use rule::{Rule, all_rules, known_rule_ids, run_rules, run_rules_ignoring};
+pub use rule::{
+    Category, Rule, RuleMetadata, all_rules, all_rules_with, configured_rules, known_rule_ids,
+    renamed_rule_id, run_rules, run_rules_ignoring, run_rules_on_unparsed, run_rules_with,
+};
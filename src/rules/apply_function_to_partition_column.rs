@@ -1,11 +1,30 @@
+use serde::{Deserialize, Serialize};
+
 use crate::ast::NodeRef;
 
 use crate::diagnostic::{Diagnostic, Severity};
-use crate::rules::helpers::{get_node_text, one_based_start};
-use crate::rules::rule::Rule;
+use crate::rules::helpers::get_node_text;
+use crate::rules::rule::{Category, LintContext, Rule, RuleMetadata};
 
 const RULE_ID: &str = "apply_function_to_partition_column";
 
+const METADATA: RuleMetadata<'static> = RuleMetadata {
+    name: "Applying a function to a partition column in a filter",
+    default_severity: Severity::Warning,
+    default_enabled: true,
+    category: Category::Performance,
+    summary: "A date/time function or cast wraps a column in a WHERE-clause comparison, which defeats partition pruning.",
+    description: "Wrapping a partition column in a date/time function or cast inside a `WHERE` clause prevents BigQuery from pruning partitions, so the query scans the whole table. The rule targets `DATE`, `DATETIME`, `TIMESTAMP`, `TIME`, their `*_TRUNC` and `FORMAT_*` variants, and casts to those types, since partition columns are in practice date/time columns.",
+    bad_example: Some("SELECT * FROM dataset.table WHERE DATE(created_at) = '2024-01-01'"),
+    good_example: Some(
+        "SELECT * FROM dataset.table WHERE created_at >= '2024-01-01' AND created_at < '2024-01-02'",
+    ),
+    doc_url: Some(
+        "https://github.com/hirosassa/bqvalid/blob/main/docs/rules.md#applying-a-function-to-a-partition-column-in-a-filter",
+    ),
+    fixable: false,
+};
+
 /// Date/time functions that, when wrapped around a partition column in a
 /// filter, prevent BigQuery from pruning partitions. Partition columns are in
 /// practice date/timestamp columns (or the `_PARTITIONTIME`/`_PARTITIONDATE`
@@ -30,22 +49,56 @@ const DATE_TIME_FUNCTIONS: &[&str] = &[
 /// with the same partition-pruning consequences as the functions above.
 const DATE_TIME_CAST_TYPES: &[&str] = &["date", "datetime", "timestamp", "time"];
 
+/// `[rules.apply_function_to_partition_column]` options: project-specific
+/// transforms to treat like the built-in date/time ones.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApplyFunctionToPartitionColumnOptions {
+    /// Function names flagged in addition to [`DATE_TIME_FUNCTIONS`], e.g.
+    /// `parse_date`.
+    #[serde(default)]
+    pub extra_functions: Vec<String>,
+    /// Cast target types flagged in addition to [`DATE_TIME_CAST_TYPES`].
+    #[serde(default)]
+    pub extra_cast_types: Vec<String>,
+}
+
 /// Flags a date/time function or cast applied to a column in a WHERE-clause
 /// comparison, which defeats partition pruning and forces a full scan.
-pub struct ApplyFunctionToPartitionColumn;
+#[derive(Default)]
+pub struct ApplyFunctionToPartitionColumn {
+    options: ApplyFunctionToPartitionColumnOptions,
+}
+
+impl ApplyFunctionToPartitionColumn {
+    #[must_use]
+    pub const fn new(options: ApplyFunctionToPartitionColumnOptions) -> Self {
+        Self { options }
+    }
+}
 
 impl Rule for ApplyFunctionToPartitionColumn {
-    fn id(&self) -> &'static str {
+    fn id(&self) -> &str {
         RULE_ID
     }
 
-    fn check_node(&self, node: NodeRef<'_>, sql: &str, diagnostics: &mut Vec<Diagnostic>) {
+    fn metadata(&self) -> RuleMetadata<'_> {
+        METADATA
+    }
+
+    fn check_node(
+        &self,
+        node: NodeRef<'_>,
+        cx: &LintContext<'_>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let sql = cx.sql;
         if node.kind() != "ASTWhereClause" {
             return;
         }
         for descendant in node.pre_order() {
             for operand in comparison_operands(&descendant) {
-                if let Some(func) = date_time_transform_on_column(&operand, sql) {
+                if let Some(func) = date_time_transform_on_column(&operand, sql, &self.options) {
                     diagnostics.push(new_full_scan_warning(&func));
                 }
             }
@@ -81,19 +134,25 @@ fn comparison_operands<'a>(node: &NodeRef<'a>) -> Vec<NodeRef<'a>> {
 
 /// Returns the offending `function_call` / `cast_expression` node when `operand`
 /// is a date/time transform wrapped around a column reference, else `None`.
-fn date_time_transform_on_column<'a>(operand: &NodeRef<'a>, sql: &str) -> Option<NodeRef<'a>> {
+fn date_time_transform_on_column<'a>(
+    operand: &NodeRef<'a>,
+    sql: &str,
+    options: &ApplyFunctionToPartitionColumnOptions,
+) -> Option<NodeRef<'a>> {
     match operand.kind() {
         // A function call's first named child is the function name (an
         // `ASTPathExpression`), so skip it when looking for a wrapped column.
         "ASTFunctionCall"
-            if is_date_time_function(operand, sql) && wraps_column(operand, sql, true) =>
+            if is_date_time_function(operand, sql, &options.extra_functions)
+                && wraps_column(operand, sql, true) =>
         {
             Some(*operand)
         }
         // A cast's operand is a child too, so keep it; the target type is skipped
         // inside `wraps_column`.
         "ASTCastExpression"
-            if casts_to_date_time(operand, sql) && wraps_column(operand, sql, false) =>
+            if casts_to_date_time(operand, sql, &options.extra_cast_types)
+                && wraps_column(operand, sql, false) =>
         {
             Some(*operand)
         }
@@ -107,29 +166,33 @@ fn date_time_transform_on_column<'a>(operand: &NodeRef<'a>, sql: &str) -> Option
     }
 }
 
-/// True when the function-call node's name is one of [`DATE_TIME_FUNCTIONS`].
+/// True when the function-call node's name is one of [`DATE_TIME_FUNCTIONS`]
+/// or the configured `extra` functions.
 ///
 /// The name node is an `ASTPathExpression` (wrapping the identifier, same text).
-fn is_date_time_function(func: &NodeRef<'_>, sql: &str) -> bool {
+fn is_date_time_function(func: &NodeRef<'_>, sql: &str, extra: &[String]) -> bool {
     func.named_child(0).is_some_and(|name| {
         name.kind() == "ASTPathExpression"
-            && DATE_TIME_FUNCTIONS
-                .iter()
-                .any(|f| f.eq_ignore_ascii_case(get_node_text(&name, sql)))
+            && is_listed(get_node_text(&name, sql), DATE_TIME_FUNCTIONS, extra)
     })
 }
 
-/// True when the cast's target type is one of [`DATE_TIME_CAST_TYPES`], e.g.
-/// `cast(col as date)`. The type node is an `ASTSimpleType` on googlesql.
-fn casts_to_date_time(cast: &NodeRef<'_>, sql: &str) -> bool {
+/// True when the cast's target type is one of [`DATE_TIME_CAST_TYPES`] or the
+/// configured `extra` types, e.g. `cast(col as date)`. The type node is an
+/// `ASTSimpleType` on googlesql.
+fn casts_to_date_time(cast: &NodeRef<'_>, sql: &str, extra: &[String]) -> bool {
     cast.named_children().into_iter().any(|child| {
         child.kind() == "ASTSimpleType"
-            && DATE_TIME_CAST_TYPES
-                .iter()
-                .any(|t| t.eq_ignore_ascii_case(get_node_text(&child, sql)))
+            && is_listed(get_node_text(&child, sql), DATE_TIME_CAST_TYPES, extra)
     })
 }
 
+/// Case-insensitive membership in the built-in list or the configured extras.
+fn is_listed(name: &str, built_in: &[&str], extra: &[String]) -> bool {
+    built_in.iter().any(|b| b.eq_ignore_ascii_case(name))
+        || extra.iter().any(|e| e.eq_ignore_ascii_case(name))
+}
+
 /// True when the transform is applied to a column reference rather than only
 /// literals, e.g. `date(created_at)` (flagged) vs `date('2024-01-01')` (not).
 ///
@@ -158,15 +221,14 @@ fn wraps_column(operand: &NodeRef<'_>, sql: &str, skip_first_named_child: bool)
 
 /// Build the full-scan diagnostic pointing at the offending transform node.
 fn new_full_scan_warning(node: &NodeRef<'_>) -> Diagnostic {
-    let (row, col) = one_based_start(node);
-    Diagnostic::new(
+    Diagnostic::for_node(
         RULE_ID,
         Severity::Warning,
-        row,
-        col,
+        node,
         "Full scan will cause! Should not apply a function to a partition column in a filter"
             .to_string(),
     )
+    .with_help("compare the bare column against a range of constants instead")
 }
 
 #[cfg(test)]
@@ -185,14 +247,14 @@ mod tests {
     #[test]
     fn flags_date_function_on_column_in_binary_expression() {
         let sql = "select * from t where date(created_at) = '2024-01-01'";
-        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn, sql);
+        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn::default(), sql);
         assert_eq!(diagnostics.len(), 1, "date(col) = ... must be flagged");
     }
 
     #[test]
     fn flags_cast_to_date_on_column() {
         let sql = "select * from t where cast(created_at as date) = '2024-01-01'";
-        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn, sql);
+        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn::default(), sql);
         assert_eq!(diagnostics.len(), 1, "cast(col as date) must be flagged");
     }
 
@@ -201,14 +263,14 @@ mod tests {
         // The transform can sit on either side of the comparison; a right-hand
         // `date(col)` defeats pruning just as a left-hand one does.
         let sql = "select * from t where '2024-01-01' = date(created_at)";
-        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn, sql);
+        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn::default(), sql);
         assert_eq!(diagnostics.len(), 1, "literal = date(col) must be flagged");
     }
 
     #[test]
     fn flags_cast_to_date_on_column_on_right_operand() {
         let sql = "select * from t where '2024-01-01' = cast(created_at as date)";
-        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn, sql);
+        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn::default(), sql);
         assert_eq!(
             diagnostics.len(),
             1,
@@ -219,7 +281,7 @@ mod tests {
     #[test]
     fn flags_trunc_function_on_column() {
         let sql = "select * from t where timestamp_trunc(ts, day) = '2024-01-01'";
-        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn, sql);
+        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn::default(), sql);
         assert_eq!(
             diagnostics.len(),
             1,
@@ -230,14 +292,14 @@ mod tests {
     #[test]
     fn flags_function_in_between_expression() {
         let sql = "select * from t where date(created_at) between '2024-01-01' and '2024-01-02'";
-        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn, sql);
+        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn::default(), sql);
         assert_eq!(diagnostics.len(), 1, "date(col) between .. must be flagged");
     }
 
     #[test]
     fn flags_pseudo_partition_column_wrapped_in_function() {
         let sql = "select * from t where date(_partitiontime) = '2024-01-01'";
-        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn, sql);
+        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn::default(), sql);
         assert_eq!(diagnostics.len(), 1, "date(_partitiontime) must be flagged");
     }
 
@@ -246,14 +308,14 @@ mod tests {
         // `date(col) IN (...)` wraps the tested value in a transform and defeats
         // pruning just like a binary comparison does.
         let sql = "select * from t where date(created_at) in ('2024-01-01', '2024-01-02')";
-        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn, sql);
+        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn::default(), sql);
         assert_eq!(diagnostics.len(), 1, "date(col) IN (..) must be flagged");
     }
 
     #[test]
     fn does_not_flag_bare_column_in_expression() {
         let sql = "select * from t where created_at in ('2024-01-01', '2024-01-02')";
-        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn, sql);
+        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn::default(), sql);
         assert!(
             diagnostics.is_empty(),
             "a bare column IN list prunes partitions and must not be flagged"
@@ -263,7 +325,7 @@ mod tests {
     #[test]
     fn flags_extract_on_column() {
         let sql = "select * from t where extract(year from created_at) = 2024";
-        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn, sql);
+        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn::default(), sql);
         assert_eq!(diagnostics.len(), 1, "extract(.. from col) must be flagged");
     }
 
@@ -272,7 +334,7 @@ mod tests {
         // The date part (`year`) must not be mistaken for a column: with a literal
         // operand there is no partition column to prune.
         let sql = "select * from t where extract(year from date '2024-01-01') = 2024";
-        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn, sql);
+        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn::default(), sql);
         assert!(
             diagnostics.is_empty(),
             "extract from a literal has no column to prune"
@@ -282,7 +344,7 @@ mod tests {
     #[test]
     fn flags_format_date_on_column() {
         let sql = "select * from t where format_date('%Y-%m', created_at) = '2024-01'";
-        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn, sql);
+        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn::default(), sql);
         assert_eq!(
             diagnostics.len(),
             1,
@@ -293,7 +355,7 @@ mod tests {
     #[test]
     fn flags_format_timestamp_on_column() {
         let sql = "select * from t where format_timestamp('%Y', created_at) = '2024'";
-        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn, sql);
+        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn::default(), sql);
         assert_eq!(
             diagnostics.len(),
             1,
@@ -304,7 +366,7 @@ mod tests {
     #[test]
     fn does_not_flag_bare_column_comparison() {
         let sql = "select * from t where created_at >= '2024-01-01'";
-        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn, sql);
+        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn::default(), sql);
         assert!(
             diagnostics.is_empty(),
             "a bare column comparison prunes partitions and must not be flagged"
@@ -314,7 +376,7 @@ mod tests {
     #[test]
     fn does_not_flag_non_date_function() {
         let sql = "select * from t where upper(name) = 'FOO'";
-        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn, sql);
+        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn::default(), sql);
         assert!(
             diagnostics.is_empty(),
             "non-date functions are unrelated to partition pruning"
@@ -325,7 +387,7 @@ mod tests {
     fn does_not_flag_date_function_on_literal_only() {
         // No column reference inside, so there is no partition column to prune.
         let sql = "select * from t where created_at >= date('2024-01-01')";
-        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn, sql);
+        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn::default(), sql);
         assert!(
             diagnostics.is_empty(),
             "date(literal) does not defeat pruning of a bare column"
@@ -336,7 +398,7 @@ mod tests {
     fn does_not_flag_cast_to_string() {
         // Only date/time cast targets are treated as pruning-defeating transforms.
         let sql = "select * from t where cast(name as string) = 'x'";
-        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn, sql);
+        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn::default(), sql);
         assert!(diagnostics.is_empty(), "cast to string is out of scope");
     }
 
@@ -344,7 +406,7 @@ mod tests {
     fn points_at_the_function_position() {
         let sql = "SELECT x FROM t WHERE DATE(created_at) = '2024-01-01'";
         let func_col = sql.find("DATE").expect("query contains DATE(");
-        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn, sql);
+        let diagnostics = run_rule(&ApplyFunctionToPartitionColumn::default(), sql);
         assert_eq!(diagnostics.len(), 1);
         assert_eq!(diagnostics[0].row(), 1);
         assert_eq!(diagnostics[0].col(), func_col + 1);
@@ -359,7 +421,23 @@ mod tests {
             "SELECT x FROM t WHERE created_at IN ('2024-01-01', '2024-01-02')",
             "SELECT x FROM t WHERE DATE(created_at) IS NOT NULL",
         ] {
-            let _ = run_rule(&ApplyFunctionToPartitionColumn, sql);
+            let _ = run_rule(&ApplyFunctionToPartitionColumn::default(), sql);
         }
     }
+
+    #[test]
+    fn configured_extra_functions_and_cast_types_are_flagged() {
+        let rule = ApplyFunctionToPartitionColumn::new(ApplyFunctionToPartitionColumnOptions {
+            extra_functions: vec!["parse_date".to_string()],
+            extra_cast_types: vec!["string".to_string()],
+        });
+        let sql = "select * from t where PARSE_DATE('%Y%m%d', dt) = '2024-01-01'";
+        assert_eq!(run_rule(&rule, sql).len(), 1);
+        let sql = "select * from t where cast(dt as string) = '2024-01-01'";
+        assert_eq!(run_rule(&rule, sql).len(), 1);
+
+        // The defaults still leave both alone.
+        let sql = "select * from t where PARSE_DATE('%Y%m%d', dt) = '2024-01-01'";
+        assert!(run_rule(&ApplyFunctionToPartitionColumn::default(), sql).is_empty());
+    }
 }
@@ -1,20 +1,47 @@
 use crate::ast::NodeRef;
 
-use crate::diagnostic::{Diagnostic, Severity};
-use crate::rules::helpers::{get_node_text, one_based_start};
-use crate::rules::rule::Rule;
+use crate::diagnostic::{Diagnostic, Related, Severity};
+use crate::rules::helpers::get_node_text;
+use crate::rules::rule::{Category, LintContext, Rule, RuleMetadata};
 
 const RULE_ID: &str = "compare_table_suffix_with_subquery";
 
+const METADATA: RuleMetadata<'static> = RuleMetadata {
+    name: "Comparing `_TABLE_SUFFIX` with subquery",
+    default_severity: Severity::Warning,
+    default_enabled: true,
+    category: Category::Performance,
+    summary: "`_TABLE_SUFFIX` is compared with a subquery, which scans every table matched by the wildcard.",
+    description: "BigQuery can only limit the tables a wildcard query reads when `_TABLE_SUFFIX` is compared with constant expressions. Comparing it with a subquery, directly or as a `BETWEEN` bound, makes the filter dynamic and every matching table is scanned in full.",
+    bad_example: Some(
+        "SELECT * FROM `dataset.table_*` WHERE _TABLE_SUFFIX = (SELECT MAX(dt) FROM dates)",
+    ),
+    good_example: Some("SELECT * FROM `dataset.table_*` WHERE _TABLE_SUFFIX = '20220601'"),
+    doc_url: Some(
+        "https://github.com/hirosassa/bqvalid/blob/main/docs/rules.md#comparing-_table_suffix-with-subquery",
+    ),
+    fixable: false,
+};
+
 /// Flags `_TABLE_SUFFIX` compared against a subquery, which forces a full scan.
 pub struct CompareTableSuffixWithSubquery;
 
 impl Rule for CompareTableSuffixWithSubquery {
-    fn id(&self) -> &'static str {
+    fn id(&self) -> &str {
         RULE_ID
     }
 
-    fn check_node(&self, node: NodeRef<'_>, sql: &str, diagnostics: &mut Vec<Diagnostic>) {
+    fn metadata(&self) -> RuleMetadata<'_> {
+        METADATA
+    }
+
+    fn check_node(
+        &self,
+        node: NodeRef<'_>,
+        cx: &LintContext<'_>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let sql = cx.sql;
         if node.kind() == "ASTWhereClause" {
             if let Some(diagnostic) = compared_with_subquery_in_binary_expression(node, sql) {
                 diagnostics.push(diagnostic);
@@ -52,7 +79,7 @@ fn compared_with_subquery_in_binary_expression(n: NodeRef<'_>, src: &str) -> Opt
             continue;
         };
         if is_table_suffix(&left, src) && is_subquery(&right) {
-            return Some(new_full_scan_warning(&right));
+            return Some(new_full_scan_warning(&right, &left));
         }
     }
     None
@@ -72,24 +99,28 @@ fn compared_with_subquery_in_between_expression(n: NodeRef<'_>, src: &str) -> Op
         // googlesql lists the bounds directly under ASTBetweenExpression.
         for c in node.children() {
             if is_subquery(&c) {
-                return Some(new_full_scan_warning(&c));
+                return Some(new_full_scan_warning(&c, &operand));
             }
         }
     }
     None
 }
 
-/// Build the full-scan diagnostic pointing at `subquery_node`. Both the binary
-/// and BETWEEN paths report the same problem, so construction lives here.
-fn new_full_scan_warning(subquery_node: &NodeRef<'_>) -> Diagnostic {
-    let (row, col) = one_based_start(subquery_node);
-    Diagnostic::new(
+/// Build the full-scan diagnostic pointing at `subquery_node`, with the
+/// compared `_TABLE_SUFFIX` as a related location. Both the binary and BETWEEN
+/// paths report the same problem, so construction lives here.
+fn new_full_scan_warning(subquery_node: &NodeRef<'_>, suffix_node: &NodeRef<'_>) -> Diagnostic {
+    Diagnostic::for_node(
         RULE_ID,
         Severity::Warning,
-        row,
-        col,
+        subquery_node,
         "Full scan will cause! Should not compare _TABLE_SUFFIX with subquery".to_string(),
     )
+    .with_related(Related::for_node(
+        suffix_node,
+        "_TABLE_SUFFIX is compared here",
+    ))
+    .with_note("BigQuery only prunes wildcard tables when _TABLE_SUFFIX is compared with constant expressions")
 }
 
 #[cfg(test)]
@@ -204,6 +235,13 @@ where
                 // 1-based, and row 1 because the query is on a single line.
                 assert_eq!(diagnostic.row(), 1);
                 assert_eq!(diagnostic.col(), paren_col + 1);
+                // The compared pseudo column is the related location.
+                let related = diagnostic.related();
+                assert_eq!(related.len(), 1);
+                assert_eq!(
+                    related[0].start,
+                    (1, sql.find("_TABLE_SUFFIX").unwrap() + 1)
+                );
                 checked = true;
             }
         }
@@ -0,0 +1,138 @@
+use crate::ast::NodeRef;
+
+use crate::config::CustomRuleConfig;
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::query::{Selector, SelectorError};
+use crate::rules::rule::{Category, LintContext, Rule, RuleMetadata};
+
+/// A project-specific rule declared in the config file: every node matched by
+/// its selector is reported with a fixed message.
+pub struct CustomRule {
+    id: String,
+    selector: Selector,
+    message: String,
+    description: String,
+    severity: Severity,
+}
+
+impl CustomRule {
+    /// Build the rule from its `[[custom_rules]]` entry.
+    ///
+    /// # Errors
+    /// Returns the [`SelectorError`] when the entry's selector does not parse.
+    pub fn from_config(config: &CustomRuleConfig) -> Result<Self, SelectorError> {
+        Ok(Self {
+            id: config.id.clone(),
+            selector: Selector::parse(&config.selector)?,
+            message: config.message.clone(),
+            description: format!(
+                "{}\n\nDefined in the config file; reports every node matched by `{}`.",
+                config.message, config.selector
+            ),
+            severity: config.severity,
+        })
+    }
+}
+
+impl Rule for CustomRule {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            name: &self.id,
+            default_severity: self.severity,
+            default_enabled: true,
+            category: Category::Custom,
+            summary: &self.message,
+            description: &self.description,
+            bad_example: None,
+            good_example: None,
+            doc_url: None,
+            fixable: false,
+        }
+    }
+
+    fn check_node(
+        &self,
+        node: NodeRef<'_>,
+        cx: &LintContext<'_>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let sql = cx.sql;
+        if self.selector.matches(node, sql) {
+            diagnostics.push(Diagnostic::for_node(
+                self.id.clone(),
+                self.severity,
+                &node,
+                self.message.clone(),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::indexing_slicing,
+    clippy::arithmetic_side_effects,
+    reason = "test code"
+)]
+mod tests {
+    use super::*;
+    use crate::rules::helpers::run_rule;
+
+    fn rule(selector: &str) -> CustomRule {
+        CustomRule::from_config(&CustomRuleConfig {
+            id: "no_regexp_in_where".to_string(),
+            selector: selector.to_string(),
+            message: "Avoid REGEXP_CONTAINS in filters".to_string(),
+            severity: Severity::Error,
+        })
+        .expect("selector parses")
+    }
+
+    #[test]
+    fn reports_each_match_with_the_configured_id_and_severity() {
+        let sql = "SELECT REGEXP_CONTAINS(a, 'x') FROM t WHERE REGEXP_CONTAINS(b, 'y')";
+        let diagnostics = run_rule(
+            &rule("ASTWhereClause ASTFunctionCall[name=REGEXP_CONTAINS]"),
+            sql,
+        );
+        assert_eq!(diagnostics.len(), 1);
+        let d = &diagnostics[0];
+        assert_eq!(d.rule_id(), "no_regexp_in_where");
+        assert_eq!(d.severity(), Severity::Error);
+        assert_eq!(d.message(), "Avoid REGEXP_CONTAINS in filters");
+        assert_eq!(d.col(), sql.rfind("REGEXP_CONTAINS").unwrap() + 1);
+    }
+
+    #[test]
+    fn metadata_comes_from_the_config_entry() {
+        let rule = rule("ASTFunctionCall[name=REGEXP_CONTAINS]");
+        let meta = rule.metadata();
+        assert_eq!(meta.name, "no_regexp_in_where");
+        assert_eq!(meta.category, Category::Custom);
+        assert_eq!(meta.default_severity, Severity::Error);
+        assert_eq!(meta.summary, "Avoid REGEXP_CONTAINS in filters");
+        assert!(
+            meta.description
+                .contains("`ASTFunctionCall[name=REGEXP_CONTAINS]`")
+        );
+        assert!(meta.bad_example.is_none() && meta.doc_url.is_none());
+    }
+
+    #[test]
+    fn invalid_selector_is_rejected() {
+        let err = CustomRule::from_config(&CustomRuleConfig {
+            id: "broken".to_string(),
+            selector: "ASTSelect[".to_string(),
+            message: "m".to_string(),
+            severity: Severity::Warning,
+        });
+        assert!(err.is_err());
+    }
+}
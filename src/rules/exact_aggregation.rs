@@ -0,0 +1,304 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ast::NodeRef;
+
+use crate::diagnostic::{Diagnostic, FunctionLevel, Severity};
+use crate::rules::helpers::{find_child_of_kind, find_parent_select, get_node_text, is_aggregate};
+use crate::rules::rule::{Category, LintContext, Rule, RuleMetadata};
+
+const RULE_ID: &str = "exact_aggregation";
+
+const METADATA: RuleMetadata<'static> = RuleMetadata {
+    name: "Exact aggregation over a large table",
+    default_severity: Severity::Warning,
+    default_enabled: false,
+    category: Category::Performance,
+    summary: "An exact distinct count, distinct array or percentile is computed over a large table where an approximation would do.",
+    description: "`COUNT(DISTINCT ...)`, `ARRAY_AGG(DISTINCT ...)` and `PERCENTILE_CONT`/`PERCENTILE_DISC` keep every distinct value in memory, which is slow and can exceed resources on large tables. `APPROX_COUNT_DISTINCT`, `APPROX_QUANTILES` and `APPROX_TOP_COUNT` answer within a small error in a fraction of the time, and HLL sketches (`HLL_COUNT.INIT`/`MERGE`) let distinct counts be pre-aggregated and combined. The rule is off unless enabled in its options. It flags these calls in queries over the configured large tables, and in queries that read a table with neither a partition filter nor a `LIMIT`.",
+    bad_example: Some("SELECT COUNT(DISTINCT user_id) FROM analytics.events"),
+    good_example: Some("SELECT APPROX_COUNT_DISTINCT(user_id) FROM analytics.events"),
+    doc_url: Some(
+        "https://github.com/hirosassa/bqvalid/blob/main/docs/rules.md#exact-aggregation-over-a-large-table",
+    ),
+    fixable: false,
+};
+
+/// The approximate alternatives to exact aggregates: the function, whether
+/// only its `DISTINCT` form has one, and what to suggest instead.
+const APPROXIMATIONS: &[(&str, bool, &str)] = &[
+    (
+        "COUNT",
+        true,
+        "APPROX_COUNT_DISTINCT, or HLL_COUNT sketches to combine counts across queries",
+    ),
+    (
+        "ARRAY_AGG",
+        true,
+        "APPROX_TOP_COUNT when only the most frequent values are needed",
+    ),
+    ("PERCENTILE_CONT", false, "APPROX_QUANTILES"),
+    ("PERCENTILE_DISC", false, "APPROX_QUANTILES"),
+];
+
+/// Pseudo columns whose filters prune partitions and wildcard tables.
+const PARTITION_PSEUDO_COLUMNS: &[&str] = &["_PARTITIONTIME", "_PARTITIONDATE", "_TABLE_SUFFIX"];
+
+/// `[rules.exact_aggregation]` options. The rule is off until `level` is set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExactAggregationOptions {
+    /// The level findings are reported at; `off` by default.
+    #[serde(default = "default_level")]
+    pub level: FunctionLevel,
+    /// Tables always worth an approximation, e.g. `analytics.events`. A name
+    /// matches a path equal to it or ending with `.` and it.
+    #[serde(default)]
+    pub large_tables: Vec<String>,
+    /// Partition columns whose filters count as partition filters, in
+    /// addition to the `_PARTITIONTIME`, `_PARTITIONDATE` and `_TABLE_SUFFIX`
+    /// pseudo columns.
+    #[serde(default)]
+    pub partition_columns: Vec<String>,
+}
+
+impl Default for ExactAggregationOptions {
+    fn default() -> Self {
+        Self {
+            level: default_level(),
+            large_tables: Vec::new(),
+            partition_columns: Vec::new(),
+        }
+    }
+}
+
+const fn default_level() -> FunctionLevel {
+    FunctionLevel::Off
+}
+
+/// Flags exact aggregates that an approximate function could replace on large
+/// inputs.
+#[derive(Default)]
+pub struct ExactAggregation {
+    options: ExactAggregationOptions,
+}
+
+impl ExactAggregation {
+    #[must_use]
+    pub const fn new(options: ExactAggregationOptions) -> Self {
+        Self { options }
+    }
+
+    /// Why the select `call` belongs to reads too much for an exact
+    /// aggregate, if it does.
+    fn reason(&self, call: &NodeRef<'_>, src: &str) -> Option<String> {
+        let select = find_parent_select(call)?;
+        let from = find_child_of_kind(&select, "ASTFromClause")?;
+        let tables: Vec<String> = from
+            .pre_order()
+            .into_iter()
+            .filter(|node| node.kind() == "ASTTablePathExpression")
+            .filter_map(|table| table.named_child(0))
+            .filter(|path| path.kind() == "ASTPathExpression")
+            .map(|path| get_node_text(&path, src).replace('`', "").to_lowercase())
+            .collect();
+        if let Some(table) = tables.iter().find(|table| self.is_large_table(table)) {
+            return Some(format!("`{}` is configured as a large table", table));
+        }
+        if tables.is_empty() || has_limit(&select) || self.has_partition_filter(&select, src) {
+            return None;
+        }
+        Some("the query has neither a partition filter nor a LIMIT".to_string())
+    }
+
+    fn is_large_table(&self, path: &str) -> bool {
+        self.options.large_tables.iter().any(|name| {
+            let name = name.replace('`', "").to_lowercase();
+            path == name
+                || path
+                    .strip_suffix(name.as_str())
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        })
+    }
+
+    /// True when the select's `WHERE` clause references a partition column.
+    fn has_partition_filter(&self, select: &NodeRef<'_>, src: &str) -> bool {
+        let Some(filter) = find_child_of_kind(select, "ASTWhereClause") else {
+            return false;
+        };
+        filter
+            .pre_order()
+            .into_iter()
+            .filter(|node| node.kind() == "ASTPathExpression")
+            .filter_map(|path| {
+                get_node_text(&path, src)
+                    .rsplit('.')
+                    .next()
+                    .map(|name| name.trim_matches('`').to_string())
+            })
+            .any(|column| {
+                PARTITION_PSEUDO_COLUMNS
+                    .iter()
+                    .copied()
+                    .chain(self.options.partition_columns.iter().map(String::as_str))
+                    .any(|partition| partition.eq_ignore_ascii_case(&column))
+            })
+    }
+}
+
+impl Rule for ExactAggregation {
+    fn id(&self) -> &str {
+        RULE_ID
+    }
+
+    fn metadata(&self) -> RuleMetadata<'_> {
+        METADATA
+    }
+
+    fn check_node(
+        &self,
+        node: NodeRef<'_>,
+        cx: &LintContext<'_>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let Some(severity) = self.options.level.severity() else {
+            return;
+        };
+        if node.kind() != "ASTFunctionCall" {
+            return;
+        }
+        let Some((label, suggestion)) = exact_aggregate(&node, cx) else {
+            return;
+        };
+        let Some(reason) = self.reason(&node, cx.sql) else {
+            return;
+        };
+        diagnostics.push(
+            Diagnostic::for_node(
+                RULE_ID,
+                severity,
+                &node,
+                format!("{} is computed exactly over a large input", label),
+            )
+            .with_note(reason)
+            .with_help(format!("if an estimate is enough, use {}", suggestion)),
+        );
+    }
+}
+
+/// How a diagnostic names `call`, e.g. `COUNT(DISTINCT)`, and the approximate
+/// alternative to suggest, when `call` aggregates rows exactly and
+/// [`APPROXIMATIONS`] has an alternative to it.
+fn exact_aggregate(call: &NodeRef<'_>, cx: &LintContext<'_>) -> Option<(String, &'static str)> {
+    let src = cx.sql;
+    let name = call
+        .child(0)
+        .filter(|name| name.kind() == "ASTPathExpression")?;
+    let text = get_node_text(&name, src).trim();
+    // `PERCENTILE_CONT` and `PERCENTILE_DISC` are analytic rather than
+    // aggregate functions, but likewise hold every row of their window.
+    let is_analytic = call
+        .parent()
+        .is_some_and(|parent| parent.kind() == "ASTAnalyticFunctionCall");
+    if !is_aggregate(text, cx.udfs) && !is_analytic {
+        return None;
+    }
+    let &(function, distinct_only, suggestion) = APPROXIMATIONS
+        .iter()
+        .find(|(function, _, _)| function.eq_ignore_ascii_case(text))?;
+    if !distinct_only {
+        return Some((function.to_string(), suggestion));
+    }
+    // The tree has no DISTINCT flag, so look for the keyword after the
+    // opening parenthesis.
+    let arguments = src.get(name.end_byte()..call.end_byte())?.trim_start();
+    let first_word = arguments
+        .strip_prefix('(')?
+        .trim_start()
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .next()?;
+    first_word
+        .eq_ignore_ascii_case("DISTINCT")
+        .then(|| (format!("{}(DISTINCT)", function), suggestion))
+}
+
+/// True when the query `select` heads has a `LIMIT`.
+fn has_limit(select: &NodeRef<'_>) -> bool {
+    select
+        .parent()
+        .filter(|parent| parent.kind() == "ASTQuery")
+        .is_some_and(|query| find_child_of_kind(&query, "ASTLimitOffset").is_some())
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::indexing_slicing,
+    clippy::arithmetic_side_effects,
+    reason = "test code"
+)]
+mod tests {
+    use super::*;
+    use crate::rules::helpers::run_rule;
+    use rstest::rstest;
+
+    fn rule() -> ExactAggregation {
+        ExactAggregation::new(ExactAggregationOptions {
+            level: FunctionLevel::Warning,
+            large_tables: vec!["analytics.events".to_string()],
+            partition_columns: vec!["event_date".to_string()],
+        })
+    }
+
+    #[rstest]
+    #[case("SELECT COUNT(DISTINCT user_id) FROM logs")]
+    #[case("SELECT count( distinct user_id) FROM logs")]
+    #[case("SELECT ARRAY_AGG(DISTINCT tag) FROM logs")]
+    #[case("SELECT PERCENTILE_CONT(latency, 0.5) OVER () FROM logs")]
+    #[case(
+        "SELECT COUNT(DISTINCT user_id) FROM `project.analytics.events` WHERE event_date = '2024-01-01'"
+    )]
+    #[case("SELECT COUNT(DISTINCT user_id) FROM analytics.events LIMIT 10")]
+    #[case("SELECT COUNT(DISTINCT user_id) FROM logs WHERE country = 'JP'")]
+    fn flags_exact_aggregates_over_large_inputs(#[case] sql: &str) {
+        assert_eq!(run_rule(&rule(), sql).len(), 1, "{sql}");
+    }
+
+    #[rstest]
+    #[case("SELECT APPROX_COUNT_DISTINCT(user_id) FROM logs")]
+    #[case("SELECT COUNT(user_id) FROM logs")]
+    #[case("SELECT ARRAY_AGG(tag) FROM logs")]
+    #[case("SELECT COUNT(DISTINCT user_id) FROM logs WHERE event_date = '2024-01-01'")]
+    #[case("SELECT COUNT(DISTINCT user_id) FROM logs WHERE _PARTITIONTIME >= '2024-01-01'")]
+    #[case("SELECT COUNT(DISTINCT user_id) FROM logs_* WHERE _TABLE_SUFFIX = '20240101'")]
+    #[case("SELECT COUNT(DISTINCT user_id) FROM logs LIMIT 10")]
+    #[case("SELECT COUNT(DISTINCT x) FROM UNNEST([1, 2, 2]) AS x")]
+    #[case("SELECT COUNT(DISTINCT user_id) FROM main.events WHERE event_date = '2024-01-01'")]
+    fn accepts_approximate_or_bounded_aggregates(#[case] sql: &str) {
+        assert!(run_rule(&rule(), sql).is_empty(), "{sql}");
+    }
+
+    #[test]
+    fn is_off_by_default() {
+        let sql = "SELECT COUNT(DISTINCT user_id) FROM logs";
+        assert!(run_rule(&ExactAggregation::default(), sql).is_empty());
+    }
+
+    #[test]
+    fn names_the_large_table_and_the_alternative() {
+        let sql =
+            "SELECT COUNT(DISTINCT user_id) FROM analytics.events WHERE event_date = '2024-01-01'";
+        let diagnostics = run_rule(&rule(), sql);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(
+            diagnostic.message(),
+            "COUNT(DISTINCT) is computed exactly over a large input"
+        );
+        assert_eq!(
+            diagnostic.notes(),
+            ["`analytics.events` is configured as a large table"]
+        );
+        assert!(diagnostic.help().unwrap().contains("APPROX_COUNT_DISTINCT"));
+    }
+}
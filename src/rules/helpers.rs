@@ -1,4 +1,8 @@
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
 use crate::ast::NodeRef;
+use crate::udf::UdfCatalog;
 
 /// Extract text content from a node, if its byte range maps to valid UTF-8
 /// within `sql`.
@@ -37,6 +41,13 @@ pub fn one_based_start(node: &NodeRef<'_>) -> (usize, usize) {
     (point.row.saturating_add(1), point.column.saturating_add(1))
 }
 
+/// 1-based (row, col) of a node's end position, exclusive; see
+/// [`one_based_start`].
+pub fn one_based_end(node: &NodeRef<'_>) -> (usize, usize) {
+    let point = node.end_position();
+    (point.row.saturating_add(1), point.column.saturating_add(1))
+}
+
 /// Find the first named child node with the specified kind
 pub fn find_child_of_kind<'a>(node: &NodeRef<'a>, kind: &str) -> Option<NodeRef<'a>> {
     node.named_children()
@@ -63,6 +74,92 @@ pub fn find_parent_select<'a>(node: &NodeRef<'a>) -> Option<NodeRef<'a>> {
     None
 }
 
+/// The `ASTSelect` a query evaluates, looking through extra parentheses.
+/// `None` for set operations, whose branches would each need checking.
+pub fn query_select(query: NodeRef<'_>) -> Option<NodeRef<'_>> {
+    query
+        .named_children()
+        .into_iter()
+        .find_map(|child| match child.kind() {
+            "ASTSelect" => Some(child),
+            "ASTQuery" => query_select(child),
+            _ => None,
+        })
+}
+
+/// The branches of an `ASTSetOperation`, in source order.
+///
+/// googlesql flattens a chain of the same operator (`a UNION ALL b UNION ALL
+/// c`) into one node with a branch per operand; parenthesized operands are
+/// `ASTQuery` nodes, the rest `ASTSelect`. The operator metadata children are
+/// left out.
+pub fn set_operation_branches<'a>(node: &NodeRef<'a>) -> Vec<NodeRef<'a>> {
+    node.named_children()
+        .into_iter()
+        .filter(|child| matches!(child.kind(), "ASTSelect" | "ASTQuery"))
+        .collect()
+}
+
+/// The operators between consecutive branches of an `ASTSetOperation`,
+/// uppercased with single spaces (`"UNION DISTINCT"`, `"EXCEPT ALL"`, ...).
+///
+/// The tree carries no operator attribute, so this reads the source between
+/// the branches, dropping comments and the parentheses around them.
+pub fn set_operators(node: &NodeRef<'_>, sql: &str) -> Vec<String> {
+    set_operation_branches(node)
+        .windows(2)
+        .filter_map(|pair| match pair {
+            [left, right] => sql.get(left.end_byte()..right.start_byte()),
+            _ => None,
+        })
+        .map(operator_text)
+        .collect()
+}
+
+/// The operator of an `ASTBinaryExpression`, uppercased with single spaces
+/// (`"="`, `"<="`, `"NOT LIKE"`, `"IS NOT"`, ...).
+///
+/// Like set operators, it is read from the source between the first and last
+/// operands, dropping comments and the parentheses around them.
+pub fn binary_operator(node: &NodeRef<'_>, sql: &str) -> Option<String> {
+    if node.kind() != "ASTBinaryExpression" {
+        return None;
+    }
+    let (lhs, rhs) = (node.child(0)?, node.children().into_iter().last()?);
+    let operator = operator_text(sql.get(lhs.end_byte()..rhs.start_byte())?);
+    (!operator.is_empty()).then_some(operator)
+}
+
+/// The operator written in `between`, the source between two operands:
+/// its words uppercased and joined by single spaces, without the `--`, `#`
+/// and `/* */` comments and parentheses around them.
+fn operator_text(between: &str) -> String {
+    let mut text = String::with_capacity(between.len());
+    let mut chars = between.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('-', Some('-')) | ('#', _) => {
+                chars.by_ref().find(|&c| c == '\n');
+                text.push(' ');
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                chars
+                    .by_ref()
+                    .find(|&c| std::mem::replace(&mut previous, c) == '*' && c == '/');
+                text.push(' ');
+            }
+            _ => text.push(c),
+        }
+    }
+    text.split(|c: char| c == '(' || c == ')' || c.is_whitespace())
+        .filter(|token| !token.is_empty())
+        .map(str::to_ascii_uppercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 /// Check if a node is a function name (the name part of a function call).
 ///
 /// On googlesql the name identifier is wrapped in an `ASTPathExpression` that is
@@ -90,6 +187,180 @@ pub fn is_function_name(node: &NodeRef<'_>) -> bool {
     }
 }
 
+/// BigQuery aggregate functions (uppercase, matched case-insensitively).
+///
+/// Reference: <https://cloud.google.com/bigquery/docs/reference/standard-sql/aggregate_functions>
+static AGGREGATE_FUNCTIONS: LazyLock<HashSet<&'static str>> = LazyLock::new(|| {
+    [
+        // Standard aggregate functions
+        "ANY_VALUE",
+        "ARRAY_AGG",
+        "ARRAY_CONCAT_AGG",
+        "AVG",
+        "BIT_AND",
+        "BIT_OR",
+        "BIT_XOR",
+        "COUNT",
+        "COUNTIF",
+        "GROUPING",
+        "LOGICAL_AND",
+        "LOGICAL_OR",
+        "MAX",
+        "MAX_BY",
+        "MIN",
+        "MIN_BY",
+        "STRING_AGG",
+        "SUM",
+        // Approximate aggregate functions
+        "APPROX_COUNT_DISTINCT",
+        "APPROX_QUANTILES",
+        "APPROX_TOP_COUNT",
+        "APPROX_TOP_SUM",
+        // Statistical aggregate functions
+        "CORR",
+        "COVAR_POP",
+        "COVAR_SAMP",
+        "STDDEV",
+        "STDDEV_POP",
+        "STDDEV_SAMP",
+        "VAR_POP",
+        "VAR_SAMP",
+        "VARIANCE",
+        // Geography aggregate functions
+        "ST_CENTROID_AGG",
+        "ST_UNION_AGG",
+    ]
+    .into_iter()
+    .collect()
+});
+
+/// True when `name` is a built-in aggregate function or a user-defined
+/// aggregate in `udfs`.
+pub fn is_aggregate(name: &str, udfs: &UdfCatalog) -> bool {
+    AGGREGATE_FUNCTIONS.contains(name.to_uppercase().as_str()) || udfs.is_aggregate(name)
+}
+
+/// True for the node kinds that can stand on either side of a join.
+pub fn is_table_expression(kind: &str) -> bool {
+    matches!(
+        kind,
+        "ASTTablePathExpression"
+            | "ASTTableSubquery"
+            | "ASTJoin"
+            | "ASTParenthesizedJoin"
+            | "ASTTVF"
+    )
+}
+
+/// The lowercase names columns of the relations in `item` are qualified with:
+/// each table's alias, or the last part of its path when it has none.
+pub fn qualifiers(item: &NodeRef<'_>, src: &str) -> HashSet<String> {
+    match item.kind() {
+        "ASTJoin" | "ASTParenthesizedJoin" => item
+            .named_children()
+            .iter()
+            .filter(|child| is_table_expression(child.kind()))
+            .flat_map(|child| qualifiers(child, src))
+            .collect(),
+        _ => {
+            let alias = find_child_of_kind(item, "ASTAlias")
+                .and_then(|alias| alias.named_children().into_iter().last())
+                .map(|name| get_node_text(&name, src));
+            let implicit = table_path(item)
+                .map(|path| get_node_text(&path, src))
+                .and_then(|path| path.rsplit('.').next());
+            alias
+                .or(implicit)
+                .map(|name| name.trim_matches('`').to_lowercase())
+                .into_iter()
+                .collect()
+        }
+    }
+}
+
+/// The path naming the table of a table item, `None` for UNNEST, subqueries
+/// and table functions.
+pub fn table_path<'a>(item: &NodeRef<'a>) -> Option<NodeRef<'a>> {
+    if item.kind() != "ASTTablePathExpression" {
+        return None;
+    }
+    item.named_child(0)
+        .filter(|path| path.kind() == "ASTPathExpression")
+}
+
+/// True when `item` is `UNNEST(...)` or a correlated array path such as the
+/// `t.items` in `FROM t, t.items`, which are flattened rather than crossed.
+pub fn is_array_item(item: &NodeRef<'_>, left: &HashSet<String>, src: &str) -> bool {
+    if item.kind() == "ASTTablePathExpression"
+        && find_child_of_kind(item, "ASTUnnestExpression").is_some()
+    {
+        return true;
+    }
+    table_path(item).is_some_and(|path| {
+        let text = get_node_text(&path, src);
+        text.contains('.')
+            && text
+                .split('.')
+                .next()
+                .is_some_and(|first| left.contains(&first.trim_matches('`').to_lowercase()))
+    })
+}
+
+/// The name a diagnostic calls the relation `item` by.
+pub fn relation_name<'a>(item: &NodeRef<'_>, src: &'a str) -> &'a str {
+    table_path(item).map_or_else(
+        || get_node_text(item, src),
+        |path| get_node_text(&path, src),
+    )
+}
+
+/// `expr` split at its top-level `AND`s.
+pub fn conjuncts(expr: NodeRef<'_>) -> Vec<NodeRef<'_>> {
+    if expr.kind() == "ASTAndExpr" {
+        expr.named_children()
+            .into_iter()
+            .flat_map(conjuncts)
+            .collect()
+    } else {
+        vec![expr]
+    }
+}
+
+/// True when `expr` references a column of each side. Unqualified columns
+/// cannot be attributed without a schema, so an expression comparing one with
+/// another column is given the benefit of the doubt.
+pub fn relates(
+    expr: &NodeRef<'_>,
+    left: &HashSet<String>,
+    right: &HashSet<String>,
+    src: &str,
+) -> bool {
+    let mut columns = 0_usize;
+    let mut unqualified = false;
+    let (mut uses_left, mut uses_right) = (false, false);
+    for node in expr.pre_order() {
+        if node.kind() != "ASTPathExpression"
+            || is_function_name(&node)
+            || node
+                .parent()
+                .is_some_and(|parent| parent.kind() == "ASTTablePathExpression")
+        {
+            continue;
+        }
+        columns = columns.saturating_add(1);
+        let text = get_node_text(&node, src);
+        match text.split_once('.') {
+            Some((qualifier, _)) => {
+                let qualifier = qualifier.trim_matches('`').to_lowercase();
+                uses_left |= left.contains(&qualifier);
+                uses_right |= right.contains(&qualifier);
+            }
+            None => unqualified = true,
+        }
+    }
+    (uses_left && uses_right) || (unqualified && columns >= 2)
+}
+
 /// Parse `sql` into a neutral [`crate::ast::Ast`] via the googlesql (ZetaSQL)
 /// backend (test helper).
 ///
@@ -121,6 +392,26 @@ pub fn run_rule<R: crate::rules::rule::Rule>(
     rule.check(&ast, sql)
 }
 
+/// Like [`run_rule`], with the tables `schema` declares in the catalog.
+#[cfg(test)]
+pub fn run_rule_with_schema<R: crate::rules::rule::Rule>(
+    rule: &R,
+    sql: &str,
+    schema: &crate::schema::SchemaConfig,
+) -> Vec<crate::diagnostic::Diagnostic> {
+    let ast = parse_sql(sql);
+    let udfs = crate::udf::UdfCatalog::default();
+    let schema = crate::schema::SchemaCatalog::new(schema);
+    rule.check_with(
+        &ast,
+        &crate::rules::rule::LintContext {
+            sql,
+            udfs: &udfs,
+            schema: &schema,
+        },
+    )
+}
+
 #[cfg(test)]
 #[allow(
     clippy::unwrap_used,
@@ -198,6 +489,14 @@ mod tests {
         assert_eq!(one_based_start(&node), (1, 8));
     }
 
+    #[test]
+    fn one_based_end_is_exclusive() {
+        let sql = "SELECT col1 FROM t";
+        let ast = parse_sql(sql);
+        let node = col1_ref(&ast, sql).expect("expected to find the col1 reference");
+        assert_eq!(one_based_end(&node), (1, 12));
+    }
+
     #[test]
     fn test_find_child_of_kind() {
         let sql = "SELECT col1 FROM table1 GROUP BY col1";
@@ -239,4 +538,44 @@ mod tests {
             "Should not have non-existent kind"
         );
     }
+
+    #[test]
+    fn operator_text_drops_comments_and_parentheses() {
+        assert_eq!(
+            operator_text(" ) /* c */ UNION distinct ( "),
+            "UNION DISTINCT"
+        );
+        assert_eq!(operator_text(" -- keep all\n  union all\n"), "UNION ALL");
+        assert_eq!(
+            operator_text(" # note\nEXCEPT /**/ DISTINCT"),
+            "EXCEPT DISTINCT"
+        );
+        assert_eq!(operator_text(" not /* a */ like "), "NOT LIKE");
+        assert_eq!(operator_text(" <= "), "<=");
+        assert_eq!(operator_text(" /* unterminated"), "");
+    }
+
+    #[test]
+    fn set_operators_ignore_comments_between_branches() {
+        let sql = "SELECT 'a', x FROM t /* c */ UNION DISTINCT SELECT 'b', x FROM u";
+        let ast = parse_sql(sql);
+        let operation = ast
+            .pre_order()
+            .into_iter()
+            .find(|node| node.kind() == "ASTSetOperation")
+            .expect("Should find the set operation");
+        assert_eq!(set_operators(&operation, sql), ["UNION DISTINCT"]);
+    }
+
+    #[test]
+    fn binary_operator_ignores_comments_around_it() {
+        let sql = "SELECT * FROM t WHERE a -- why\n >= /* bound */ b";
+        let ast = parse_sql(sql);
+        let comparison = ast
+            .pre_order()
+            .into_iter()
+            .find(|node| node.kind() == "ASTBinaryExpression")
+            .expect("Should find the comparison");
+        assert_eq!(binary_operator(&comparison, sql).as_deref(), Some(">="));
+    }
 }
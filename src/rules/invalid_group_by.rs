@@ -1,89 +1,117 @@
 use std::collections::HashSet;
-use std::sync::LazyLock;
+
+use serde::{Deserialize, Serialize};
 
 use crate::ast::NodeRef;
-use crate::diagnostic::{Diagnostic, Severity};
-use crate::rules::helpers::{find_child_of_kind, get_node_text, is_function_name, one_based_start};
-use crate::rules::rule::Rule;
+use crate::diagnostic::{Diagnostic, Related, Severity};
+use crate::rules::helpers::{find_child_of_kind, get_node_text, is_aggregate, is_function_name};
+use crate::rules::rule::{Category, LintContext, Rule, RuleMetadata};
+use crate::udf::UdfCatalog;
 
 const RULE_ID: &str = "invalid_group_by";
 
-/// BigQuery aggregate functions (uppercase, matched case-insensitively).
-///
-/// Reference: <https://cloud.google.com/bigquery/docs/reference/standard-sql/aggregate_functions>
-static AGGREGATE_FUNCTIONS: LazyLock<HashSet<&'static str>> = LazyLock::new(|| {
-    [
-        // Standard aggregate functions
-        "ANY_VALUE",
-        "ARRAY_AGG",
-        "ARRAY_CONCAT_AGG",
-        "AVG",
-        "BIT_AND",
-        "BIT_OR",
-        "BIT_XOR",
-        "COUNT",
-        "COUNTIF",
-        "GROUPING",
-        "LOGICAL_AND",
-        "LOGICAL_OR",
-        "MAX",
-        "MAX_BY",
-        "MIN",
-        "MIN_BY",
-        "STRING_AGG",
-        "SUM",
-        // Approximate aggregate functions
-        "APPROX_COUNT_DISTINCT",
-        "APPROX_QUANTILES",
-        "APPROX_TOP_COUNT",
-        "APPROX_TOP_SUM",
-        // Statistical aggregate functions
-        "CORR",
-        "COVAR_POP",
-        "COVAR_SAMP",
-        "STDDEV",
-        "STDDEV_POP",
-        "STDDEV_SAMP",
-        "VAR_POP",
-        "VAR_SAMP",
-        "VARIANCE",
-        // Geography aggregate functions
-        "ST_CENTROID_AGG",
-        "ST_UNION_AGG",
-    ]
-    .into_iter()
-    .collect()
-});
+const METADATA: RuleMetadata<'static> = RuleMetadata {
+    name: "Invalid GROUP BY usage",
+    default_severity: Severity::Error,
+    default_enabled: true,
+    category: Category::Correctness,
+    summary: "A selected column is neither grouped by nor aggregated.",
+    description: "When a query has a `GROUP BY`, every column in the `SELECT` list must either appear in the `GROUP BY` clause or be used inside an aggregate function. BigQuery rejects the query otherwise.",
+    bad_example: Some("SELECT col1, col2, COUNT(*) AS cnt FROM my_table GROUP BY col1"),
+    good_example: Some("SELECT col1, col2, COUNT(*) AS cnt FROM my_table GROUP BY col1, col2"),
+    doc_url: Some(
+        "https://github.com/hirosassa/bqvalid/blob/main/docs/rules.md#invalid-group-by-usage",
+    ),
+    fixable: false,
+};
+
+/// `[rules.invalid_group_by]` options.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InvalidGroupByOptions {
+    /// Functions treated as aggregates in addition to the built-in ones,
+    /// e.g. `HLL_COUNT.MERGE` or a user-defined aggregate.
+    #[serde(default)]
+    pub extra_aggregates: Vec<String>,
+}
 
 /// Flags SELECT columns that are neither grouped nor aggregated, which BigQuery
 /// rejects at runtime.
-pub struct InvalidGroupBy;
+#[derive(Default)]
+pub struct InvalidGroupBy {
+    /// The configured extra aggregates, uppercased for matching.
+    extra_aggregates: HashSet<String>,
+}
+
+impl InvalidGroupBy {
+    #[must_use]
+    pub fn new(options: &InvalidGroupByOptions) -> Self {
+        Self {
+            extra_aggregates: options
+                .extra_aggregates
+                .iter()
+                .map(|name| name.to_uppercase())
+                .collect(),
+        }
+    }
+}
 
 impl Rule for InvalidGroupBy {
-    fn id(&self) -> &'static str {
+    fn id(&self) -> &str {
         RULE_ID
     }
 
-    fn check_node(&self, node: NodeRef<'_>, sql: &str, diagnostics: &mut Vec<Diagnostic>) {
+    fn metadata(&self) -> RuleMetadata<'_> {
+        METADATA
+    }
+
+    fn check_node(
+        &self,
+        node: NodeRef<'_>,
+        cx: &LintContext<'_>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let aggregates = Aggregates {
+            extra: &self.extra_aggregates,
+            udfs: cx.udfs,
+        };
         if node.kind() == "ASTSelect"
-            && let Some(diags) = check_select(&node, sql)
+            && let Some(diags) = check_select(&node, cx.sql, &aggregates)
         {
             diagnostics.extend(diags);
         }
     }
 }
 
-fn check_select(node: &NodeRef<'_>, sql: &str) -> Option<Vec<Diagnostic>> {
-    let group_by_columns = extract_group_by_columns(node, sql)?;
+/// The functions that count as aggregates: those [`is_aggregate`] knows and
+/// the configured extras (uppercased).
+struct Aggregates<'a> {
+    extra: &'a HashSet<String>,
+    udfs: &'a UdfCatalog,
+}
+
+impl Aggregates<'_> {
+    fn contains(&self, name: &str) -> bool {
+        is_aggregate(name, self.udfs) || self.extra.contains(&name.to_uppercase())
+    }
+}
+
+fn check_select(
+    node: &NodeRef<'_>,
+    sql: &str,
+    aggregates: &Aggregates<'_>,
+) -> Option<Vec<Diagnostic>> {
+    let group_by = find_child_of_kind(node, "ASTGroupBy")?;
+    let group_by_columns = extract_group_by_columns(&group_by, sql);
 
     let select_list = find_child_of_kind(node, "ASTSelectList")?;
 
     let mut diagnostics = Vec::new();
     for child in select_list.named_children() {
         if child.kind() == "ASTSelectColumn"
-            && let Some(diag) = check_select_expression(&child, sql, &group_by_columns)
+            && let Some(diag) = check_select_expression(&child, sql, &group_by_columns, aggregates)
         {
-            diagnostics.push(diag);
+            diagnostics.push(diag.with_related(Related::for_node(&group_by, "GROUP BY clause")));
         }
     }
 
@@ -94,8 +122,7 @@ fn check_select(node: &NodeRef<'_>, sql: &str) -> Option<Vec<Diagnostic>> {
     }
 }
 
-fn extract_group_by_columns(select_node: &NodeRef<'_>, sql: &str) -> Option<HashSet<String>> {
-    let group_by_node = find_child_of_kind(select_node, "ASTGroupBy")?;
+fn extract_group_by_columns(group_by_node: &NodeRef<'_>, sql: &str) -> HashSet<String> {
     let mut columns = HashSet::new();
 
     for node in group_by_node.pre_order() {
@@ -105,36 +132,39 @@ fn extract_group_by_columns(select_node: &NodeRef<'_>, sql: &str) -> Option<Hash
         }
     }
 
-    Some(columns)
+    columns
 }
 
 fn check_select_expression(
     expr_node: &NodeRef<'_>,
     sql: &str,
     group_by_columns: &HashSet<String>,
+    aggregates: &Aggregates<'_>,
 ) -> Option<Diagnostic> {
     // Check if this expression contains an identifier that's not in an aggregate function
     for node in expr_node.pre_order() {
         if node.kind() == "ASTIdentifier"
             && !is_alias(&node)
             && !is_function_name(&node)
-            && !is_in_aggregate_function(&node, sql)
+            && !is_in_aggregate_function(&node, sql, aggregates)
         {
             let field_text = get_node_text(&node, sql);
 
             // Check if the identifier is in GROUP BY
             if !group_by_columns.contains(field_text) {
-                let (row, col) = one_based_start(&node);
-                return Some(Diagnostic::new(
+                return Some(Diagnostic::for_node(
                     RULE_ID,
                     Severity::Error,
-                    row,
-                    col,
+                    &node,
                     format!(
                         "Column '{}' must appear in the GROUP BY clause or be used in an aggregate function",
                         field_text
                     ),
-                ));
+                )
+                .with_help(format!(
+                    "add '{}' to the GROUP BY clause or wrap it in an aggregate such as ANY_VALUE",
+                    field_text
+                )));
             }
         }
     }
@@ -148,18 +178,18 @@ fn is_alias(node: &NodeRef<'_>) -> bool {
         .is_some_and(|parent| parent.kind() == "ASTAlias")
 }
 
-fn is_in_aggregate_function(node: &NodeRef<'_>, sql: &str) -> bool {
+/// True when `node` sits inside a call to one of the `aggregates`.
+fn is_in_aggregate_function(node: &NodeRef<'_>, sql: &str, aggregates: &Aggregates<'_>) -> bool {
     let mut current = node.parent();
 
     while let Some(parent) = current {
         if parent.kind() == "ASTFunctionCall" {
             // googlesql has no field names, so the function name is the first
             // child (an `ASTPathExpression` wrapping the name).
-            if let Some(func_node) = parent.child(0) {
-                let func_name = get_node_text(&func_node, sql);
-                if AGGREGATE_FUNCTIONS.contains(func_name.to_uppercase().as_str()) {
-                    return true;
-                }
+            if let Some(func_node) = parent.child(0)
+                && aggregates.contains(get_node_text(&func_node, sql))
+            {
+                return true;
             }
         }
         current = parent.parent();
@@ -180,6 +210,7 @@ fn is_in_aggregate_function(node: &NodeRef<'_>, sql: &str) -> bool {
 mod tests {
     use super::*;
     use crate::rules::helpers::{parse_sql, run_rule};
+    use crate::schema::SchemaCatalog;
     use rstest::rstest;
 
     #[rstest]
@@ -232,7 +263,7 @@ GROUP BY t1.col1, col2;
         1
     )]
     fn test_invalid_group_by(#[case] sql: &str, #[case] expected_count: usize) {
-        let diagnostics = run_rule(&InvalidGroupBy, sql);
+        let diagnostics = run_rule(&InvalidGroupBy::default(), sql);
         assert_eq!(
             diagnostics.len(),
             expected_count,
@@ -336,7 +367,7 @@ GROUP BY t1.user_id, t2.category;
         // Some cases bundle several independent valid statements; googlesql parses
         // one statement at a time, so run each `;`-separated statement on its own.
         for statement in sql.split(';').filter(|s| !s.trim().is_empty()) {
-            let diagnostics = run_rule(&InvalidGroupBy, statement);
+            let diagnostics = run_rule(&InvalidGroupBy::default(), statement);
             assert!(
                 diagnostics.is_empty(),
                 "Expected no diagnostics for valid GROUP BY, got {} for: {statement}",
@@ -349,12 +380,25 @@ GROUP BY t1.user_id, t2.category;
     fn points_at_the_ungrouped_column() {
         let sql = "SELECT col1, col2, COUNT(*) AS cnt FROM my_table GROUP BY col1";
         let col2 = sql.find("col2").expect("query selects col2");
-        let diagnostics = run_rule(&InvalidGroupBy, sql);
+        let diagnostics = run_rule(&InvalidGroupBy::default(), sql);
         assert_eq!(diagnostics.len(), 1);
         assert_eq!(diagnostics[0].row(), 1);
         assert_eq!(diagnostics[0].col(), col2 + 1);
     }
 
+    #[test]
+    fn points_back_at_the_group_by_clause() {
+        let sql = "SELECT col1, col2 FROM my_table GROUP BY col1";
+        let group_by = sql.find("GROUP BY").expect("query has GROUP BY");
+        let diagnostics = run_rule(&InvalidGroupBy::default(), sql);
+        assert_eq!(diagnostics.len(), 1);
+        let related = diagnostics[0].related();
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].start, (1, group_by + 1));
+        assert_eq!(related[0].message, "GROUP BY clause");
+        assert!(diagnostics[0].help().unwrap().contains("ANY_VALUE"));
+    }
+
     #[test]
     fn test_is_alias() {
         // On googlesql the alias name is an `ASTIdentifier` under an `ASTAlias`,
@@ -424,7 +468,14 @@ GROUP BY t1.user_id, t2.category;
                 if text == "col1" {
                     saw_col1 = true;
                     assert!(
-                        is_in_aggregate_function(&node, sql),
+                        is_in_aggregate_function(
+                            &node,
+                            sql,
+                            &Aggregates {
+                                extra: &HashSet::new(),
+                                udfs: &UdfCatalog::default(),
+                            }
+                        ),
                         "col1 should be recognized as inside aggregate function"
                     );
                 }
@@ -432,4 +483,41 @@ GROUP BY t1.user_id, t2.category;
         }
         assert!(saw_col1, "the col1 identifier must be visited");
     }
+
+    #[test]
+    fn configured_extra_aggregates_count_as_aggregates() {
+        let rule = InvalidGroupBy::new(&InvalidGroupByOptions {
+            extra_aggregates: vec!["HLL_COUNT.MERGE".to_string(), "my_udf_agg".to_string()],
+        });
+        let sql = "SELECT k, hll_count.merge(sketch), MY_UDF_AGG(v) FROM t GROUP BY k";
+        assert!(run_rule(&rule, sql).is_empty());
+
+        // Without the option both arguments are reported as ungrouped columns.
+        assert!(!run_rule(&InvalidGroupBy::default(), sql).is_empty());
+    }
+
+    #[test]
+    fn user_defined_aggregates_in_scope_count_as_aggregates() {
+        let script = "CREATE TEMP AGGREGATE FUNCTION my_agg(x INT64) AS (SUM(x));\n\
+                      SELECT k, my_agg(v) FROM t GROUP BY k";
+        let mut module = crate::build_module().unwrap();
+        let parsed = module.parse_statements(script).unwrap();
+        let asts: Vec<crate::ast::Ast> = parsed
+            .statements()
+            .iter()
+            .map(|statement| crate::ast::Ast::from_googlesql_root(statement.root(), script))
+            .collect();
+        let udfs = UdfCatalog::from_script(&asts, script);
+        let schema = SchemaCatalog::default();
+        let cx = LintContext {
+            sql: script,
+            udfs: &udfs,
+            schema: &schema,
+        };
+
+        let rule = InvalidGroupBy::default();
+        assert!(rule.check_with(&asts[1], &cx).is_empty());
+        // Without the catalog `v` looks like an ungrouped column.
+        assert_eq!(rule.check(&asts[1], script).len(), 1);
+    }
 }
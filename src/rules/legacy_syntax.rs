@@ -0,0 +1,477 @@
+use std::iter::Peekable;
+use std::ops::Range;
+
+use crate::ast::{NodeRef, point_in};
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::rules::helpers::get_node_text;
+use crate::rules::rule::{Category, LintContext, Rule, RuleMetadata};
+
+const RULE_ID: &str = "legacy_syntax";
+
+const METADATA: RuleMetadata<'static> = RuleMetadata {
+    name: "Legacy SQL syntax",
+    default_severity: Severity::Warning,
+    default_enabled: true,
+    category: Category::Correctness,
+    summary: "The query uses legacy SQL syntax or a function standard SQL supersedes.",
+    description: "Queries migrated from legacy SQL often keep its bracketed table names (`[project:dataset.table]`) and functions such as `TABLE_DATE_RANGE`, `FLATTEN` and `NEST`, which standard SQL rejects, and `JSON_EXTRACT`/`JSON_EXTRACT_SCALAR`, which `JSON_QUERY`/`JSON_VALUE` supersede. The rule points at each one with its standard SQL replacement, including in the part of a file the parser gave up on, so a migration leftover gets a targeted message rather than only a syntax error.",
+    bad_example: Some("SELECT JSON_EXTRACT_SCALAR(payload, '$.id') FROM events"),
+    good_example: Some("SELECT JSON_VALUE(payload, '$.id') FROM events"),
+    doc_url: Some("https://github.com/hirosassa/bqvalid/blob/main/docs/rules.md#legacy-sql-syntax"),
+    fixable: false,
+};
+
+/// A function carried over from legacy SQL, or superseded in standard SQL.
+struct LegacyFunction {
+    name: &'static str,
+    /// What to write instead.
+    replacement: &'static str,
+    /// Whether standard SQL rejects the function outright, rather than still
+    /// accepting it.
+    rejected: bool,
+}
+
+const LEGACY_FUNCTIONS: [LegacyFunction; 8] = [
+    LegacyFunction {
+        name: "FLATTEN",
+        replacement: "UNNEST in the FROM clause",
+        rejected: true,
+    },
+    LegacyFunction {
+        name: "NEST",
+        replacement: "ARRAY_AGG",
+        rejected: true,
+    },
+    LegacyFunction {
+        name: "TABLE_DATE_RANGE",
+        replacement: "a wildcard table filtered on _TABLE_SUFFIX",
+        rejected: true,
+    },
+    LegacyFunction {
+        name: "TABLE_QUERY",
+        replacement: "a wildcard table filtered on _TABLE_SUFFIX",
+        rejected: true,
+    },
+    LegacyFunction {
+        name: "JSON_EXTRACT",
+        replacement: "JSON_QUERY",
+        rejected: false,
+    },
+    LegacyFunction {
+        name: "JSON_EXTRACT_ARRAY",
+        replacement: "JSON_QUERY_ARRAY",
+        rejected: false,
+    },
+    LegacyFunction {
+        name: "JSON_EXTRACT_SCALAR",
+        replacement: "JSON_VALUE",
+        rejected: false,
+    },
+    LegacyFunction {
+        name: "JSON_EXTRACT_STRING_ARRAY",
+        replacement: "JSON_VALUE_ARRAY",
+        rejected: false,
+    },
+];
+
+/// Flags legacy SQL table names and functions, in the parsed tree and in the
+/// source the parser gave up on.
+pub struct LegacySyntax;
+
+impl Rule for LegacySyntax {
+    fn id(&self) -> &str {
+        RULE_ID
+    }
+
+    fn metadata(&self) -> RuleMetadata<'_> {
+        METADATA
+    }
+
+    fn check_node(
+        &self,
+        node: NodeRef<'_>,
+        cx: &LintContext<'_>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        // `FLATTEN(...)` and `TABLE_DATE_RANGE(...)` in FROM parse as table
+        // functions, the rest as calls; either way the name comes first.
+        if !matches!(node.kind(), "ASTFunctionCall" | "ASTTVF") {
+            return;
+        }
+        let Some(name) = node
+            .child(0)
+            .filter(|name| name.kind() == "ASTPathExpression")
+        else {
+            return;
+        };
+        let text = get_node_text(&name, cx.sql);
+        let unqualified = strip_safe_prefix(text);
+        if cx.udfs.get(text).is_some() {
+            return;
+        }
+        if let Some(function) = legacy_function(unqualified) {
+            diagnostics.push(function_diagnostic(
+                function,
+                Diagnostic::for_node(
+                    RULE_ID,
+                    function_severity(function),
+                    &name,
+                    function_message(function),
+                ),
+            ));
+        }
+    }
+
+    fn check_unparsed(
+        &self,
+        unparsed: Range<usize>,
+        cx: &LintContext<'_>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let Some(text) = cx.sql.get(unparsed.clone()) else {
+            return;
+        };
+        for (range, finding) in scan(text) {
+            let range = range.start.saturating_add(unparsed.start)
+                ..range.end.saturating_add(unparsed.start);
+            let (row, col) = one_based(cx.sql, range.start);
+            let diagnostic = match finding {
+                Finding::Table(name) => Diagnostic::new(
+                    RULE_ID,
+                    Severity::Error,
+                    row,
+                    col,
+                    format!("legacy SQL table name `[{}]`", name),
+                )
+                .with_help(format!("write it as {}", standard_table_name(name))),
+                Finding::Function(function) => function_diagnostic(
+                    function,
+                    Diagnostic::new(
+                        RULE_ID,
+                        function_severity(function),
+                        row,
+                        col,
+                        function_message(function),
+                    ),
+                ),
+            };
+            diagnostics.push(
+                diagnostic
+                    .with_end(one_based(cx.sql, range.end))
+                    .with_span(range)
+                    .with_note("the parser stopped before this point"),
+            );
+        }
+    }
+}
+
+/// Something legacy found in unparsed source.
+enum Finding<'a> {
+    /// A bracketed table name, without the brackets.
+    Table(&'a str),
+    Function(&'static LegacyFunction),
+}
+
+fn legacy_function(name: &str) -> Option<&'static LegacyFunction> {
+    LEGACY_FUNCTIONS
+        .iter()
+        .find(|function| function.name.eq_ignore_ascii_case(name))
+}
+
+/// `name` without a leading `SAFE.`, which any function call may carry.
+fn strip_safe_prefix(name: &str) -> &str {
+    match name.split_once('.') {
+        Some((prefix, rest)) if prefix.eq_ignore_ascii_case("safe") => rest,
+        _ => name,
+    }
+}
+
+const fn function_severity(function: &LegacyFunction) -> Severity {
+    if function.rejected {
+        Severity::Error
+    } else {
+        Severity::Warning
+    }
+}
+
+fn function_message(function: &LegacyFunction) -> String {
+    if function.rejected {
+        format!(
+            "{} is legacy SQL and does not exist in standard SQL",
+            function.name
+        )
+    } else {
+        format!(
+            "{} is superseded by {}",
+            function.name, function.replacement
+        )
+    }
+}
+
+/// `diagnostic` with the help, and for the JSON functions the note, that fit
+/// `function`.
+fn function_diagnostic(function: &LegacyFunction, diagnostic: Diagnostic) -> Diagnostic {
+    let diagnostic = diagnostic.with_help(format!("use {} instead", function.replacement));
+    if function.name.starts_with("JSON_") {
+        diagnostic.with_note(
+            "the JSONPath of the replacement quotes keys with double quotes (`$.\"a.b\"`), not brackets (`$['a.b']`)",
+        )
+    } else {
+        diagnostic
+    }
+}
+
+/// The standard SQL spelling of the legacy table name `[project:dataset.table]`.
+fn standard_table_name(name: &str) -> String {
+    format!("`{}`", name.replacen(':', ".", 1))
+}
+
+/// The 1-based (row, col) of `byte` in `sql`.
+fn one_based(sql: &str, byte: usize) -> (usize, usize) {
+    let point = point_in(sql, byte);
+    (point.row.saturating_add(1), point.column.saturating_add(1))
+}
+
+/// The legacy table names and function calls in `text`, with their byte
+/// ranges in it. Strings, quoted identifiers and comments are skipped.
+///
+/// A bracketed name counts as a table when it contains `:` (the legacy
+/// project separator) or follows `FROM` or `JOIN`; anything else in brackets
+/// is more likely an array literal or subscript.
+fn scan(text: &str) -> Vec<(Range<usize>, Finding<'_>)> {
+    let mut findings = Vec::new();
+    let mut last_word = "";
+    let mut after_dot = false;
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '\'' | '"' | '`' => skip_quoted(&mut chars, c),
+            '#' => skip_line(&mut chars),
+            '-' if chars.peek().is_some_and(|&(_, d)| d == '-') => skip_line(&mut chars),
+            '/' if chars.peek().is_some_and(|&(_, d)| d == '*') => {
+                let mut previous = ' ';
+                for (_, d) in chars.by_ref() {
+                    if previous == '*' && d == '/' {
+                        break;
+                    }
+                    previous = d;
+                }
+            }
+            '[' => {
+                let rest = text.get(start.saturating_add(1)..).unwrap_or_default();
+                let name = rest.split(']').next().unwrap_or_default();
+                let after_from = ["FROM", "JOIN"]
+                    .iter()
+                    .any(|keyword| keyword.eq_ignore_ascii_case(last_word));
+                if rest.len() > name.len()
+                    && is_table_name(name)
+                    && (name.contains(':') || after_from)
+                {
+                    let end = start.saturating_add(name.len()).saturating_add(2);
+                    findings.push((start..end, Finding::Table(name)));
+                    while chars.peek().is_some_and(|&(i, _)| i < end) {
+                        chars.next();
+                    }
+                }
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = start.saturating_add(c.len_utf8());
+                while let Some(&(i, d)) = chars.peek() {
+                    if !(d.is_alphanumeric() || d == '_') {
+                        break;
+                    }
+                    end = i.saturating_add(d.len_utf8());
+                    chars.next();
+                }
+                let word = text.get(start..end).unwrap_or_default();
+                let is_call = text
+                    .get(end..)
+                    .is_some_and(|rest| rest.trim_start().starts_with('('));
+                if !after_dot
+                    && is_call
+                    && let Some(function) = legacy_function(word)
+                {
+                    findings.push((start..end, Finding::Function(function)));
+                }
+                last_word = word;
+                after_dot = false;
+                continue;
+            }
+            _ => {}
+        }
+        if !c.is_whitespace() {
+            after_dot = c == '.';
+        }
+    }
+    findings
+}
+
+/// Skip the rest of a literal or quoted identifier opened by `quote`: a single
+/// quote, or three for a triple-quoted string. A backslash escapes the next
+/// character, also in raw strings, where it stays part of the value but still
+/// keeps a quote from closing the literal.
+fn skip_quoted(chars: &mut Peekable<impl Iterator<Item = (usize, char)>>, quote: char) {
+    let mut closing = 1_usize;
+    if quote != '`' && chars.peek().is_some_and(|&(_, c)| c == quote) {
+        chars.next();
+        if chars.peek().is_none_or(|&(_, c)| c != quote) {
+            // `''` is an empty string.
+            return;
+        }
+        chars.next();
+        closing = 3;
+    }
+    let mut run = 0_usize;
+    while let Some((_, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+            run = 0;
+        } else if c == quote {
+            run = run.saturating_add(1);
+            if run == closing {
+                return;
+            }
+        } else {
+            run = 0;
+        }
+    }
+}
+
+fn skip_line(chars: &mut impl Iterator<Item = (usize, char)>) {
+    for (_, c) in chars {
+        if c == '\n' {
+            break;
+        }
+    }
+}
+
+/// True when `name` looks like a legacy table reference: dotted or with a
+/// project, and made only of identifier characters.
+fn is_table_name(name: &str) -> bool {
+    (name.contains('.') || name.contains(':'))
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':' | '$'))
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::indexing_slicing,
+    clippy::arithmetic_side_effects,
+    reason = "test code"
+)]
+mod tests {
+    use super::*;
+    use crate::rules::helpers::run_rule;
+    use crate::schema::SchemaCatalog;
+    use crate::udf::UdfCatalog;
+    use rstest::rstest;
+
+    /// The diagnostics for `sql`, all of which the parser gave up on.
+    fn check_unparsed(sql: &str) -> Vec<Diagnostic> {
+        let (udfs, schema) = (UdfCatalog::default(), SchemaCatalog::default());
+        let mut diagnostics = Vec::new();
+        LegacySyntax.check_unparsed(
+            0..sql.len(),
+            &LintContext {
+                sql,
+                udfs: &udfs,
+                schema: &schema,
+            },
+            &mut diagnostics,
+        );
+        diagnostics
+    }
+
+    #[rstest]
+    #[case("SELECT JSON_EXTRACT(payload, '$.a') FROM t", Severity::Warning)]
+    #[case("SELECT json_extract_scalar(payload, '$.a') FROM t", Severity::Warning)]
+    #[case("SELECT SAFE.JSON_EXTRACT_ARRAY(payload) FROM t", Severity::Warning)]
+    #[case("SELECT NEST(x) FROM t", Severity::Error)]
+    #[case("SELECT x FROM FLATTEN(t, y)", Severity::Error)]
+    fn flags_legacy_calls(#[case] sql: &str, #[case] severity: Severity) {
+        let diagnostics = run_rule(&LegacySyntax, sql);
+        assert_eq!(diagnostics.len(), 1, "{sql}");
+        assert_eq!(diagnostics[0].severity(), severity, "{sql}");
+    }
+
+    #[rstest]
+    #[case("SELECT JSON_VALUE(payload, '$.a') FROM t")]
+    #[case("SELECT t.nest FROM t")]
+    #[case("SELECT ds.flatten(x) FROM t")]
+    #[case("SELECT x FROM `p.d.t`")]
+    fn accepts_standard_sql(#[case] sql: &str) {
+        assert!(run_rule(&LegacySyntax, sql).is_empty(), "{sql}");
+    }
+
+    #[test]
+    fn points_at_the_name_with_its_replacement() {
+        let sql = "SELECT JSON_EXTRACT_SCALAR(payload, '$.a') FROM t";
+        let diagnostics = run_rule(&LegacySyntax, sql);
+        assert_eq!(
+            diagnostics[0].message(),
+            "JSON_EXTRACT_SCALAR is superseded by JSON_VALUE"
+        );
+        assert_eq!(diagnostics[0].col(), 8);
+        assert_eq!(diagnostics[0].end(), Some((1, 27)));
+        assert_eq!(diagnostics[0].help(), Some("use JSON_VALUE instead"));
+    }
+
+    #[test]
+    fn finds_bracketed_table_names_in_unparsed_source() {
+        let sql = "SELECT x\nFROM [my-project:ds.events_20240101]";
+        let diagnostics = check_unparsed(sql);
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.severity(), Severity::Error);
+        assert_eq!(
+            diagnostic.message(),
+            "legacy SQL table name `[my-project:ds.events_20240101]`"
+        );
+        assert_eq!((diagnostic.row(), diagnostic.col()), (2, 6));
+        assert_eq!(
+            diagnostic.help(),
+            Some("write it as `my-project.ds.events_20240101`")
+        );
+    }
+
+    #[rstest]
+    #[case("SELECT * FROM [ds.t]", &["legacy SQL table name `[ds.t]`"])]
+    #[case(
+        "SELECT x FROM TABLE_DATE_RANGE([p:ds.events_], TIMESTAMP('2024-01-01'), CURRENT_TIMESTAMP())",
+        &[
+            "TABLE_DATE_RANGE is legacy SQL and does not exist in standard SQL",
+            "legacy SQL table name `[p:ds.events_]`",
+        ]
+    )]
+    #[case("SELECT NEST(x) FROM FLATTEN([p:ds.t], y)", &[
+        "NEST is legacy SQL and does not exist in standard SQL",
+        "FLATTEN is legacy SQL and does not exist in standard SQL",
+        "legacy SQL table name `[p:ds.t]`",
+    ])]
+    #[case("SELECT arr[OFFSET(0)], [a.b], 'FROM [p:d.t]' -- NEST(x)\nFROM t", &[])]
+    #[case("SELECT t.nest(x), json_extract (y, '$') FROM t", &["JSON_EXTRACT is superseded by JSON_QUERY"])]
+    #[case("SELECT 'it\\'s FROM [p:d.t]', NEST(x) FROM t", &[
+        "NEST is legacy SQL and does not exist in standard SQL",
+    ])]
+    #[case(r#"SELECT r'\d', "a\"b", FLATTEN(t, x)"#, &[
+        "FLATTEN is legacy SQL and does not exist in standard SQL",
+    ])]
+    #[case(r#"SELECT '''it's '' FROM [p:d.t]''', """NEST(x)""" FROM [p:d.u]"#, &[
+        "legacy SQL table name `[p:d.u]`",
+    ])]
+    #[case("SELECT '', NEST(x) FROM t", &[
+        "NEST is legacy SQL and does not exist in standard SQL",
+    ])]
+    fn scans_unparsed_source(#[case] sql: &str, #[case] messages: &[&str]) {
+        let found: Vec<String> = check_unparsed(sql)
+            .iter()
+            .map(|d| d.message().to_string())
+            .collect();
+        assert_eq!(found, messages, "{sql}");
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashSet;
+
+use crate::ast::{Ast, NodeRef};
+
+use crate::diagnostic::{Diagnostic, Related, Severity};
+use crate::rules::helpers::{
+    conjuncts, find_child_of_kind, find_parent_select, get_node_text, is_array_item,
+    is_table_expression, qualifiers, relates, relation_name, table_path,
+};
+use crate::rules::rule::{Category, LintContext, Rule, RuleMetadata};
+
+const RULE_ID: &str = "missing_join_condition";
+
+const METADATA: RuleMetadata<'static> = RuleMetadata {
+    name: "Cartesian product or missing join condition",
+    default_severity: Severity::Warning,
+    default_enabled: true,
+    category: Category::Performance,
+    summary: "Two tables are joined without a condition relating them, which produces a cartesian product.",
+    description: "A comma join or `CROSS JOIN` between two tables with no predicate in `WHERE` that relates them pairs every row of one with every row of the other, as does a `JOIN ... ON` whose condition does not reference both sides (`ON TRUE`, `ON a.id = a.id`). The result grows with the product of the table sizes and is rarely what was meant. `CROSS JOIN UNNEST(...)`, comma joins with a correlated array path and cross joins with a CTE or subquery (typically a single row of parameters) are not flagged.",
+    bad_example: Some("SELECT * FROM orders, customers WHERE orders.amount > 100"),
+    good_example: Some(
+        "SELECT * FROM orders, customers WHERE orders.customer_id = customers.id AND orders.amount > 100",
+    ),
+    doc_url: Some(
+        "https://github.com/hirosassa/bqvalid/blob/main/docs/rules.md#cartesian-product-or-missing-join-condition",
+    ),
+    fixable: false,
+};
+
+/// Flags joins that pair every row of one table with every row of another.
+pub struct MissingJoinCondition;
+
+impl Rule for MissingJoinCondition {
+    fn id(&self) -> &str {
+        RULE_ID
+    }
+
+    fn metadata(&self) -> RuleMetadata<'_> {
+        METADATA
+    }
+
+    fn check_tree(&self, ast: &Ast, cx: &LintContext<'_>, diagnostics: &mut Vec<Diagnostic>) {
+        let sql = cx.sql;
+        let nodes = ast.pre_order();
+        let ctes: HashSet<String> = nodes
+            .iter()
+            .filter(|node| node.kind() == "ASTAliasedQuery")
+            .filter_map(|cte| cte.child(0))
+            .map(|name| get_node_text(&name, sql).to_lowercase())
+            .collect();
+        for join in nodes.iter().filter(|node| node.kind() == "ASTJoin") {
+            if let Some(diagnostic) = check_join(join, sql, &ctes) {
+                diagnostics.push(diagnostic);
+            }
+        }
+    }
+}
+
+/// The diagnostic for an `ASTJoin` whose sides are not related, if any.
+fn check_join(join: &NodeRef<'_>, src: &str, ctes: &HashSet<String>) -> Option<Diagnostic> {
+    // googlesql: `ASTJoin -> lhs, rhs, [ASTOnClause | ASTUsingClause]`, where a
+    // side is a table item or, for chains, another join.
+    let mut sides = join
+        .named_children()
+        .into_iter()
+        .filter(|child| is_table_expression(child.kind()));
+    let (lhs, rhs) = (sides.next()?, sides.next()?);
+    if find_child_of_kind(join, "ASTUsingClause").is_some() {
+        return None;
+    }
+    let left = qualifiers(&lhs, src);
+    let right = qualifiers(&rhs, src);
+    if is_array_item(&rhs, &left, src) {
+        return None;
+    }
+    let name = relation_name(&rhs, src);
+
+    if let Some(on) = find_child_of_kind(join, "ASTOnClause") {
+        let condition = on.named_child(0)?;
+        if relates(&condition, &left, &right, src) {
+            return None;
+        }
+        return Some(
+            Diagnostic::for_node(
+                RULE_ID,
+                Severity::Warning,
+                &on,
+                format!(
+                    "join condition does not relate `{}` to the other tables",
+                    name
+                ),
+            )
+            .with_related(Related::for_node(&rhs, "joined here"))
+            .with_help("compare a column of each side, e.g. `ON a.id = b.a_id`"),
+        );
+    }
+
+    // A comma join or CROSS JOIN. Only pairs of physical tables are flagged:
+    // crossing with a CTE or subquery usually attaches a row of parameters.
+    if !is_physical_table(&rhs, src, ctes)
+        || !has_physical_table(&lhs, src, ctes)
+        || where_relates(join, &left, &right, src)
+    {
+        return None;
+    }
+    Some(
+        Diagnostic::for_node(
+            RULE_ID,
+            Severity::Warning,
+            &rhs,
+            format!(
+                "`{}` is joined without a condition, producing a cartesian product",
+                name
+            ),
+        )
+        .with_help("add a predicate relating the tables to WHERE, or use JOIN ... ON"),
+    )
+}
+
+/// True when `item` reads a table rather than a CTE, subquery or array.
+fn is_physical_table(item: &NodeRef<'_>, src: &str, ctes: &HashSet<String>) -> bool {
+    table_path(item).is_some_and(|path| {
+        !ctes.contains(&get_node_text(&path, src).trim_matches('`').to_lowercase())
+    })
+}
+
+/// True when some relation in `item` is a physical table.
+fn has_physical_table(item: &NodeRef<'_>, src: &str, ctes: &HashSet<String>) -> bool {
+    match item.kind() {
+        "ASTJoin" | "ASTParenthesizedJoin" => item
+            .named_children()
+            .iter()
+            .any(|child| has_physical_table(child, src, ctes)),
+        _ => is_physical_table(item, src, ctes),
+    }
+}
+
+/// True when a top-level conjunct of the `WHERE` clause owning `join` relates
+/// its two sides.
+fn where_relates(
+    join: &NodeRef<'_>,
+    left: &HashSet<String>,
+    right: &HashSet<String>,
+    src: &str,
+) -> bool {
+    let Some(condition) = find_parent_select(join)
+        .and_then(|select| find_child_of_kind(&select, "ASTWhereClause"))
+        .and_then(|clause| clause.named_child(0))
+    else {
+        return false;
+    };
+    conjuncts(condition)
+        .iter()
+        .any(|conjunct| relates(conjunct, left, right, src))
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::indexing_slicing,
+    clippy::arithmetic_side_effects,
+    reason = "test code"
+)]
+mod tests {
+    use super::*;
+    use crate::rules::helpers::run_rule;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("SELECT * FROM a, b")]
+    #[case("SELECT * FROM a, b WHERE a.x > 0 AND b.y > 0")]
+    #[case("SELECT * FROM dataset.a CROSS JOIN dataset.b")]
+    #[case("SELECT * FROM a AS x CROSS JOIN b AS y WHERE x.id = 1")]
+    #[case("SELECT * FROM a JOIN b ON TRUE")]
+    #[case("SELECT * FROM a JOIN b ON a.id = a.id")]
+    #[case("SELECT * FROM a LEFT JOIN b ON a.flag AND a.x > 0")]
+    #[case("SELECT * FROM a JOIN b ON a.id = b.id, c")]
+    fn flags_unrelated_joins(#[case] sql: &str) {
+        assert_eq!(run_rule(&MissingJoinCondition, sql).len(), 1, "{sql}");
+    }
+
+    #[rstest]
+    #[case("SELECT * FROM a, b WHERE a.id = b.a_id")]
+    #[case("SELECT * FROM a x, b y WHERE x.z > 0 AND x.id = y.a_id")]
+    #[case("SELECT * FROM a, b WHERE id = a_id")]
+    #[case("SELECT * FROM a JOIN b ON a.id = b.a_id")]
+    #[case("SELECT * FROM a JOIN b USING (id)")]
+    #[case("SELECT * FROM a JOIN b ON id = a_id")]
+    #[case("SELECT * FROM a CROSS JOIN UNNEST(a.items) AS item")]
+    #[case("SELECT * FROM a, UNNEST(a.items) AS item")]
+    #[case("SELECT * FROM a, a.items AS item")]
+    #[case("SELECT * FROM a LEFT JOIN UNNEST(a.items) AS item ON TRUE")]
+    #[case("WITH p AS (SELECT 1 AS n) SELECT * FROM a CROSS JOIN p")]
+    #[case("SELECT * FROM a CROSS JOIN (SELECT MAX(d) AS d FROM b)")]
+    fn accepts_related_or_flattening_joins(#[case] sql: &str) {
+        assert!(run_rule(&MissingJoinCondition, sql).is_empty(), "{sql}");
+    }
+
+    #[test]
+    fn on_diagnostic_points_at_the_condition_and_the_joined_table() {
+        let sql = "SELECT * FROM a JOIN b ON a.id = a.id";
+        let diagnostics = run_rule(&MissingJoinCondition, sql);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].col(), sql.find("ON").unwrap() + 1);
+        assert_eq!(
+            diagnostics[0].message(),
+            "join condition does not relate `b` to the other tables"
+        );
+        assert_eq!(
+            diagnostics[0].related()[0].start,
+            (1, sql.find("b ON").unwrap() + 1)
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::HashSet;
+
+use crate::ast::NodeRef;
+
+use crate::diagnostic::{Diagnostic, Related, Severity};
+use crate::rules::helpers::{
+    binary_operator, conjuncts, get_node_text, is_function_name, is_table_expression, qualifiers,
+    relates, relation_name,
+};
+use crate::rules::rule::{Category, LintContext, Rule, RuleMetadata};
+
+const RULE_ID: &str = "non_equi_join";
+
+const METADATA: RuleMetadata<'static> = RuleMetadata {
+    name: "Join without an equality condition",
+    default_severity: Severity::Warning,
+    default_enabled: true,
+    category: Category::Performance,
+    summary: "A join condition has no equality between the two sides, so BigQuery compares every pair of rows.",
+    description: "BigQuery joins large tables by hashing both sides on the columns an `ON` condition compares with `=`. A condition relating the sides only through `REGEXP_CONTAINS`, `LIKE`, `STARTS_WITH`, a geography function or a range comparison gives it no key, and the join runs as a cross join filtered row pair by row pair. Join keys wrapped in functions on both sides (`ON LOWER(a.email) = LOWER(b.email)`) still hash, but are recomputed for every row and usually mean one of the tables stores the key unnormalised; they are reported separately.",
+    bad_example: Some(
+        "SELECT * FROM logs AS l JOIN patterns AS p ON REGEXP_CONTAINS(l.url, p.regex)",
+    ),
+    good_example: Some(
+        "SELECT * FROM logs AS l JOIN patterns AS p ON l.host = p.host AND REGEXP_CONTAINS(l.url, p.regex)",
+    ),
+    doc_url: Some(
+        "https://github.com/hirosassa/bqvalid/blob/main/docs/rules.md#join-without-an-equality-condition",
+    ),
+    fixable: false,
+};
+
+/// Functions whose use in a join condition typically replaces an equality.
+const PATTERN_FUNCTIONS: [&str; 3] = ["REGEXP_CONTAINS", "STARTS_WITH", "ENDS_WITH"];
+
+/// Flags joins BigQuery cannot run as hash joins, and join keys computed by
+/// functions.
+pub struct NonEquiJoin;
+
+impl Rule for NonEquiJoin {
+    fn id(&self) -> &str {
+        RULE_ID
+    }
+
+    fn metadata(&self) -> RuleMetadata<'_> {
+        METADATA
+    }
+
+    fn check_node(
+        &self,
+        node: NodeRef<'_>,
+        cx: &LintContext<'_>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        if node.kind() == "ASTOnClause" {
+            diagnostics.extend(check_on_clause(&node, cx.sql));
+        }
+    }
+}
+
+/// Which side of a join an operand of an equality reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+    /// Only unqualified columns, which cannot be attributed without a schema.
+    Unknown,
+}
+
+/// The diagnostics for the `ON` condition of a join.
+fn check_on_clause(on: &NodeRef<'_>, src: &str) -> Vec<Diagnostic> {
+    let Some(join) = on.parent().filter(|parent| parent.kind() == "ASTJoin") else {
+        return Vec::new();
+    };
+    let mut sides = join
+        .named_children()
+        .into_iter()
+        .filter(|child| is_table_expression(child.kind()));
+    let (Some(lhs), Some(rhs), Some(condition)) = (sides.next(), sides.next(), on.named_child(0))
+    else {
+        return Vec::new();
+    };
+    let (left, right) = (qualifiers(&lhs, src), qualifiers(&rhs, src));
+    // A condition not relating the sides at all is `missing_join_condition`'s.
+    if !relates(&condition, &left, &right, src) {
+        return Vec::new();
+    }
+
+    let keys: Vec<(NodeRef<'_>, NodeRef<'_>, NodeRef<'_>)> = conjuncts(condition)
+        .into_iter()
+        .filter(|conjunct| binary_operator(conjunct, src).as_deref() == Some("="))
+        .filter_map(|conjunct| {
+            let (a, b) = (conjunct.child(0)?, conjunct.children().into_iter().last()?);
+            let sides = (side(&a, &left, &right, src)?, side(&b, &left, &right, src)?);
+            (sides.0 != sides.1 || sides.0 == Side::Unknown).then_some((conjunct, a, b))
+        })
+        .collect();
+
+    if keys.is_empty() {
+        let diagnostic = Diagnostic::for_node(
+            RULE_ID,
+            Severity::Warning,
+            on,
+            format!(
+                "join condition has no equality between `{}` and the other tables",
+                relation_name(&rhs, src)
+            ),
+        )
+        .with_note("without one BigQuery cannot hash the join and compares every pair of rows")
+        .with_help("add an equality on a shared key, e.g. `ON a.key = b.key AND ...`");
+        return vec![pattern_predicates(&condition, src).iter().fold(
+            diagnostic,
+            |diagnostic, predicate| {
+                diagnostic.with_related(Related::for_node(
+                    predicate,
+                    "this predicate cannot be used as a join key",
+                ))
+            },
+        )];
+    }
+
+    keys.iter()
+        .filter(|(_, a, b)| is_computed(a) && is_computed(b))
+        .map(|(conjunct, _, _)| {
+            Diagnostic::for_node(
+                RULE_ID,
+                Severity::Warning,
+                conjunct,
+                format!(
+                    "join keys are computed by functions: `{}`",
+                    get_node_text(conjunct, src).trim()
+                ),
+            )
+            .with_note("the keys are recomputed for every row of both tables")
+            .with_help("store the normalised key in a column of each table and join on those")
+        })
+        .collect()
+}
+
+/// The side of the join whose columns `operand` reads, or `None` when it reads
+/// no column or columns of both sides.
+fn side(
+    operand: &NodeRef<'_>,
+    left: &HashSet<String>,
+    right: &HashSet<String>,
+    src: &str,
+) -> Option<Side> {
+    let mut found = None;
+    for node in operand.pre_order() {
+        if node.kind() != "ASTPathExpression" || is_function_name(&node) {
+            continue;
+        }
+        let side = match get_node_text(&node, src).split_once('.') {
+            Some((qualifier, _)) => {
+                let qualifier = qualifier.trim_matches('`').to_lowercase();
+                if left.contains(&qualifier) {
+                    Side::Left
+                } else if right.contains(&qualifier) {
+                    Side::Right
+                } else {
+                    Side::Unknown
+                }
+            }
+            None => Side::Unknown,
+        };
+        found = match found {
+            None => Some(side),
+            Some(Side::Unknown) => Some(side),
+            Some(seen) if side == Side::Unknown || side == seen => Some(seen),
+            Some(_) => return None,
+        };
+    }
+    found
+}
+
+/// True when a join key is a function of its columns rather than a column.
+fn is_computed(operand: &NodeRef<'_>) -> bool {
+    matches!(operand.kind(), "ASTFunctionCall" | "ASTCastExpression")
+}
+
+/// The predicates in `condition` that typically stand in for an equality:
+/// pattern matches and geography functions.
+fn pattern_predicates<'a>(condition: &NodeRef<'a>, src: &str) -> Vec<NodeRef<'a>> {
+    condition
+        .pre_order()
+        .into_iter()
+        .filter(|node| match node.kind() {
+            "ASTFunctionCall" => node.child(0).is_some_and(|name| {
+                let name = get_node_text(&name, src).trim().to_ascii_uppercase();
+                let name = name.strip_prefix("SAFE.").unwrap_or(&name);
+                PATTERN_FUNCTIONS.contains(&name) || name.starts_with("ST_")
+            }),
+            "ASTLikeExpression" => true,
+            "ASTBinaryExpression" => {
+                binary_operator(node, src).is_some_and(|operator| operator.ends_with("LIKE"))
+            }
+            _ => false,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::indexing_slicing,
+    clippy::arithmetic_side_effects,
+    reason = "test code"
+)]
+mod tests {
+    use super::*;
+    use crate::rules::helpers::run_rule;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("SELECT * FROM a JOIN b ON REGEXP_CONTAINS(a.url, b.pattern)")]
+    #[case("SELECT * FROM a JOIN b ON a.name LIKE CONCAT(b.prefix, '%')")]
+    #[case("SELECT * FROM a JOIN b ON STARTS_WITH(a.path, b.prefix)")]
+    #[case("SELECT * FROM a JOIN b ON ST_CONTAINS(b.area, a.point)")]
+    #[case("SELECT * FROM a JOIN b ON a.ts BETWEEN b.start_ts AND b.end_ts")]
+    #[case("SELECT * FROM a JOIN b ON a.id = b.id OR a.alt_id = b.id")]
+    #[case("SELECT * FROM a AS x JOIN b AS y ON x.id = x.id + y.offset")]
+    fn flags_joins_without_an_equality(#[case] sql: &str) {
+        let diagnostics = run_rule(&NonEquiJoin, sql);
+        assert_eq!(diagnostics.len(), 1, "{sql}");
+        assert!(
+            diagnostics[0]
+                .message()
+                .starts_with("join condition has no equality"),
+            "{sql}"
+        );
+    }
+
+    #[rstest]
+    #[case("SELECT * FROM a JOIN b ON LOWER(a.email) = LOWER(b.email)")]
+    #[case("SELECT * FROM a JOIN b ON CAST(a.id AS STRING) = TRIM(b.id)")]
+    #[case("SELECT * FROM a JOIN b ON a.d = b.d AND UPPER(a.code) = UPPER(b.code)")]
+    fn flags_function_wrapped_keys(#[case] sql: &str) {
+        let diagnostics = run_rule(&NonEquiJoin, sql);
+        assert_eq!(diagnostics.len(), 1, "{sql}");
+        assert!(
+            diagnostics[0]
+                .message()
+                .starts_with("join keys are computed"),
+            "{sql}"
+        );
+    }
+
+    #[rstest]
+    #[case("SELECT * FROM a JOIN b ON a.id = b.a_id")]
+    #[case("SELECT * FROM a JOIN b ON a.host = b.host AND REGEXP_CONTAINS(a.url, b.pattern)")]
+    #[case("SELECT * FROM a JOIN b ON LOWER(a.email) = b.email_lower")]
+    #[case("SELECT * FROM a JOIN b ON id = a_id")]
+    #[case("SELECT * FROM a JOIN b USING (id)")]
+    #[case("SELECT * FROM a JOIN b ON TRUE")]
+    #[case("SELECT * FROM a JOIN b ON a.id = a.id")]
+    #[case("SELECT * FROM a, b WHERE REGEXP_CONTAINS(a.url, b.pattern)")]
+    fn accepts_hashable_or_unrelated_joins(#[case] sql: &str) {
+        assert!(run_rule(&NonEquiJoin, sql).is_empty(), "{sql}");
+    }
+
+    #[test]
+    fn points_at_the_predicates_that_replace_the_key() {
+        let sql = "SELECT * FROM a JOIN b ON REGEXP_CONTAINS(a.url, b.pattern)";
+        let diagnostics = run_rule(&NonEquiJoin, sql);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.col(), sql.find("ON").unwrap() + 1);
+        assert_eq!(diagnostic.related().len(), 1);
+        assert_eq!(
+            diagnostic.related()[0].start,
+            (1, sql.find("REGEXP").unwrap() + 1)
+        );
+    }
+}
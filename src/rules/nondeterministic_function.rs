@@ -0,0 +1,399 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast::NodeRef;
+
+use crate::diagnostic::{Diagnostic, FunctionLevel, Severity};
+use crate::rules::helpers::get_node_text;
+use crate::rules::rule::{Category, LintContext, Rule, RuleMetadata};
+
+const RULE_ID: &str = "nondeterministic_function";
+
+const METADATA: RuleMetadata<'static> = RuleMetadata {
+    name: "Using a non-deterministic function",
+    default_severity: Severity::Warning,
+    default_enabled: true,
+    category: Category::Maintainability,
+    summary: "A function like `CURRENT_DATE` or `RAND` makes the query's result depend on when or how often it runs.",
+    description: "A query that reads the clock (`CURRENT_DATE`, `CURRENT_TIMESTAMP`, `CURRENT_DATETIME`, `CURRENT_TIME`), draws random values (`RAND`, `GENERATE_UUID`) or asks who runs it (`SESSION_USER`) returns different results on every run. It cannot be re-run for a past date or reproduced in tests, and BigQuery does not cache its results. Pass such values in as query parameters instead. The `NET.*` functions only depend on their arguments, so they are not flagged unless the `functions` option enables them with `\"NET.*\"`. Values written by `INSERT`, `UPDATE` and `MERGE` and column defaults, such as audit timestamps, are not flagged.",
+    bad_example: Some("SELECT * FROM dataset.table WHERE dt = CURRENT_DATE()"),
+    good_example: Some("SELECT * FROM dataset.table WHERE dt = @run_date"),
+    doc_url: Some(
+        "https://github.com/hirosassa/bqvalid/blob/main/docs/rules.md#using-a-non-deterministic-function",
+    ),
+    fixable: false,
+};
+
+/// Functions flagged unless configured otherwise. The `NET.*` family is
+/// deterministic, so projects opt into it through the `functions` option.
+const DEFAULT_FUNCTIONS: &[&str] = &[
+    "CURRENT_DATE",
+    "CURRENT_DATETIME",
+    "CURRENT_TIME",
+    "CURRENT_TIMESTAMP",
+    "GENERATE_UUID",
+    "RAND",
+    "SESSION_USER",
+];
+
+/// `[rules.nondeterministic_function]` options. The table is also read under
+/// the rule's former name, `[rules.use_current_date]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NondeterministicFunctionOptions {
+    /// Per-function levels, overriding the default warning for the built-in
+    /// functions and adding further ones. A key ending in `.*` covers a whole
+    /// family, e.g. `"NET.*"`.
+    #[serde(default)]
+    pub functions: BTreeMap<String, FunctionLevel>,
+    /// Further functions to report as warnings; shorthand for `functions`
+    /// entries set to `warning`.
+    #[serde(default)]
+    pub also_flag: Vec<String>,
+    /// Whether values written by `INSERT`, `UPDATE` and `MERGE` and column
+    /// defaults, typically audit timestamps, are allowed.
+    #[serde(default = "default_allow_in_written_values")]
+    pub allow_in_written_values: bool,
+}
+
+impl Default for NondeterministicFunctionOptions {
+    fn default() -> Self {
+        Self {
+            functions: BTreeMap::new(),
+            also_flag: Vec::new(),
+            allow_in_written_values: default_allow_in_written_values(),
+        }
+    }
+}
+
+const fn default_allow_in_written_values() -> bool {
+    true
+}
+
+/// Where a call appears, as far as the rule cares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Context {
+    /// A `WHERE`, `HAVING`, `QUALIFY` or `ON` condition.
+    Filter,
+    /// A value written by DML or a column default.
+    Written,
+    Other,
+}
+
+/// Flags functions whose result changes from run to run, which hurts query
+/// reproducibility and disables result caching.
+#[derive(Default)]
+pub struct NondeterministicFunction {
+    options: NondeterministicFunctionOptions,
+}
+
+impl NondeterministicFunction {
+    #[must_use]
+    pub const fn new(options: NondeterministicFunctionOptions) -> Self {
+        Self { options }
+    }
+
+    /// The level `name` is reported at. Exact `functions` entries win over
+    /// `.*` families, which win over `also_flag` and the built-in list.
+    fn level(&self, name: &str) -> FunctionLevel {
+        let configured = |exact: bool| {
+            self.options.functions.iter().find_map(|(pattern, level)| {
+                let matched = match pattern.strip_suffix(".*") {
+                    Some(prefix) if !exact => family_member(name, prefix),
+                    None if exact => pattern.eq_ignore_ascii_case(name),
+                    _ => false,
+                };
+                matched.then_some(*level)
+            })
+        };
+        configured(true)
+            .or_else(|| configured(false))
+            .unwrap_or_else(|| {
+                let flagged = DEFAULT_FUNCTIONS
+                    .iter()
+                    .any(|f| f.eq_ignore_ascii_case(name))
+                    || self
+                        .options
+                        .also_flag
+                        .iter()
+                        .any(|f| match f.strip_suffix(".*") {
+                            Some(prefix) => family_member(name, prefix),
+                            None => f.eq_ignore_ascii_case(name),
+                        });
+                if flagged {
+                    FunctionLevel::Warning
+                } else {
+                    FunctionLevel::Off
+                }
+            })
+    }
+
+    /// Flags `node` when it names a call to a function reported at some level.
+    fn nondeterministic_call(&self, node: NodeRef<'_>, src: &str) -> Option<Diagnostic> {
+        // The name of a call is the `ASTPathExpression` heading its
+        // `ASTFunctionCall`. The date/time functions may also be written
+        // without parentheses; accept them as a bare path too, in case the
+        // parser does not turn them into a call.
+        if node.kind() != "ASTPathExpression" {
+            return None;
+        }
+        let name: String = get_node_text(&node, src).split_whitespace().collect();
+        let is_call = node.parent().is_some_and(|parent| {
+            parent.kind() == "ASTFunctionCall"
+                && parent.child(0).is_some_and(|first| first.id() == node.id())
+        });
+        if !is_call && !is_date_time_keyword(&name) {
+            return None;
+        }
+        let severity = self.level(&name).severity()?;
+        let context = context_of(&node);
+        if context == Context::Written && self.options.allow_in_written_values {
+            return None;
+        }
+
+        let diagnostic = Diagnostic::for_node(
+            RULE_ID,
+            severity,
+            &node,
+            format!("{} is used!", name.to_uppercase()),
+        )
+        .with_help("pass the value in as a query parameter instead");
+        Some(if context == Context::Filter {
+            diagnostic
+                .with_note("in a filter, which rows are selected depends on when the query runs")
+        } else {
+            diagnostic
+        })
+    }
+}
+
+impl Rule for NondeterministicFunction {
+    fn id(&self) -> &str {
+        RULE_ID
+    }
+
+    fn metadata(&self) -> RuleMetadata<'_> {
+        METADATA
+    }
+
+    fn check_node(
+        &self,
+        node: NodeRef<'_>,
+        cx: &LintContext<'_>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        if let Some(diagnostic) = self.nondeterministic_call(node, cx.sql) {
+            diagnostics.push(diagnostic);
+        }
+    }
+}
+
+/// True for the functions BigQuery also accepts without parentheses.
+fn is_date_time_keyword(name: &str) -> bool {
+    [
+        "CURRENT_DATE",
+        "CURRENT_DATETIME",
+        "CURRENT_TIME",
+        "CURRENT_TIMESTAMP",
+    ]
+    .iter()
+    .any(|keyword| keyword.eq_ignore_ascii_case(name))
+}
+
+/// True when `name` is `prefix.<something>`, ignoring ASCII case.
+fn family_member(name: &str, prefix: &str) -> bool {
+    name.split_once('.')
+        .is_some_and(|(head, rest)| head.eq_ignore_ascii_case(prefix) && !rest.is_empty())
+}
+
+/// The nearest enclosing context of the call named by `name` that decides
+/// whether it is reported.
+fn context_of(name: &NodeRef<'_>) -> Context {
+    let mut current = name.parent();
+    while let Some(node) = current {
+        match node.kind() {
+            "ASTWhereClause" | "ASTHaving" | "ASTQualify" | "ASTOnClause" => {
+                return Context::Filter;
+            }
+            // INSERT ... VALUES rows, UPDATE and MERGE `SET` items, and
+            // column defaults in CREATE TABLE.
+            "ASTInsertValuesRow" | "ASTUpdateItem" | "ASTColumnDefinition" => {
+                return Context::Written;
+            }
+            // The select list of INSERT ... SELECT.
+            "ASTSelectList" if feeds_insert(&node) => return Context::Written,
+            _ => {}
+        }
+        current = node.parent();
+    }
+    Context::Other
+}
+
+/// True when `select_list` belongs to the query of an `INSERT` statement.
+fn feeds_insert(select_list: &NodeRef<'_>) -> bool {
+    select_list
+        .parent()
+        .and_then(|select| select.parent())
+        .filter(|query| query.kind() == "ASTQuery")
+        .and_then(|query| query.parent())
+        .is_some_and(|statement| statement.kind() == "ASTInsertStatement")
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::indexing_slicing,
+    clippy::arithmetic_side_effects,
+    reason = "test code"
+)]
+mod tests {
+    use super::*;
+    use crate::rules::helpers::run_rule;
+    use rstest::rstest;
+
+    #[test]
+    fn current_date_is_used() {
+        let sql = "\
+select
+  current_date,
+  column_a
+from
+  dataset.table
+";
+        assert!(!run_rule(&NondeterministicFunction::default(), sql).is_empty());
+    }
+
+    #[test]
+    fn current_date_is_not_used() {
+        let sql = "\
+select
+  *
+from
+  dataset.table
+";
+        assert!(run_rule(&NondeterministicFunction::default(), sql).is_empty());
+    }
+
+    #[test]
+    fn check_flags_every_occurrence() {
+        // Two calls on one line -> two diagnostics, each pointing at its own column.
+        let sql = "SELECT CURRENT_DATE(), CURRENT_DATE() FROM t";
+
+        let diagnostics = run_rule(&NondeterministicFunction::default(), sql);
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics.iter().all(|d| d.row() == 1));
+
+        let expected_cols: Vec<usize> = sql
+            .match_indices("CURRENT_DATE")
+            .map(|(i, _)| i + 1)
+            .collect();
+        assert_eq!(expected_cols.len(), 2);
+        let cols: Vec<usize> = diagnostics.iter().map(Diagnostic::col).collect();
+        for expected in expected_cols {
+            assert!(
+                cols.contains(&expected),
+                "missing diagnostic at col {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn check_is_case_insensitive() {
+        // Lowercase spelling must be flagged just like the canonical uppercase.
+        let sql = "SELECT current_date() FROM t";
+        assert_eq!(run_rule(&NondeterministicFunction::default(), sql).len(), 1);
+    }
+
+    #[rstest]
+    #[case("SELECT CURRENT_TIMESTAMP() FROM t", "CURRENT_TIMESTAMP is used!")]
+    #[case("SELECT CURRENT_DATETIME() FROM t", "CURRENT_DATETIME is used!")]
+    #[case("SELECT CURRENT_TIME() FROM t", "CURRENT_TIME is used!")]
+    #[case("SELECT * FROM t WHERE RAND() < 0.1", "RAND is used!")]
+    #[case("SELECT GENERATE_UUID() AS id FROM t", "GENERATE_UUID is used!")]
+    #[case(
+        "SELECT * FROM t WHERE owner = SESSION_USER()",
+        "SESSION_USER is used!"
+    )]
+    fn flags_the_built_in_functions(#[case] sql: &str, #[case] message: &str) {
+        let diagnostics = run_rule(&NondeterministicFunction::default(), sql);
+        assert_eq!(diagnostics.len(), 1, "{sql}");
+        assert_eq!(diagnostics[0].message(), message);
+    }
+
+    #[test]
+    fn a_column_named_like_a_function_is_not_flagged() {
+        let sql = "SELECT rand, current_time_zone FROM t";
+        assert!(run_rule(&NondeterministicFunction::default(), sql).is_empty());
+    }
+
+    #[rstest]
+    #[case("INSERT INTO t (id, loaded_at) VALUES (1, CURRENT_TIMESTAMP())")]
+    #[case("INSERT INTO t SELECT id, CURRENT_TIMESTAMP() AS loaded_at FROM s")]
+    #[case("UPDATE t SET updated_at = CURRENT_TIMESTAMP() WHERE id = 1")]
+    #[case("CREATE TABLE t (id INT64, loaded_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP())")]
+    fn allows_written_values(#[case] sql: &str) {
+        assert!(
+            run_rule(&NondeterministicFunction::default(), sql).is_empty(),
+            "{sql}"
+        );
+
+        let strict = NondeterministicFunction::new(NondeterministicFunctionOptions {
+            allow_in_written_values: false,
+            ..Default::default()
+        });
+        assert_eq!(run_rule(&strict, sql).len(), 1, "{sql}");
+    }
+
+    #[test]
+    fn filters_are_flagged_even_in_dml() {
+        let sql = "INSERT INTO t SELECT id FROM s WHERE dt = CURRENT_DATE()";
+        let diagnostics = run_rule(&NondeterministicFunction::default(), sql);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].notes().len(), 1);
+    }
+
+    #[test]
+    fn functions_configure_each_function() {
+        let rule = NondeterministicFunction::new(NondeterministicFunctionOptions {
+            functions: [
+                ("rand".to_string(), FunctionLevel::Off),
+                ("CURRENT_DATE".to_string(), FunctionLevel::Error),
+                ("NET.*".to_string(), FunctionLevel::Warning),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        });
+        let sql = "SELECT RAND(), CURRENT_DATE(), NET.HOST(url), CURRENT_TIME() FROM t";
+        let diagnostics = run_rule(&rule, sql);
+        let found: Vec<(&str, Severity)> = diagnostics
+            .iter()
+            .map(|d| (d.message(), d.severity()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("CURRENT_DATE is used!", Severity::Error),
+                ("NET.HOST is used!", Severity::Warning),
+                ("CURRENT_TIME is used!", Severity::Warning),
+            ]
+        );
+    }
+
+    #[test]
+    fn also_flag_reports_the_configured_functions() {
+        let rule = NondeterministicFunction::new(NondeterministicFunctionOptions {
+            also_flag: vec!["MY_NOW".to_string()],
+            ..Default::default()
+        });
+        let sql = "SELECT my_now(), other_fn() FROM t";
+        let diagnostics = run_rule(&rule, sql);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message(), "MY_NOW is used!");
+
+        assert!(run_rule(&NondeterministicFunction::default(), sql).is_empty());
+    }
+}
@@ -0,0 +1,269 @@
+use crate::ast::NodeRef;
+
+use crate::diagnostic::{Diagnostic, Related, Severity};
+use crate::rules::helpers::{conjuncts, find_child_of_kind, get_node_text, query_select};
+use crate::rules::rule::{Category, LintContext, Rule, RuleMetadata};
+use crate::schema::{ColumnMode, SchemaCatalog};
+
+const RULE_ID: &str = "not_in_nullable_subquery";
+
+const METADATA: RuleMetadata<'static> = RuleMetadata {
+    name: "`NOT IN` with a nullable subquery",
+    default_severity: Severity::Warning,
+    default_enabled: true,
+    category: Category::Correctness,
+    summary: "`NOT IN` compares with a subquery whose column may be NULL, so a single NULL empties the result.",
+    description: "`x NOT IN (SELECT y FROM t)` is false or NULL for every `x` as soon as one `y` is NULL, so the filter silently drops every row. The rule flags `NOT IN` subqueries whose selected column is not provably non-null: it is not a literal, `COALESCE`/`IFNULL` or `COUNT`, not a column the `[schema]` configuration declares `REQUIRED`, and the subquery does not filter it with `IS NOT NULL`. Use `NOT EXISTS` with a correlated subquery instead, or exclude the NULLs explicitly.",
+    bad_example: Some("SELECT * FROM users WHERE id NOT IN (SELECT user_id FROM banned_users)"),
+    good_example: Some(
+        "SELECT * FROM users WHERE id NOT IN (SELECT user_id FROM banned_users WHERE user_id IS NOT NULL)",
+    ),
+    doc_url: Some(
+        "https://github.com/hirosassa/bqvalid/blob/main/docs/rules.md#not-in-with-a-nullable-subquery",
+    ),
+    fixable: false,
+};
+
+/// Functions whose result is never NULL, whatever their arguments.
+const NON_NULL_FUNCTIONS: [&str; 4] = ["COALESCE", "IFNULL", "COUNT", "COUNTIF"];
+
+/// Flags `NOT IN (subquery)` where the subquery may yield NULL.
+pub struct NotInNullableSubquery;
+
+impl Rule for NotInNullableSubquery {
+    fn id(&self) -> &str {
+        RULE_ID
+    }
+
+    fn metadata(&self) -> RuleMetadata<'_> {
+        METADATA
+    }
+
+    fn check_node(
+        &self,
+        node: NodeRef<'_>,
+        cx: &LintContext<'_>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        if node.kind() == "ASTInExpression"
+            && let Some(diagnostic) = nullable_not_in(node, cx.sql, cx.schema)
+        {
+            diagnostics.push(diagnostic);
+        }
+    }
+}
+
+/// The diagnostic for an `ASTInExpression` that is a `NOT IN` over a subquery
+/// whose selected column may be NULL.
+fn nullable_not_in(node: NodeRef<'_>, src: &str, schema: &SchemaCatalog) -> Option<Diagnostic> {
+    // googlesql: `ASTInExpression -> lhs, ASTQuery` for `x [NOT] IN (SELECT ...)`;
+    // an IN list or UNNEST takes the query's place otherwise.
+    let lhs = node.child(0)?;
+    let query = find_child_of_kind(&node, "ASTQuery")?;
+    if !is_negated(&lhs, &query, src) {
+        return None;
+    }
+    let select = query_select(query)?;
+    let column = selected_expression(&select)?;
+    if is_non_null(&column, src)
+        || is_required_column(&select, &column, src, schema)
+        || filtered_not_null(&select, &column, src)
+    {
+        return None;
+    }
+
+    Some(
+        Diagnostic::for_node(
+            RULE_ID,
+            Severity::Warning,
+            &node,
+            "NOT IN returns no rows if the subquery yields a NULL".to_string(),
+        )
+        .with_related(Related::for_node(&column, "this column may be NULL"))
+        .with_help(format!(
+            "use NOT EXISTS with a correlated subquery, or add `WHERE {} IS NOT NULL` to the subquery",
+            get_node_text(&column, src)
+        )),
+    )
+}
+
+/// True when the tokens between the left operand and the subquery start with
+/// `NOT`. The tree carries no negation flag, so this reads the source.
+fn is_negated(lhs: &NodeRef<'_>, query: &NodeRef<'_>, src: &str) -> bool {
+    src.get(lhs.end_byte()..query.start_byte())
+        .and_then(|between| {
+            between
+                .trim_start_matches(|c: char| c == ')' || c.is_whitespace())
+                .split_whitespace()
+                .next()
+        })
+        .is_some_and(|token| token.eq_ignore_ascii_case("not"))
+}
+
+/// The expression of the subquery's single select column, or `None` for
+/// `SELECT *` and the like, which cannot be judged without a schema.
+fn selected_expression<'a>(select: &NodeRef<'a>) -> Option<NodeRef<'a>> {
+    let list = find_child_of_kind(select, "ASTSelectList")?;
+    let column = find_child_of_kind(&list, "ASTSelectColumn")?;
+    column
+        .named_children()
+        .into_iter()
+        .next()
+        .filter(|expr| !expr.kind().starts_with("ASTStar"))
+}
+
+/// True when `expr` can never be NULL: a non-NULL literal or a call to one of
+/// [`NON_NULL_FUNCTIONS`].
+fn is_non_null(expr: &NodeRef<'_>, src: &str) -> bool {
+    match expr.kind() {
+        "ASTNullLiteral" => false,
+        "ASTFunctionCall" => expr.child(0).is_some_and(|name| {
+            let name = get_node_text(&name, src);
+            NON_NULL_FUNCTIONS
+                .iter()
+                .any(|f| f.eq_ignore_ascii_case(name))
+        }),
+        kind => kind.ends_with("Literal"),
+    }
+}
+
+/// True when `column` is a column the schema declares `REQUIRED` in the one
+/// table `select` reads.
+fn is_required_column(
+    select: &NodeRef<'_>,
+    column: &NodeRef<'_>,
+    src: &str,
+    schema: &SchemaCatalog,
+) -> bool {
+    if column.kind() != "ASTPathExpression" {
+        return false;
+    }
+    let table = find_child_of_kind(select, "ASTFromClause")
+        .and_then(|from| from.named_child(0))
+        .filter(|source| source.kind() == "ASTTablePathExpression")
+        .and_then(|source| find_child_of_kind(&source, "ASTPathExpression"))
+        .and_then(|path| schema.table(get_node_text(&path, src)));
+    table
+        .and_then(|table| table.column(last_segment(get_node_text(column, src))))
+        .is_some_and(|declared| declared.mode == ColumnMode::Required)
+}
+
+/// True when the select's `WHERE` clause requires `column IS NOT NULL`, either
+/// on its own or as one of the conjuncts of an `AND`.
+fn filtered_not_null(select: &NodeRef<'_>, column: &NodeRef<'_>, src: &str) -> bool {
+    let Some(condition) =
+        find_child_of_kind(select, "ASTWhereClause").and_then(|clause| clause.named_child(0))
+    else {
+        return false;
+    };
+    let column = last_segment(get_node_text(column, src));
+    conjuncts(condition)
+        .iter()
+        .any(|conjunct| is_not_null_check(conjunct, column, src))
+}
+
+/// True when `expr` is `<column> IS NOT NULL`. googlesql parses `IS [NOT]
+/// NULL` as an `ASTBinaryExpression` whose right operand is the NULL literal.
+fn is_not_null_check(expr: &NodeRef<'_>, column: &str, src: &str) -> bool {
+    if expr.kind() != "ASTBinaryExpression" {
+        return false;
+    }
+    let (Some(operand), Some(null)) = (expr.child(0), expr.children().into_iter().last()) else {
+        return false;
+    };
+    if null.kind() != "ASTNullLiteral"
+        || !last_segment(get_node_text(&operand, src)).eq_ignore_ascii_case(column)
+    {
+        return false;
+    }
+    src.get(operand.end_byte()..null.start_byte())
+        .is_some_and(|op| {
+            let mut tokens = op.split_whitespace();
+            tokens.next().is_some_and(|t| t.eq_ignore_ascii_case("is"))
+                && tokens.next().is_some_and(|t| t.eq_ignore_ascii_case("not"))
+        })
+}
+
+/// The column name of a possibly qualified reference, so `t.y` matches `y`.
+fn last_segment(path: &str) -> &str {
+    path.rsplit('.').next().unwrap_or(path).trim()
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::indexing_slicing,
+    clippy::arithmetic_side_effects,
+    reason = "test code"
+)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::rules::helpers::{run_rule, run_rule_with_schema};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("SELECT * FROM t WHERE x NOT IN (SELECT y FROM u)")]
+    #[case("SELECT * FROM t WHERE x not in (select u.y from u where u.z > 0)")]
+    #[case("SELECT * FROM t WHERE a = 1 AND x NOT IN ((SELECT y FROM u))")]
+    #[case("SELECT * FROM t WHERE x NOT IN (SELECT y FROM u WHERE z IS NOT NULL)")]
+    #[case("SELECT * FROM t WHERE x NOT IN (SELECT y FROM u WHERE y IS NULL)")]
+    #[case("SELECT * FROM t WHERE x NOT IN (SELECT NULL FROM u)")]
+    fn flags_not_in_over_nullable_columns(#[case] sql: &str) {
+        assert_eq!(run_rule(&NotInNullableSubquery, sql).len(), 1, "{sql}");
+    }
+
+    #[rstest]
+    #[case("SELECT * FROM t WHERE x IN (SELECT y FROM u)")]
+    #[case("SELECT * FROM t WHERE x NOT IN (1, 2)")]
+    #[case("SELECT * FROM t WHERE x NOT IN UNNEST([1, 2])")]
+    #[case("SELECT * FROM t WHERE x NOT IN (SELECT y FROM u WHERE y IS NOT NULL)")]
+    #[case("SELECT * FROM t WHERE x NOT IN (SELECT u.y FROM u WHERE z > 0 AND u.y IS NOT NULL)")]
+    #[case("SELECT * FROM t WHERE x NOT IN (SELECT COALESCE(y, 0) FROM u)")]
+    #[case("SELECT * FROM t WHERE x NOT IN (SELECT IFNULL(y, '') FROM u)")]
+    #[case("SELECT * FROM t WHERE x NOT IN (SELECT 1 FROM u)")]
+    #[case("SELECT * FROM t WHERE NOT EXISTS (SELECT 1 FROM u WHERE u.y = t.x)")]
+    fn accepts_non_null_subqueries(#[case] sql: &str) {
+        assert!(run_rule(&NotInNullableSubquery, sql).is_empty(), "{sql}");
+    }
+
+    #[test]
+    fn points_at_the_in_expression_and_the_column() {
+        let sql = "SELECT * FROM t WHERE x NOT IN (SELECT y FROM u)";
+        let diagnostics = run_rule(&NotInNullableSubquery, sql);
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.col(), sql.find("x NOT").unwrap() + 1);
+
+        let related = diagnostic.related();
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].start, (1, sql.find("y FROM").unwrap() + 1));
+        assert!(diagnostic.help().unwrap().contains("WHERE y IS NOT NULL"));
+    }
+
+    #[rstest]
+    #[case("SELECT * FROM t WHERE x NOT IN (SELECT id FROM ds.users)", false)]
+    #[case(
+        "SELECT * FROM t WHERE x NOT IN (SELECT u.id FROM `proj.ds.users` AS u)",
+        false
+    )]
+    #[case("SELECT * FROM t WHERE x NOT IN (SELECT email FROM ds.users)", true)]
+    #[case(
+        "SELECT * FROM t WHERE x NOT IN (SELECT id FROM ds.users JOIN ds.orders USING (id))",
+        true
+    )]
+    fn trusts_columns_the_schema_declares_required(#[case] sql: &str, #[case] flagged: bool) {
+        let config = Config::from_toml(
+            r#"
+[schema."ds.users"]
+id = { type = "INT64", mode = "REQUIRED" }
+email = { type = "STRING" }
+"#,
+        )
+        .unwrap();
+        let diagnostics = run_rule_with_schema(&NotInNullableSubquery, sql, &config.schema);
+        assert_eq!(!diagnostics.is_empty(), flagged, "{sql}");
+    }
+}
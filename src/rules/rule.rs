@@ -1,13 +1,87 @@
 use std::collections::HashSet;
+use std::ops::Range;
 
 use crate::ast::{Ast, NodeRef};
-use crate::diagnostic::Diagnostic;
+use crate::config::{CustomRuleConfig, RuleOptions};
+use crate::diagnostic::{Diagnostic, Severity};
 use crate::rules::{
     apply_function_to_partition_column::ApplyFunctionToPartitionColumn,
-    compare_table_suffix_with_subquery::CompareTableSuffixWithSubquery,
-    invalid_group_by::InvalidGroupBy, unnecessary_order_by::UnnecessaryOrderBy,
-    unused_column_in_cte::UnusedColumnInCte, use_current_date::UseCurrentDate,
+    compare_table_suffix_with_subquery::CompareTableSuffixWithSubquery, custom::CustomRule,
+    exact_aggregation::ExactAggregation, invalid_group_by::InvalidGroupBy,
+    legacy_syntax::LegacySyntax, missing_join_condition::MissingJoinCondition,
+    non_equi_join::NonEquiJoin, nondeterministic_function::NondeterministicFunction,
+    not_in_nullable_subquery::NotInNullableSubquery,
+    set_operation_column_mismatch::SetOperationColumnMismatch,
+    temporal_type_mismatch::TemporalTypeMismatch, unnecessary_order_by::UnnecessaryOrderBy,
+    unnecessary_union_distinct::UnnecessaryUnionDistinct,
+    unqualified_column_reference::UnqualifiedColumnReference,
+    unused_column_in_cte::UnusedColumnInCte, window_function_misuse::WindowFunctionMisuse,
 };
+use crate::schema::SchemaCatalog;
+use crate::udf::UdfCatalog;
+
+/// What a rule guards against, used to group rules in listings and as a SARIF
+/// tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    /// Queries BigQuery rejects or that return wrong results.
+    Correctness,
+    /// Queries that scan or compute more than they need to.
+    Performance,
+    /// Queries that are harder to read, reuse or reproduce.
+    Maintainability,
+    /// Rules declared in the config file.
+    Custom,
+}
+
+impl Category {
+    /// The lowercase name used in listings and machine-readable output.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Correctness => "correctness",
+            Self::Performance => "performance",
+            Self::Maintainability => "maintainability",
+            Self::Custom => "custom",
+        }
+    }
+}
+
+/// Everything `bqvalid rules`, `bqvalid explain` and the SARIF rule table say
+/// about a rule. Built-in rules return a `'static` constant; custom rules
+/// borrow from their config entry.
+#[derive(Debug, Clone, Copy)]
+pub struct RuleMetadata<'a> {
+    /// Human-readable title, e.g. "Using CURRENT_DATE".
+    pub name: &'a str,
+    /// The severity the rule reports with unless configured otherwise.
+    pub default_severity: Severity,
+    /// Whether the rule reports anything by default. Opt-in rules are off
+    /// until their options enable them.
+    pub default_enabled: bool,
+    pub category: Category,
+    /// One sentence saying what is flagged.
+    pub summary: &'a str,
+    /// Why it matters and what exactly is matched.
+    pub description: &'a str,
+    /// A query the rule flags.
+    pub bad_example: Option<&'a str>,
+    /// The same query written so the rule stays quiet.
+    pub good_example: Option<&'a str>,
+    /// Where the rule is documented in full.
+    pub doc_url: Option<&'a str>,
+    /// Whether bqvalid can rewrite the flagged code itself.
+    pub fixable: bool,
+}
+
+/// What a rule sees besides the tree: the script's source text, the
+/// user-defined functions in scope for it and the declared table schemas.
+#[derive(Clone, Copy)]
+pub struct LintContext<'a> {
+    pub sql: &'a str,
+    pub udfs: &'a UdfCatalog,
+    pub schema: &'a SchemaCatalog,
+}
 
 /// A single lint rule.
 ///
@@ -16,52 +90,150 @@ use crate::rules::{
 /// calls once per node so every rule sees the tree in a single pre-order pass.
 /// Rules that need cross-node analysis (e.g. tracking CTE columns across the
 /// whole query) instead implement [`Rule::check_tree`] and walk the tree
-/// themselves.
-pub trait Rule {
+/// themselves. Rules that recognise syntax the parser rejects can also
+/// implement [`Rule::check_unparsed`] to look at the source it gave up on.
+///
+/// Rules are shared across the worker threads that lint files in parallel, so
+/// they must be `Send + Sync`.
+pub trait Rule: Send + Sync {
     /// Stable identifier for the rule. Used to reference the rule in output and
-    /// (in the future) in configuration and inline suppression.
-    fn id(&self) -> &'static str;
+    /// configuration. Built-in rules return a constant; config-defined custom
+    /// rules return the id they were declared with.
+    fn id(&self) -> &str;
+
+    /// Descriptive metadata for listings, `explain` and SARIF output.
+    fn metadata(&self) -> RuleMetadata<'_>;
 
     /// React to a single node visited during the shared pre-order traversal.
     /// Node-driven rules override this; the default does nothing so tree-driven
     /// rules can ignore it.
-    fn check_node(&self, _node: NodeRef<'_>, _sql: &str, _diagnostics: &mut Vec<Diagnostic>) {}
+    fn check_node(
+        &self,
+        _node: NodeRef<'_>,
+        _cx: &LintContext<'_>,
+        _diagnostics: &mut Vec<Diagnostic>,
+    ) {
+    }
 
     /// React to the whole tree, for rules that cannot be expressed per node.
     /// The default does nothing so node-driven rules can ignore it.
-    fn check_tree(&self, _ast: &Ast, _sql: &str, _diagnostics: &mut Vec<Diagnostic>) {}
+    fn check_tree(&self, _ast: &Ast, _cx: &LintContext<'_>, _diagnostics: &mut Vec<Diagnostic>) {}
 
-    /// Run this rule alone over `ast`. Convenience for unit tests and callers
-    /// that want a single rule's diagnostics; [`run_rules`] shares one traversal
-    /// across every rule instead.
+    /// React to the source the parser gave up on: `cx.sql[unparsed]`, from the
+    /// end of the last statement it recovered to the end of the script. Only
+    /// called when a syntax error stopped the parser. The default does nothing.
+    fn check_unparsed(
+        &self,
+        _unparsed: Range<usize>,
+        _cx: &LintContext<'_>,
+        _diagnostics: &mut Vec<Diagnostic>,
+    ) {
+    }
+
+    /// Run this rule alone over `ast` with no user-defined functions or table
+    /// schemas in scope.
+    /// Convenience for unit tests and callers that want a single rule's
+    /// diagnostics; [`run_rules`] shares one traversal across every rule
+    /// instead.
     fn check(&self, ast: &Ast, sql: &str) -> Vec<Diagnostic> {
+        let (udfs, schema) = (UdfCatalog::default(), SchemaCatalog::default());
+        self.check_with(
+            ast,
+            &LintContext {
+                sql,
+                udfs: &udfs,
+                schema: &schema,
+            },
+        )
+    }
+
+    /// Run this rule alone over `ast` in the given context.
+    fn check_with(&self, ast: &Ast, cx: &LintContext<'_>) -> Vec<Diagnostic> {
         let mut diagnostics = Vec::new();
         for node in ast.pre_order() {
-            self.check_node(node, sql, &mut diagnostics);
+            self.check_node(node, cx, &mut diagnostics);
         }
-        self.check_tree(ast, sql, &mut diagnostics);
+        self.check_tree(ast, cx, &mut diagnostics);
         diagnostics
     }
 }
 
-/// The registry of every enabled rule. This is the single place rules are wired
-/// in: adding a rule means adding one entry here rather than editing the
-/// analysis loop.
+/// Every registered rule with its default options, see [`all_rules_with`].
 pub fn all_rules() -> Vec<Box<dyn Rule>> {
+    all_rules_with(&RuleOptions::default())
+}
+
+/// The registry of every enabled rule, each built from its `[rules.<id>]`
+/// options. This is the single place rules are wired in: adding a rule means
+/// adding one entry here rather than editing the analysis loop.
+pub fn all_rules_with(options: &RuleOptions) -> Vec<Box<dyn Rule>> {
     vec![
-        Box::new(ApplyFunctionToPartitionColumn),
+        Box::new(ApplyFunctionToPartitionColumn::new(
+            options.apply_function_to_partition_column.clone(),
+        )),
         Box::new(CompareTableSuffixWithSubquery),
-        Box::new(InvalidGroupBy),
+        Box::new(ExactAggregation::new(options.exact_aggregation.clone())),
+        Box::new(InvalidGroupBy::new(&options.invalid_group_by)),
+        Box::new(LegacySyntax),
+        Box::new(MissingJoinCondition),
+        Box::new(NonEquiJoin),
+        Box::new(NondeterministicFunction::new(
+            options.nondeterministic_function.clone(),
+        )),
+        Box::new(NotInNullableSubquery),
+        Box::new(SetOperationColumnMismatch),
+        Box::new(TemporalTypeMismatch),
         Box::new(UnnecessaryOrderBy),
+        Box::new(UnnecessaryUnionDistinct),
+        Box::new(UnqualifiedColumnReference),
         Box::new(UnusedColumnInCte),
-        Box::new(UseCurrentDate),
+        Box::new(WindowFunctionMisuse),
     ]
 }
 
 /// The id of every registered rule. Callers use this to validate user-supplied
 /// ignore lists so unknown ids can be reported rather than silently dropped.
-pub fn known_rule_ids() -> HashSet<&'static str> {
-    all_rules().iter().map(|r| r.id()).collect()
+pub fn known_rule_ids() -> HashSet<String> {
+    all_rules().iter().map(|r| r.id().to_string()).collect()
+}
+
+/// Former ids of renamed rules, each with the id the rule has now.
+const RENAMED_RULE_IDS: &[(&str, &str)] = &[("use_current_date", "nondeterministic_function")];
+
+/// The current id of a rule that used to be called `id`, so ignore lists and
+/// commands written for an older release keep working. `None` when `id` was
+/// never renamed.
+#[must_use]
+pub fn renamed_rule_id(id: &str) -> Option<&'static str> {
+    RENAMED_RULE_IDS
+        .iter()
+        .find(|(old, _)| *old == id)
+        .map(|(_, new)| *new)
+}
+
+/// The rules a run should apply: every registered rule built with `options`,
+/// plus one [`CustomRule`] per `custom` entry, minus any whose id is in
+/// `ignore`.
+///
+/// # Errors
+/// Returns a message naming the offending entry when a custom rule's selector
+/// does not parse or its id collides with another rule.
+pub fn configured_rules(
+    options: &RuleOptions,
+    custom: &[CustomRuleConfig],
+    ignore: &HashSet<String>,
+) -> Result<Vec<Box<dyn Rule>>, String> {
+    let mut rules = all_rules_with(options);
+    for config in custom {
+        if rules.iter().any(|rule| rule.id() == config.id) {
+            return Err(format!("duplicate rule id in custom_rules: {}", config.id));
+        }
+        let rule = CustomRule::from_config(config)
+            .map_err(|e| format!("custom rule {}: {}", config.id, e))?;
+        rules.push(Box::new(rule));
+    }
+    rules.retain(|rule| !ignore.contains(rule.id()));
+    Ok(rules)
 }
 
 /// Run every registered rule over `ast`, see [`run_rules_ignoring`].
@@ -81,20 +253,48 @@ pub fn run_rules_ignoring(ast: &Ast, sql: &str, ignore: &HashSet<String>) -> Vec
         .into_iter()
         .filter(|rule| !ignore.contains(rule.id()))
         .collect();
+    let (udfs, schema) = (UdfCatalog::default(), SchemaCatalog::default());
+    let cx = LintContext {
+        sql,
+        udfs: &udfs,
+        schema: &schema,
+    };
+    run_rules_with(ast, &cx, &rules)
+}
+
+/// Run an already-built rule set over `ast` in a single pre-order traversal.
+///
+/// Callers that lint many inputs build the set once (see [`configured_rules`])
+/// and share it, rather than rebuilding the registry per file.
+pub fn run_rules_with(ast: &Ast, cx: &LintContext<'_>, rules: &[Box<dyn Rule>]) -> Vec<Diagnostic> {
     let mut diagnostics = Vec::new();
 
     for node in ast.pre_order() {
-        for rule in &rules {
-            rule.check_node(node, sql, &mut diagnostics);
+        for rule in rules {
+            rule.check_node(node, cx, &mut diagnostics);
         }
     }
-    for rule in &rules {
-        rule.check_tree(ast, sql, &mut diagnostics);
+    for rule in rules {
+        rule.check_tree(ast, cx, &mut diagnostics);
     }
 
     diagnostics
 }
 
+/// Run `rules` over the source the parser gave up on, see
+/// [`Rule::check_unparsed`].
+pub fn run_rules_on_unparsed(
+    unparsed: Range<usize>,
+    cx: &LintContext<'_>,
+    rules: &[Box<dyn Rule>],
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for rule in rules {
+        rule.check_unparsed(unparsed.clone(), cx, &mut diagnostics);
+    }
+    diagnostics
+}
+
 #[cfg(test)]
 #[allow(
     clippy::unwrap_used,
@@ -106,19 +306,77 @@ pub fn run_rules_ignoring(ast: &Ast, sql: &str, ignore: &HashSet<String>) -> Vec
 )]
 mod tests {
     use super::*;
+    use crate::diagnostic::FunctionLevel;
+    use crate::rules::exact_aggregation::ExactAggregationOptions;
     use crate::rules::helpers::parse_sql;
     use std::collections::HashSet;
 
     #[test]
     fn all_rules_have_unique_non_empty_ids() {
         let rules = all_rules();
-        assert_eq!(rules.len(), 6, "every rule must be registered");
+        assert_eq!(rules.len(), 16, "every rule must be registered");
 
         let ids: HashSet<&str> = rules.iter().map(|r| r.id()).collect();
         assert_eq!(ids.len(), rules.len(), "rule ids must be unique");
         assert!(rules.iter().all(|r| !r.id().is_empty()), "ids must be set");
     }
 
+    /// Options turning on every opt-in rule.
+    fn opt_in_rules_enabled() -> RuleOptions {
+        RuleOptions {
+            exact_aggregation: ExactAggregationOptions {
+                level: FunctionLevel::Warning,
+                ..ExactAggregationOptions::default()
+            },
+            ..RuleOptions::default()
+        }
+    }
+
+    #[test]
+    fn built_in_examples_demonstrate_their_rule() {
+        // The bad example must trip the rule it documents and the good one
+        // must not, so `bqvalid explain` never shows a misleading pair.
+        for rule in all_rules_with(&opt_in_rules_enabled()) {
+            let meta = rule.metadata();
+            assert!(!meta.name.is_empty() && !meta.summary.is_empty());
+            assert!(
+                meta.doc_url
+                    .is_some_and(|url| url.contains("docs/rules.md#")),
+                "{} must link its docs",
+                rule.id()
+            );
+
+            let bad = meta.bad_example.expect("built-in rules have examples");
+            assert!(
+                !rule.check(&parse_sql(bad), bad).is_empty(),
+                "{}: bad example not flagged",
+                rule.id()
+            );
+            let good = meta.good_example.expect("built-in rules have examples");
+            assert!(
+                rule.check(&parse_sql(good), good).is_empty(),
+                "{}: good example flagged",
+                rule.id()
+            );
+        }
+    }
+
+    #[test]
+    fn opt_in_rules_report_nothing_by_default() {
+        // `default_enabled` must match what the default options do; the test
+        // above checks the examples with the opt-in rules turned on.
+        for rule in all_rules() {
+            let meta = rule.metadata();
+            let bad = meta.bad_example.expect("built-in rules have examples");
+            assert_eq!(
+                !rule.check(&parse_sql(bad), bad).is_empty(),
+                meta.default_enabled,
+                "{}: default_enabled does not match the default options",
+                rule.id()
+            );
+        }
+    }
+
     #[test]
     fn run_rules_aggregates_multiple_rules_in_a_single_pass() {
         // One query that trips two independent node-driven rules. run_rules must
@@ -177,19 +435,34 @@ mod tests {
     fn known_rule_ids_matches_the_registry() {
         let ids = known_rule_ids();
         assert_eq!(ids.len(), all_rules().len());
-        assert!(ids.contains("use_current_date"));
+        assert!(ids.contains("nondeterministic_function"));
         assert!(ids.contains("compare_table_suffix_with_subquery"));
     }
 
+    #[test]
+    fn renamed_rule_ids_point_at_registered_rules() {
+        let ids = known_rule_ids();
+        for (old, new) in RENAMED_RULE_IDS {
+            assert_eq!(renamed_rule_id(old), Some(*new));
+            assert!(
+                ids.contains(*new),
+                "{old} must be renamed to a registered rule"
+            );
+            assert!(!ids.contains(*old), "{old} must not be reused");
+        }
+        assert_eq!(renamed_rule_id("invalid_group_by"), None);
+    }
+
     #[test]
     fn run_rules_ignoring_skips_the_named_rule() {
-        // This query trips use_current_date and compare_table_suffix_with_subquery.
+        // This query trips nondeterministic_function and compare_table_suffix_with_subquery.
         // Ignoring the former must drop only its diagnostic, leaving the other.
         let sql = "SELECT CURRENT_DATE() AS d \
                    FROM t \
                    WHERE _TABLE_SUFFIX = (SELECT MAX(suffix) FROM u)";
         let tree = parse_sql(sql);
-        let ignore: HashSet<String> = std::iter::once("use_current_date".to_string()).collect();
+        let ignore: HashSet<String> =
+            std::iter::once("nondeterministic_function".to_string()).collect();
 
         let diagnostics = run_rules_ignoring(&tree, sql, &ignore);
 
@@ -224,6 +497,71 @@ mod tests {
         assert_eq!(baseline, ignoring);
     }
 
+    fn custom(id: &str, selector: &str) -> CustomRuleConfig {
+        CustomRuleConfig {
+            id: id.to_string(),
+            selector: selector.to_string(),
+            message: "custom".to_string(),
+            severity: crate::diagnostic::Severity::Warning,
+        }
+    }
+
+    #[test]
+    fn configured_rules_adds_custom_rules_and_applies_ignore() {
+        let ignore: HashSet<String> =
+            std::iter::once("nondeterministic_function".to_string()).collect();
+        let rules = configured_rules(
+            &RuleOptions::default(),
+            &[custom("no_star", "ASTStar")],
+            &ignore,
+        )
+        .unwrap();
+        let ids: HashSet<&str> = rules.iter().map(|r| r.id()).collect();
+        assert!(ids.contains("no_star"));
+        assert!(!ids.contains("nondeterministic_function"));
+        assert_eq!(rules.len(), all_rules().len());
+
+        let sql = "SELECT * FROM t";
+        let (udfs, schema) = (UdfCatalog::default(), SchemaCatalog::default());
+        let cx = LintContext {
+            sql,
+            udfs: &udfs,
+            schema: &schema,
+        };
+        let diagnostics = run_rules_with(&parse_sql(sql), &cx, &rules);
+        assert!(diagnostics.iter().any(|d| d.rule_id() == "no_star"));
+    }
+
+    #[test]
+    fn configured_rules_rejects_bad_selectors_and_duplicate_ids() {
+        assert!(
+            configured_rules(
+                &RuleOptions::default(),
+                &[custom("x", "ASTSelect[")],
+                &HashSet::new()
+            )
+            .is_err()
+        );
+        assert!(
+            configured_rules(
+                &RuleOptions::default(),
+                &[custom("nondeterministic_function", "ASTStar")],
+                &HashSet::new()
+            )
+            .is_err(),
+            "a custom rule must not shadow a built-in id"
+        );
+        assert!(
+            configured_rules(
+                &RuleOptions::default(),
+                &[custom("x", "ASTStar"), custom("x", "ASTSelect")],
+                &HashSet::new()
+            )
+            .is_err(),
+            "custom rule ids must be unique"
+        );
+    }
+
     #[test]
     fn run_rules_ignoring_every_rule_yields_nothing() {
         let sql = "SELECT CURRENT_DATE() AS d \
@@ -0,0 +1,291 @@
+use crate::ast::NodeRef;
+
+use crate::diagnostic::{Diagnostic, Related, Severity};
+use crate::rules::helpers::{
+    find_child_of_kind, get_node_text, has_child_of_kind, set_operation_branches, set_operators,
+};
+use crate::rules::rule::{Category, LintContext, Rule, RuleMetadata};
+
+const RULE_ID: &str = "set_operation_column_mismatch";
+
+const METADATA: RuleMetadata<'static> = RuleMetadata {
+    name: "Set operation column mismatch",
+    default_severity: Severity::Error,
+    default_enabled: true,
+    category: Category::Correctness,
+    summary: "The branches of a set operation select different numbers of columns, or name them in a different order.",
+    description: "`UNION`, `INTERSECT` and `EXCEPT` match columns by position and take their names from the first branch. A branch selecting a different number of columns is rejected by BigQuery. A branch whose explicit alias names another column of the first branch is silently mis-aligned: its values end up under the wrong name. An alias that names no column of the first branch is only ignored, which is reported as a warning. `CORRESPONDING` and `BY NAME` operations match columns by name and are not checked.",
+    bad_example: Some(
+        "SELECT id, name FROM users UNION ALL SELECT name AS name, id AS id FROM admins",
+    ),
+    good_example: Some("SELECT id, name FROM users UNION ALL SELECT id, name FROM admins"),
+    doc_url: Some(
+        "https://github.com/hirosassa/bqvalid/blob/main/docs/rules.md#set-operation-column-mismatch",
+    ),
+    fixable: false,
+};
+
+/// Flags set operation branches whose select lists do not line up with the
+/// first branch's.
+pub struct SetOperationColumnMismatch;
+
+/// A column of a branch's select list and the name it is known by.
+struct Column<'a> {
+    node: NodeRef<'a>,
+    name: Option<String>,
+    /// Whether `name` comes from an `AS` alias rather than the column itself.
+    aliased: bool,
+}
+
+impl Rule for SetOperationColumnMismatch {
+    fn id(&self) -> &str {
+        RULE_ID
+    }
+
+    fn metadata(&self) -> RuleMetadata<'_> {
+        METADATA
+    }
+
+    fn check_node(
+        &self,
+        node: NodeRef<'_>,
+        cx: &LintContext<'_>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        if node.kind() == "ASTSetOperation" {
+            check_set_operation(node, cx.sql, diagnostics);
+        }
+    }
+}
+
+fn check_set_operation(node: NodeRef<'_>, src: &str, diagnostics: &mut Vec<Diagnostic>) {
+    if set_operators(&node, src)
+        .iter()
+        .any(|op| op.contains("CORRESPONDING") || op.contains("BY NAME"))
+    {
+        return;
+    }
+    let branches = set_operation_branches(&node);
+    let Some(first) = branches
+        .first()
+        .and_then(|branch| branch_columns(*branch, src))
+    else {
+        return;
+    };
+
+    for branch in branches.iter().skip(1) {
+        let Some(columns) = branch_columns(*branch, src) else {
+            continue;
+        };
+        if columns.len() != first.len() {
+            diagnostics.push(
+                Diagnostic::for_node(
+                    RULE_ID,
+                    Severity::Error,
+                    branch,
+                    format!(
+                        "this branch selects {} columns, but the first branch selects {}",
+                        columns.len(),
+                        first.len()
+                    ),
+                )
+                .with_related(Related::for_node(
+                    branches.first().unwrap_or(branch),
+                    "first branch",
+                )),
+            );
+            continue;
+        }
+        for (column, expected) in columns.iter().zip(&first) {
+            if let Some(diagnostic) = misnamed(column, expected, &first) {
+                diagnostics.push(diagnostic);
+            }
+        }
+    }
+}
+
+/// The diagnostic for a column whose alias disagrees with the first branch's
+/// column at the same position: an error when the alias names another column
+/// of the first branch, a warning when it names none.
+fn misnamed(
+    column: &Column<'_>,
+    expected: &Column<'_>,
+    first: &[Column<'_>],
+) -> Option<Diagnostic> {
+    let name = column.name.as_deref().filter(|_| column.aliased)?;
+    if expected
+        .name
+        .as_deref()
+        .is_some_and(|expected| expected.eq_ignore_ascii_case(name))
+    {
+        return None;
+    }
+
+    if let Some(other) = first.iter().find(|c| {
+        c.name
+            .as_deref()
+            .is_some_and(|other| other.eq_ignore_ascii_case(name))
+    }) {
+        let position = match &expected.name {
+            Some(expected) => format!("`{}`", expected),
+            None => "an unnamed column".to_string(),
+        };
+        return Some(
+            Diagnostic::for_node(
+                RULE_ID,
+                Severity::Error,
+                &column.node,
+                format!("`{}` lines up with {} of the first branch", name, position),
+            )
+            .with_related(Related::for_node(
+                &other.node,
+                format!("`{}` is here", name),
+            ))
+            .with_note("set operations match columns by position, not by name")
+            .with_help("reorder the select list to match the first branch"),
+        );
+    }
+
+    let expected_name = expected.name.as_deref()?;
+    Some(
+        Diagnostic::for_node(
+            RULE_ID,
+            Severity::Warning,
+            &column.node,
+            format!(
+                "alias `{}` is ignored; the column is named `{}`",
+                name, expected_name
+            ),
+        )
+        .with_related(Related::for_node(&expected.node, "name taken from here"))
+        .with_help("drop the alias, or rename the column in the first branch"),
+    )
+}
+
+/// The select list of the first `SELECT` a branch evaluates, or `None` when
+/// its columns cannot be counted without a schema (`*`, `SELECT AS STRUCT`).
+fn branch_columns<'a>(branch: NodeRef<'a>, src: &str) -> Option<Vec<Column<'a>>> {
+    let select = leading_select(branch)?;
+    if has_child_of_kind(&select, "ASTSelectAs") {
+        return None;
+    }
+    find_child_of_kind(&select, "ASTSelectList")?
+        .named_children()
+        .into_iter()
+        .filter(|column| column.kind() == "ASTSelectColumn")
+        .map(|column| {
+            let expr = column.named_child(0)?;
+            if expr.kind().contains("Star") {
+                return None;
+            }
+            let alias = find_child_of_kind(&column, "ASTAlias")
+                .and_then(|alias| alias.named_children().into_iter().last())
+                .map(|name| get_node_text(&name, src));
+            let implicit = (expr.kind() == "ASTPathExpression")
+                .then(|| get_node_text(&expr, src))
+                .and_then(|path| path.rsplit('.').next());
+            Some(Column {
+                node: column,
+                aliased: alias.is_some(),
+                name: alias
+                    .or(implicit)
+                    .map(|name| name.trim().trim_matches('`').to_string()),
+            })
+        })
+        .collect()
+}
+
+/// The `ASTSelect` whose select list names a branch's columns: the branch
+/// itself, or the first branch of a parenthesized query or set operation.
+fn leading_select(node: NodeRef<'_>) -> Option<NodeRef<'_>> {
+    match node.kind() {
+        "ASTSelect" => Some(node),
+        "ASTQuery" => node.named_children().into_iter().find_map(leading_select),
+        "ASTSetOperation" => set_operation_branches(&node)
+            .into_iter()
+            .next()
+            .and_then(leading_select),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::indexing_slicing,
+    clippy::arithmetic_side_effects,
+    reason = "test code"
+)]
+mod tests {
+    use super::*;
+    use crate::rules::helpers::run_rule;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("SELECT a, b FROM t UNION ALL SELECT a FROM u")]
+    #[case("SELECT a FROM t UNION DISTINCT SELECT a, b FROM u")]
+    #[case("SELECT a, b FROM t UNION ALL SELECT a, b FROM u UNION ALL SELECT a, b, c FROM v")]
+    #[case("(SELECT a, b FROM t) EXCEPT DISTINCT (SELECT a FROM u)")]
+    #[case("SELECT a, b FROM t INTERSECT ALL (SELECT 1 UNION ALL SELECT 2)")]
+    fn flags_column_count_mismatches(#[case] sql: &str) {
+        let diagnostics = run_rule(&SetOperationColumnMismatch, sql);
+        assert_eq!(diagnostics.len(), 1, "{sql}");
+        assert_eq!(diagnostics[0].severity(), Severity::Error, "{sql}");
+    }
+
+    #[rstest]
+    #[case("SELECT id, name FROM t UNION ALL SELECT name AS name, id AS id FROM u")]
+    #[case("SELECT x AS a, y AS b FROM t UNION ALL SELECT 1 AS b, 2 AS c FROM u")]
+    #[case("SELECT t.a, 1 AS b FROM t UNION ALL SELECT u.c AS b, 2 FROM u")]
+    fn flags_aliases_naming_another_column(#[case] sql: &str) {
+        let diagnostics = run_rule(&SetOperationColumnMismatch, sql);
+        assert!(!diagnostics.is_empty(), "{sql}");
+        assert_eq!(diagnostics[0].severity(), Severity::Error, "{sql}");
+    }
+
+    #[test]
+    fn warns_about_ignored_aliases() {
+        let sql = "SELECT a, b FROM t UNION ALL SELECT a, c AS d FROM u";
+        let diagnostics = run_rule(&SetOperationColumnMismatch, sql);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity(), Severity::Warning);
+        assert_eq!(
+            diagnostics[0].message(),
+            "alias `d` is ignored; the column is named `b`"
+        );
+    }
+
+    #[rstest]
+    #[case("SELECT a, b FROM t UNION ALL SELECT a, b FROM u")]
+    #[case("SELECT a, b FROM t UNION ALL SELECT b, a FROM u")]
+    #[case("SELECT src AS a, dst AS b FROM e UNION ALL SELECT dst AS a, src AS b FROM e")]
+    #[case("SELECT a, 1 + 1 FROM t UNION ALL SELECT a, 2 AS two FROM u")]
+    #[case("SELECT * FROM t UNION ALL SELECT a, b FROM u")]
+    #[case("SELECT a, b FROM t UNION ALL SELECT u.* FROM u")]
+    #[case("SELECT a, b FROM t UNION ALL CORRESPONDING SELECT b, a, c FROM u")]
+    #[case("SELECT a FROM t UNION ALL (SELECT b FROM u UNION ALL SELECT c FROM v)")]
+    fn accepts_aligned_branches(#[case] sql: &str) {
+        assert!(
+            run_rule(&SetOperationColumnMismatch, sql).is_empty(),
+            "{sql}"
+        );
+    }
+
+    #[test]
+    fn points_at_the_branch_and_the_first_branch() {
+        let sql = "SELECT a, b FROM t UNION ALL SELECT a FROM u";
+        let diagnostics = run_rule(&SetOperationColumnMismatch, sql);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].col(),
+            sql.find("SELECT a FROM u").unwrap() + 1
+        );
+        assert_eq!(
+            diagnostics[0].message(),
+            "this branch selects 1 columns, but the first branch selects 2"
+        );
+        assert_eq!(diagnostics[0].related()[0].start, (1, 1));
+    }
+}
@@ -0,0 +1,446 @@
+use crate::ast::NodeRef;
+
+use crate::diagnostic::{Diagnostic, Related, Severity};
+use crate::rules::helpers::{binary_operator, find_child_of_kind, get_node_text};
+use crate::rules::rule::{Category, LintContext, Rule, RuleMetadata};
+use crate::schema::SchemaCatalog;
+use crate::types::{SqlType, infer_type};
+
+const RULE_ID: &str = "temporal_type_mismatch";
+
+const METADATA: RuleMetadata<'static> = RuleMetadata {
+    name: "Mismatched date and time comparison",
+    default_severity: Severity::Warning,
+    default_enabled: true,
+    category: Category::Correctness,
+    summary: "A comparison mixes date and time types, or compares one with a string that is not a canonical literal of its type.",
+    description: "BigQuery converts the other side of a comparison with a date or time value implicitly. A string compared with a `TIMESTAMP` is read in UTC, a `DATE` compared with a `DATETIME` becomes midnight of that day, and strings in another format fail at run time. These conversions are a common source of off-by-a-timezone bugs. Any other pair of different date and time types, such as a `DATE` and a `TIMESTAMP` or a `TIME` and anything else, is rejected with \"No matching signature for operator\". The rule infers types from literals, casts and built-in functions, plus the column types declared in the `[schema]` configuration, and flags comparisons between different date and time types (an error unless they are a `DATE` and a `DATETIME`), string literals BigQuery cannot read as the other side's type (an error), string literals compared with a `TIMESTAMP` without a time zone, and strings not in canonical form.",
+    bad_example: Some("SELECT * FROM events WHERE CAST(created_at AS TIMESTAMP) >= '2024-01-01'"),
+    good_example: Some(
+        "SELECT * FROM events WHERE CAST(created_at AS TIMESTAMP) >= TIMESTAMP '2024-01-01 00:00:00 Asia/Tokyo'",
+    ),
+    doc_url: Some(
+        "https://github.com/hirosassa/bqvalid/blob/main/docs/rules.md#mismatched-date-and-time-comparison",
+    ),
+    fixable: false,
+};
+
+/// The operators that compare their operands.
+const COMPARISON_OPERATORS: [&str; 7] = ["=", "!=", "<>", "<", "<=", ">", ">="];
+
+/// Flags comparisons that mix date and time types or rely on reading a string
+/// literal as one.
+pub struct TemporalTypeMismatch;
+
+impl Rule for TemporalTypeMismatch {
+    fn id(&self) -> &str {
+        RULE_ID
+    }
+
+    fn metadata(&self) -> RuleMetadata<'_> {
+        METADATA
+    }
+
+    fn check_node(
+        &self,
+        node: NodeRef<'_>,
+        cx: &LintContext<'_>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        for (left, right) in compared_pairs(&node, cx.sql) {
+            if let Some(diagnostic) = check_pair(&node, &left, &right, cx.sql, cx.schema) {
+                diagnostics.push(diagnostic);
+            }
+        }
+    }
+}
+
+/// The pairs of operands `node` compares: both sides of a comparison operator,
+/// the tested value with each `BETWEEN` bound, and with each `IN` list item.
+fn compared_pairs<'a>(node: &NodeRef<'a>, src: &str) -> Vec<(NodeRef<'a>, NodeRef<'a>)> {
+    // googlesql marks keyword positions with `ASTLocation` children.
+    let operands: Vec<NodeRef<'a>> = node
+        .named_children()
+        .into_iter()
+        .filter(|child| child.kind() != "ASTLocation")
+        .collect();
+    let Some((&lhs, rest)) = operands.split_first() else {
+        return Vec::new();
+    };
+    let others: Vec<NodeRef<'a>> = match node.kind() {
+        "ASTBinaryExpression" => {
+            let (Some(&rhs), Some(operator)) = (rest.last(), binary_operator(node, src)) else {
+                return Vec::new();
+            };
+            if !COMPARISON_OPERATORS.contains(&operator.as_str()) {
+                return Vec::new();
+            }
+            vec![rhs]
+        }
+        "ASTBetweenExpression" => rest.to_vec(),
+        "ASTInExpression" => find_child_of_kind(node, "ASTInList")
+            .map(|list| list.named_children())
+            .unwrap_or_default(),
+        _ => return Vec::new(),
+    };
+    others.into_iter().map(|other| (lhs, other)).collect()
+}
+
+/// The diagnostic for comparing `left` with `right` in `comparison`, if the
+/// comparison is a problem.
+fn check_pair(
+    comparison: &NodeRef<'_>,
+    left: &NodeRef<'_>,
+    right: &NodeRef<'_>,
+    src: &str,
+    schema: &SchemaCatalog,
+) -> Option<Diagnostic> {
+    match (
+        infer_type(left, src, schema),
+        infer_type(right, src, schema),
+    ) {
+        (Some(l), Some(r)) if l.is_temporal() && r.is_temporal() && l != r => {
+            Some(mismatch(comparison, (left, l), (right, r)))
+        }
+        (Some(ty), _) if ty.is_temporal() && right.kind() == "ASTStringLiteral" => {
+            string_literal(right, ty, src)
+        }
+        (_, Some(ty)) if ty.is_temporal() && left.kind() == "ASTStringLiteral" => {
+            string_literal(left, ty, src)
+        }
+        _ => None,
+    }
+}
+
+/// A comparison between two different date and time types. Only a `DATE` and
+/// a `DATETIME` compare, by converting the `DATE`; BigQuery rejects every other
+/// pair.
+fn mismatch(
+    comparison: &NodeRef<'_>,
+    (left, left_type): (&NodeRef<'_>, SqlType),
+    (right, right_type): (&NodeRef<'_>, SqlType),
+) -> Diagnostic {
+    let converts = matches!(
+        (left_type, right_type),
+        (SqlType::Date, SqlType::Datetime) | (SqlType::Datetime, SqlType::Date)
+    );
+    let (severity, note) = if converts {
+        (
+            Severity::Warning,
+            "the DATE is converted to a DATETIME at midnight".to_string(),
+        )
+    } else {
+        (
+            Severity::Error,
+            format!(
+                "BigQuery has no comparison between {} and {}, so the query fails with \"No matching signature for operator\"",
+                left_type, right_type
+            ),
+        )
+    };
+    Diagnostic::for_node(
+        RULE_ID,
+        severity,
+        comparison,
+        format!("comparing {} with {}", left_type, right_type),
+    )
+    .with_related(Related::for_node(left, format!("this is a {}", left_type)))
+    .with_related(Related::for_node(
+        right,
+        format!("this is a {}", right_type),
+    ))
+    .with_note(note)
+    .with_help("convert one side explicitly, with the time zone you mean")
+}
+
+/// How a string reads as a literal of a date or time type.
+#[derive(Debug, PartialEq, Eq)]
+enum Format {
+    /// Zero-padded, e.g. `2024-01-05`.
+    Canonical,
+    /// Accepted but not zero-padded, e.g. `2024-1-5`.
+    Loose,
+    Invalid,
+}
+
+/// The diagnostic for a string literal compared with a value of type `ty`,
+/// when BigQuery cannot read it as one, reads it in UTC or it is not canonical.
+fn string_literal(literal: &NodeRef<'_>, ty: SqlType, src: &str) -> Option<Diagnostic> {
+    let text = get_node_text(literal, src).trim();
+    let quote = text.chars().next().filter(|c| *c == '\'' || *c == '"')?;
+    let value = text.strip_prefix(quote)?.strip_suffix(quote)?;
+    if value.contains(quote) || value.contains('\\') {
+        return None;
+    }
+
+    let (format, zoned) = match ty {
+        SqlType::Date => (date_format(value), false),
+        SqlType::Time => (time_format(value), false),
+        SqlType::Datetime => (datetime_format(value), false),
+        _ => {
+            let (datetime, zone) = split_zone(value);
+            (datetime_format(datetime), zone.is_some())
+        }
+    };
+    let (severity, message, help) = if format == Format::Invalid {
+        (
+            Severity::Error,
+            format!("{} is not a valid {} literal", text, ty),
+            canonical_help(ty),
+        )
+    } else if ty == SqlType::Timestamp && !zoned {
+        (
+            Severity::Warning,
+            format!("{} is read as a TIMESTAMP in UTC", text),
+            "add the time zone you mean, e.g. `TIMESTAMP '2024-01-01 00:00:00 Asia/Tokyo'`"
+                .to_string(),
+        )
+    } else if format == Format::Loose {
+        (
+            Severity::Warning,
+            format!("{} is not a canonical {} literal", text, ty),
+            canonical_help(ty),
+        )
+    } else {
+        return None;
+    };
+    Some(Diagnostic::for_node(RULE_ID, severity, literal, message).with_help(help))
+}
+
+fn canonical_help(ty: SqlType) -> String {
+    let form = match ty {
+        SqlType::Date => "YYYY-MM-DD",
+        SqlType::Time => "HH:MM:SS",
+        SqlType::Datetime => "YYYY-MM-DD HH:MM:SS",
+        _ => "YYYY-MM-DD HH:MM:SS+HH:MM",
+    };
+    format!("write it as {} '{}'", ty, form)
+}
+
+/// `YYYY-[M]M-[D]D`.
+fn date_format(value: &str) -> Format {
+    let mut parts = value.split('-');
+    let (Some(year), Some(month), Some(day), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Format::Invalid;
+    };
+    if year.len() != 4 || !is_number(year) {
+        return Format::Invalid;
+    }
+    fields_format(&[(month, 1, 12), (day, 1, 31)])
+}
+
+/// `[H]H:[M]M:[S]S[.F]`.
+fn time_format(value: &str) -> Format {
+    let (clock, fraction) = value.split_once('.').unwrap_or((value, "0"));
+    if fraction.is_empty() || fraction.len() > 9 || !is_number(fraction) {
+        return Format::Invalid;
+    }
+    let mut parts = clock.split(':');
+    let (Some(hour), Some(minute), Some(second), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Format::Invalid;
+    };
+    fields_format(&[(hour, 0, 23), (minute, 0, 59), (second, 0, 59)])
+}
+
+/// A date, optionally followed by a space or `T` and a time.
+fn datetime_format(value: &str) -> Format {
+    match value.split_once([' ', 'T', 't']) {
+        Some((date, time)) => match (date_format(date), time_format(time)) {
+            (Format::Invalid, _) | (_, Format::Invalid) => Format::Invalid,
+            (Format::Canonical, Format::Canonical) => Format::Canonical,
+            _ => Format::Loose,
+        },
+        None => date_format(value),
+    }
+}
+
+/// `value` split into its date and time and its time zone, if it names one:
+/// `Z`, an offset such as `+09:00`, or a name such as `UTC` or `Asia/Tokyo`.
+fn split_zone(value: &str) -> (&str, Option<&str>) {
+    let value = value.trim();
+    if let Some(datetime) = value.strip_suffix(['Z', 'z']) {
+        return (datetime, Some("Z"));
+    }
+    if let Some((datetime, zone)) = value.rsplit_once(' ')
+        && zone.starts_with(|c: char| c.is_ascii_alphabetic())
+    {
+        return (datetime, Some(zone));
+    }
+    // An offset follows the time, so look for its sign past the date.
+    match value
+        .char_indices()
+        .skip(10)
+        .find(|&(_, c)| c == '+' || c == '-')
+    {
+        Some((sign, _)) => (value.get(..sign).unwrap_or(value), value.get(sign..)),
+        None => (value, None),
+    }
+}
+
+/// The format of numeric fields given as `(text, min, max)`: invalid when one
+/// is not a one- or two-digit number in range, loose when one is not padded.
+fn fields_format(fields: &[(&str, u32, u32)]) -> Format {
+    let mut format = Format::Canonical;
+    for &(text, min, max) in fields {
+        if text.is_empty() || text.len() > 2 || !is_number(text) {
+            return Format::Invalid;
+        }
+        if !text.parse::<u32>().is_ok_and(|n| (min..=max).contains(&n)) {
+            return Format::Invalid;
+        }
+        if text.len() == 1 {
+            format = Format::Loose;
+        }
+    }
+    format
+}
+
+fn is_number(text: &str) -> bool {
+    text.bytes().all(|b| b.is_ascii_digit())
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::indexing_slicing,
+    clippy::arithmetic_side_effects,
+    reason = "test code"
+)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::rules::helpers::run_rule_with_schema;
+    use rstest::rstest;
+
+    fn run(sql: &str) -> Vec<Diagnostic> {
+        let config = Config::from_toml(
+            r#"
+[schema.t]
+created_at = { type = "TIMESTAMP" }
+Event_Date = { type = "DATE" }
+"#,
+        )
+        .unwrap();
+        run_rule_with_schema(&TemporalTypeMismatch, sql, &config.schema)
+    }
+
+    #[rstest]
+    #[case("SELECT * FROM t WHERE created_at >= '2024-01-01'", Severity::Warning)]
+    #[case(
+        "SELECT * FROM t WHERE t.created_at < DATE '2024-01-01'",
+        Severity::Error
+    )]
+    #[case(
+        "SELECT * FROM t WHERE created_at > CURRENT_DATETIME()",
+        Severity::Error
+    )]
+    #[case(
+        "SELECT * FROM t WHERE CURRENT_TIME() = CURRENT_DATETIME()",
+        Severity::Error
+    )]
+    #[case(
+        "SELECT * FROM t WHERE event_date = CURRENT_DATETIME()",
+        Severity::Warning
+    )]
+    #[case("SELECT * FROM t WHERE event_date = '2024/01/01'", Severity::Error)]
+    #[case("SELECT * FROM t WHERE event_date = '2024-1-1'", Severity::Warning)]
+    #[case(
+        "SELECT * FROM t WHERE event_date BETWEEN '2024-01-01' AND '2024-13-01'",
+        Severity::Error
+    )]
+    #[case(
+        "SELECT * FROM t WHERE event_date IN ('2024-01-01', '20240102')",
+        Severity::Error
+    )]
+    #[case(
+        "SELECT * FROM t WHERE CAST(x AS DATETIME) > '2024-01-01 25:00:00'",
+        Severity::Error
+    )]
+    #[case(
+        "SELECT * FROM t WHERE _PARTITIONTIME >= '2024-01-01'",
+        Severity::Warning
+    )]
+    fn flags_mismatched_comparisons(#[case] sql: &str, #[case] severity: Severity) {
+        let diagnostics = run(sql);
+        assert_eq!(diagnostics.len(), 1, "{sql}");
+        assert_eq!(diagnostics[0].severity(), severity, "{sql}");
+    }
+
+    #[rstest]
+    #[case("SELECT * FROM t WHERE created_at >= TIMESTAMP '2024-01-01 00:00:00 Asia/Tokyo'")]
+    #[case("SELECT * FROM t WHERE created_at >= '2024-01-01T00:00:00Z'")]
+    #[case("SELECT * FROM t WHERE created_at >= '2024-01-01 00:00:00+09:00'")]
+    #[case("SELECT * FROM t WHERE event_date = '2024-01-01'")]
+    #[case("SELECT * FROM t WHERE event_date BETWEEN '2024-01-01' AND DATE '2024-01-31'")]
+    #[case("SELECT * FROM t WHERE DATE(created_at, 'Asia/Tokyo') = event_date")]
+    #[case("SELECT * FROM t WHERE updated_at >= '2024/01/01'")]
+    #[case("SELECT * FROM t WHERE event_date IS NOT NULL")]
+    #[case("SELECT * FROM t WHERE FORMAT_DATE('%Y', event_date) = '2024'")]
+    fn accepts_consistent_comparisons(#[case] sql: &str) {
+        assert!(run(sql).is_empty(), "{sql}");
+    }
+
+    #[test]
+    fn names_both_types_and_the_conversion() {
+        let sql = "SELECT * FROM t WHERE event_date < CURRENT_DATETIME()";
+        let diagnostics = run(sql);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.message(), "comparing DATE with DATETIME");
+        assert_eq!(
+            diagnostic.notes(),
+            ["the DATE is converted to a DATETIME at midnight"]
+        );
+        assert_eq!(diagnostic.related().len(), 2);
+    }
+
+    #[test]
+    fn explains_the_comparisons_bigquery_rejects() {
+        let sql = "SELECT * FROM t WHERE created_at < DATE '2024-01-01'";
+        let diagnostics = run(sql);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.severity(), Severity::Error);
+        assert_eq!(diagnostic.message(), "comparing TIMESTAMP with DATE");
+        assert_eq!(
+            diagnostic.notes(),
+            [
+                "BigQuery has no comparison between TIMESTAMP and DATE, so the query fails with \"No matching signature for operator\""
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case("2024-01-05", Format::Canonical)]
+    #[case("2024-1-5", Format::Loose)]
+    #[case("2024-02-30x", Format::Invalid)]
+    #[case("2024/01/05", Format::Invalid)]
+    #[case("24-01-05", Format::Invalid)]
+    fn reads_date_strings(#[case] value: &str, #[case] expected: Format) {
+        assert_eq!(date_format(value), expected, "{value}");
+    }
+
+    #[rstest]
+    #[case("2024-01-05 10:00:00", Format::Canonical)]
+    #[case("2024-01-05T10:00:00.123456", Format::Canonical)]
+    #[case("2024-01-05 9:00:00", Format::Loose)]
+    #[case("2024-01-05 10:00", Format::Invalid)]
+    #[case("2024-01-05", Format::Canonical)]
+    fn reads_datetime_strings(#[case] value: &str, #[case] expected: Format) {
+        assert_eq!(datetime_format(value), expected, "{value}");
+    }
+
+    #[rstest]
+    #[case("2024-01-05 10:00:00", ("2024-01-05 10:00:00", None))]
+    #[case("2024-01-05 10:00:00Z", ("2024-01-05 10:00:00", Some("Z")))]
+    #[case("2024-01-05 10:00:00-05:00", ("2024-01-05 10:00:00", Some("-05:00")))]
+    #[case("2024-01-05 10:00:00 America/New_York", ("2024-01-05 10:00:00", Some("America/New_York")))]
+    #[case("2024-01-05", ("2024-01-05", None))]
+    fn splits_time_zones(#[case] value: &str, #[case] expected: (&str, Option<&str>)) {
+        assert_eq!(split_zone(value), expected, "{value}");
+    }
+}
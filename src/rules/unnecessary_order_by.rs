@@ -1,21 +1,50 @@
 use crate::ast::NodeRef;
 
-use crate::diagnostic::{Diagnostic, Severity};
-use crate::rules::helpers::{find_child_of_kind, has_child_of_kind, one_based_start};
-use crate::rules::rule::Rule;
+use crate::diagnostic::{Diagnostic, Severity, Suggestion};
+use crate::rules::helpers::{find_child_of_kind, has_child_of_kind, one_based_end};
+use crate::rules::rule::{Category, LintContext, Rule, RuleMetadata};
 
 const RULE_ID: &str = "unnecessary_order_by";
 
+const METADATA: RuleMetadata<'static> = RuleMetadata {
+    name: "Unnecessary ORDER BY in CTE or subquery",
+    default_severity: Severity::Warning,
+    default_enabled: true,
+    category: Category::Performance,
+    summary: "A CTE or subquery is sorted without a LIMIT, so the sort has no effect.",
+    description: "The order of rows produced by a CTE or subquery is not preserved by the enclosing query, so an `ORDER BY` there only costs compute. It is meaningful with `LIMIT`/`OFFSET`, inside aggregates such as `ARRAY_AGG`, and in the outermost query.",
+    bad_example: Some(
+        "WITH sorted AS (SELECT id, name FROM table1 ORDER BY id) SELECT * FROM sorted",
+    ),
+    good_example: Some(
+        "WITH sorted AS (SELECT id, name FROM table1) SELECT * FROM sorted ORDER BY id",
+    ),
+    doc_url: Some(
+        "https://github.com/hirosassa/bqvalid/blob/main/docs/rules.md#unnecessary-order-by-in-cte-or-subquery",
+    ),
+    fixable: true,
+};
+
 /// Flags `ORDER BY` in a CTE or subquery without `LIMIT`, where the sort has no
 /// effect and only wastes work.
 pub struct UnnecessaryOrderBy;
 
 impl Rule for UnnecessaryOrderBy {
-    fn id(&self) -> &'static str {
+    fn id(&self) -> &str {
         RULE_ID
     }
 
-    fn check_node(&self, node: NodeRef<'_>, sql: &str, diagnostics: &mut Vec<Diagnostic>) {
+    fn metadata(&self) -> RuleMetadata<'_> {
+        METADATA
+    }
+
+    fn check_node(
+        &self,
+        node: NodeRef<'_>,
+        cx: &LintContext<'_>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let sql = cx.sql;
         if node.kind() == "ASTQuery"
             && let Some(diagnostic) = check_unnecessary_order_by_in_scope(&node, sql)
         {
@@ -55,14 +84,28 @@ fn check_unnecessary_order_by_in_scope(scope_node: &NodeRef<'_>, _sql: &str) ->
     if !has_child_of_kind(&query_body, limit_kind)
         && let Some(order_by_node) = find_child_of_kind(&query_body, order_by_kind)
     {
-        let (row, col) = one_based_start(&order_by_node);
-        return Some(Diagnostic::new(
+        let mut diagnostic = Diagnostic::for_node(
             RULE_ID,
             Severity::Warning,
-            row,
-            col,
+            &order_by_node,
             "Unnecessary ORDER BY: This ORDER BY clause has no effect without LIMIT/OFFSET or in aggregate functions".to_string(),
-        ));
+        )
+        .with_help("remove the ORDER BY, or add a LIMIT if only the first rows are wanted");
+        // Deleting from the end of the preceding clause drops the whitespace
+        // before `ORDER BY` along with it.
+        if let Some(previous) = query_body
+            .children()
+            .into_iter()
+            .take_while(|child| child.end_byte() <= order_by_node.start_byte())
+            .last()
+        {
+            diagnostic = diagnostic.with_suggestion(Suggestion {
+                start: one_based_end(&previous),
+                end: one_based_end(&order_by_node),
+                text: String::new(),
+            });
+        }
+        return Some(diagnostic);
     }
 
     None
@@ -195,4 +238,29 @@ order by id;
         assert_eq!(diagnostics[0].row(), 1);
         assert_eq!(diagnostics[0].col(), order_col + 1);
     }
+
+    #[test]
+    fn suggests_deleting_the_order_by_clause() {
+        let sql = "WITH sorted AS (SELECT id FROM t ORDER BY id) SELECT * FROM sorted";
+        let diagnostics = run_rule(&UnnecessaryOrderBy, sql);
+        assert_eq!(diagnostics.len(), 1);
+
+        let diagnostic = &diagnostics[0];
+        assert_eq!((diagnostic.row(), diagnostic.col()), (1, 34));
+        assert_eq!(diagnostic.end(), Some((1, 45)));
+        let [fix] = diagnostic.suggestions() else {
+            panic!("expected one suggestion");
+        };
+        // Single-line source: the 1-based columns map straight to byte offsets.
+        let fixed = format!(
+            "{}{}{}",
+            sql.get(..fix.start.1 - 1).unwrap(),
+            fix.text,
+            sql.get(fix.end.1 - 1..).unwrap()
+        );
+        assert_eq!(
+            fixed,
+            "WITH sorted AS (SELECT id FROM t) SELECT * FROM sorted"
+        );
+    }
 }
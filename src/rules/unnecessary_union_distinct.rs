@@ -0,0 +1,213 @@
+use crate::ast::NodeRef;
+
+use crate::diagnostic::{Diagnostic, Related, Severity};
+use crate::rules::helpers::{
+    find_child_of_kind, get_node_text, has_child_of_kind, query_select, set_operation_branches,
+    set_operators,
+};
+use crate::rules::rule::{Category, LintContext, Rule, RuleMetadata};
+
+const RULE_ID: &str = "unnecessary_union_distinct";
+
+const METADATA: RuleMetadata<'static> = RuleMetadata {
+    name: "Unnecessary UNION DISTINCT",
+    default_severity: Severity::Warning,
+    default_enabled: true,
+    category: Category::Performance,
+    summary: "`UNION DISTINCT` deduplicates branches that can never share a row.",
+    description: "`UNION DISTINCT` shuffles every row of every branch to remove duplicates. When each branch selects a different literal in the same column, such as a `'web' AS source` discriminator, no row can appear in two branches and the only duplicates left are those within a single branch. Use `UNION ALL`, and `SELECT DISTINCT` in the branches that can repeat rows.",
+    bad_example: Some(
+        "SELECT 'web' AS source, user_id FROM web_events UNION DISTINCT SELECT 'app', user_id FROM app_events",
+    ),
+    good_example: Some(
+        "SELECT 'web' AS source, user_id FROM web_events UNION ALL SELECT 'app', user_id FROM app_events",
+    ),
+    doc_url: Some(
+        "https://github.com/hirosassa/bqvalid/blob/main/docs/rules.md#unnecessary-union-distinct",
+    ),
+    fixable: false,
+};
+
+/// Flags `UNION DISTINCT` whose branches are told apart by a literal column.
+pub struct UnnecessaryUnionDistinct;
+
+impl Rule for UnnecessaryUnionDistinct {
+    fn id(&self) -> &str {
+        RULE_ID
+    }
+
+    fn metadata(&self) -> RuleMetadata<'_> {
+        METADATA
+    }
+
+    fn check_node(
+        &self,
+        node: NodeRef<'_>,
+        cx: &LintContext<'_>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        if node.kind() == "ASTSetOperation"
+            && let Some(diagnostic) = disjoint_union_distinct(node, cx.sql)
+        {
+            diagnostics.push(diagnostic);
+        }
+    }
+}
+
+/// The diagnostic for a `UNION DISTINCT` with a column where every branch
+/// selects a different literal.
+fn disjoint_union_distinct(node: NodeRef<'_>, src: &str) -> Option<Diagnostic> {
+    let operators = set_operators(&node, src);
+    if operators.is_empty() || operators.iter().any(|op| op != "UNION DISTINCT") {
+        return None;
+    }
+    let branches = set_operation_branches(&node)
+        .into_iter()
+        .map(|branch| match branch.kind() {
+            "ASTSelect" => Some(branch),
+            _ => query_select(branch),
+        })
+        .map(|select| select.and_then(|select| select_expressions(&select)))
+        .collect::<Option<Vec<_>>>()?;
+
+    let width = branches.iter().map(Vec::len).min()?;
+    let (position, literals) = (0..width).find_map(|position| {
+        let literals = branches
+            .iter()
+            .map(|columns| columns.get(position).copied())
+            .collect::<Option<Vec<_>>>()?;
+        all_distinct_literals(&literals, src).then_some((position, literals))
+    })?;
+
+    let diagnostic = Diagnostic::for_node(
+        RULE_ID,
+        Severity::Warning,
+        &node,
+        "UNION DISTINCT deduplicates branches that cannot share a row".to_string(),
+    )
+    .with_note(format!(
+        "each branch selects a different literal in column {}",
+        position.saturating_add(1)
+    ))
+    .with_help("use UNION ALL, and SELECT DISTINCT in branches that can repeat rows");
+    Some(literals.iter().fold(diagnostic, |diagnostic, literal| {
+        diagnostic.with_related(Related::for_node(
+            literal,
+            format!(
+                "every row of this branch has {}",
+                get_node_text(literal, src)
+            ),
+        ))
+    }))
+}
+
+/// The expressions of a select's columns, or `None` for `SELECT AS STRUCT`,
+/// whose single column the list does not describe.
+fn select_expressions<'a>(select: &NodeRef<'a>) -> Option<Vec<NodeRef<'a>>> {
+    if has_child_of_kind(select, "ASTSelectAs") {
+        return None;
+    }
+    find_child_of_kind(select, "ASTSelectList")?
+        .named_children()
+        .into_iter()
+        .filter(|column| column.kind() == "ASTSelectColumn")
+        .map(|column| column.named_child(0))
+        .collect()
+}
+
+/// True when every expression is a literal of one kind and no two of them
+/// hold the same value.
+fn all_distinct_literals(exprs: &[NodeRef<'_>], src: &str) -> bool {
+    let Some(kind) = exprs.first().map(NodeRef::kind) else {
+        return false;
+    };
+    let mut values = Vec::with_capacity(exprs.len());
+    for expr in exprs {
+        if expr.kind() != kind {
+            return false;
+        }
+        let Some(value) = literal_value(expr, src) else {
+            return false;
+        };
+        if values.contains(&value) {
+            return false;
+        }
+        values.push(value);
+    }
+    true
+}
+
+/// The value of a string, integer or boolean literal in a form where equal
+/// values compare equal, or `None` for literals whose spelling is ambiguous
+/// (escapes, raw and triple-quoted strings, hex integers) and everything else.
+fn literal_value(expr: &NodeRef<'_>, src: &str) -> Option<String> {
+    let text = get_node_text(expr, src).trim();
+    match expr.kind() {
+        "ASTStringLiteral" => {
+            let quote = text.chars().next().filter(|c| *c == '\'' || *c == '"')?;
+            let inner = text.strip_prefix(quote)?.strip_suffix(quote)?;
+            (!inner.contains(quote) && !inner.contains('\\')).then(|| inner.to_string())
+        }
+        "ASTIntLiteral" => text.parse::<i64>().ok().map(|n| n.to_string()),
+        "ASTBooleanLiteral" => Some(text.to_ascii_lowercase()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::indexing_slicing,
+    clippy::arithmetic_side_effects,
+    reason = "test code"
+)]
+mod tests {
+    use super::*;
+    use crate::rules::helpers::run_rule;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("SELECT 'a' AS src, x FROM t UNION DISTINCT SELECT 'b', x FROM u")]
+    #[case(
+        "SELECT x, 1 FROM t UNION DISTINCT SELECT x, 2 FROM u UNION DISTINCT SELECT x, 3 FROM v"
+    )]
+    #[case("(SELECT x, TRUE FROM t) UNION DISTINCT (SELECT x, FALSE FROM u)")]
+    #[case("SELECT \"a\", x FROM t union distinct SELECT 'b', x FROM u")]
+    fn flags_disjoint_branches(#[case] sql: &str) {
+        assert_eq!(run_rule(&UnnecessaryUnionDistinct, sql).len(), 1, "{sql}");
+    }
+
+    #[rstest]
+    #[case("SELECT 'a', x FROM t UNION ALL SELECT 'b', x FROM u")]
+    #[case("SELECT x FROM t UNION DISTINCT SELECT x FROM u")]
+    #[case("SELECT 'a', x FROM t UNION DISTINCT SELECT 'a', x FROM u")]
+    #[case("SELECT 'a', x FROM t UNION DISTINCT SELECT \"a\", x FROM u")]
+    #[case("SELECT 1, x FROM t UNION DISTINCT SELECT 01, x FROM u")]
+    #[case("SELECT 'a', x FROM t UNION DISTINCT SELECT y, x FROM u")]
+    #[case("SELECT 'a', x FROM t UNION DISTINCT SELECT 1, x FROM u")]
+    #[case("SELECT 'a', x FROM t EXCEPT DISTINCT SELECT 'b', x FROM u")]
+    #[case(
+        "SELECT 'a', x FROM t UNION DISTINCT (SELECT 'b', x FROM u UNION ALL SELECT 'c', x FROM v)"
+    )]
+    fn accepts_overlapping_or_other_operations(#[case] sql: &str) {
+        assert!(run_rule(&UnnecessaryUnionDistinct, sql).is_empty(), "{sql}");
+    }
+
+    #[test]
+    fn points_at_the_operation_and_the_discriminators() {
+        let sql = "SELECT 'a' AS src, x FROM t UNION DISTINCT SELECT 'b', x FROM u";
+        let diagnostics = run_rule(&UnnecessaryUnionDistinct, sql);
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.col(), 1);
+        assert_eq!(
+            diagnostic.notes(),
+            ["each branch selects a different literal in column 1"]
+        );
+        let related = diagnostic.related();
+        assert_eq!(related.len(), 2);
+        assert_eq!(related[1].start, (1, sql.find("'b'").unwrap() + 1));
+    }
+}
@@ -0,0 +1,415 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{Ast, NodeRef};
+
+use crate::diagnostic::{Diagnostic, Related, Severity};
+use crate::rules::helpers::{find_child_of_kind, get_node_text, is_array_item, is_function_name};
+use crate::rules::rule::{Category, LintContext, Rule, RuleMetadata};
+use crate::rules::unused_column_in_cte::cte_column_names;
+use crate::schema::SchemaCatalog;
+
+const RULE_ID: &str = "unqualified_column_reference";
+
+const METADATA: RuleMetadata<'static> = RuleMetadata {
+    name: "Unqualified column in a join",
+    default_severity: Severity::Warning,
+    default_enabled: true,
+    category: Category::Maintainability,
+    summary: "A query reading from several tables refers to a column without saying which table it comes from.",
+    description: "With more than one table in `FROM`, a bare column name leaves readers guessing where it comes from, and the query breaks as soon as another of the tables gains a column with the same name. When the CTEs the query reads, or the tables the `[schema]` configuration declares, already share the name, BigQuery rejects the reference as ambiguous and the rule reports an error. Qualify the column with its table name or alias.",
+    bad_example: Some("SELECT name FROM users AS u JOIN orders AS o ON u.id = o.user_id"),
+    good_example: Some("SELECT u.name FROM users AS u JOIN orders AS o ON u.id = o.user_id"),
+    doc_url: Some(
+        "https://github.com/hirosassa/bqvalid/blob/main/docs/rules.md#unqualified-column-in-a-join",
+    ),
+    fixable: false,
+};
+
+/// Date parts and weekdays, which googlesql parses as column references when
+/// they are arguments (`DATE_TRUNC(d, MONTH)`, `EXTRACT(DAY FROM d)`).
+const DATE_PARTS: [&str; 24] = [
+    "MICROSECOND",
+    "MILLISECOND",
+    "SECOND",
+    "MINUTE",
+    "HOUR",
+    "DAY",
+    "DAYOFWEEK",
+    "DAYOFYEAR",
+    "WEEK",
+    "ISOWEEK",
+    "MONTH",
+    "QUARTER",
+    "YEAR",
+    "ISOYEAR",
+    "DATE",
+    "DATETIME",
+    "TIME",
+    "SUNDAY",
+    "MONDAY",
+    "TUESDAY",
+    "WEDNESDAY",
+    "THURSDAY",
+    "FRIDAY",
+    "SATURDAY",
+];
+
+/// Flags unqualified column references in a `SELECT` with several `FROM`
+/// sources.
+///
+/// Telling an ambiguous reference apart needs the column lists of every CTE,
+/// so this walks the tree itself via [`Rule::check_tree`].
+pub struct UnqualifiedColumnReference;
+
+/// A table, subquery or UNNEST in a `FROM` clause.
+struct Source<'a> {
+    node: NodeRef<'a>,
+    /// The lowercase name its columns are qualified with, if it has one.
+    qualifier: Option<String>,
+    /// The columns of the CTE or declared table it reads, when it reads one.
+    columns: Option<Vec<&'a str>>,
+}
+
+impl Rule for UnqualifiedColumnReference {
+    fn id(&self) -> &str {
+        RULE_ID
+    }
+
+    fn metadata(&self) -> RuleMetadata<'_> {
+        METADATA
+    }
+
+    fn check_tree(&self, ast: &Ast, cx: &LintContext<'_>, diagnostics: &mut Vec<Diagnostic>) {
+        let ctes = cte_column_names(ast, cx.sql);
+        for select in ast
+            .pre_order()
+            .into_iter()
+            .filter(|node| node.kind() == "ASTSelect")
+        {
+            check_select(select, &ctes, cx, diagnostics);
+        }
+    }
+}
+
+fn check_select(
+    select: NodeRef<'_>,
+    ctes: &HashMap<String, Vec<String>>,
+    cx: &LintContext<'_>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let src = cx.sql;
+    let Some(from) = find_child_of_kind(&select, "ASTFromClause") else {
+        return;
+    };
+    // UNNEST and correlated array paths flatten a column of another source
+    // rather than adding a table; only their alias names something.
+    let mut tables: Vec<Source<'_>> = Vec::new();
+    let mut elements: HashSet<String> = HashSet::new();
+    for source in sources(from, ctes, cx.schema, src) {
+        let left: HashSet<String> = tables
+            .iter()
+            .filter_map(|table| table.qualifier.clone())
+            .chain(elements.iter().cloned())
+            .collect();
+        if is_array_item(&source.node, &left, src) {
+            elements.extend(source.qualifier);
+        } else {
+            tables.push(source);
+        }
+    }
+    let sources = tables;
+    if sources.len() < 2 {
+        return;
+    }
+    let mut known = known_names(&select, &from, &sources, src);
+    known.extend(elements);
+
+    // `ORDER BY` belongs to the query around the select rather than to the
+    // select itself, but sorts by the select's columns.
+    let order_by = select
+        .parent()
+        .filter(|query| query.kind() == "ASTQuery")
+        .and_then(|query| find_child_of_kind(&query, "ASTOrderBy"));
+    let scopes = std::iter::once(select).chain(order_by);
+    for column in scopes.flat_map(|scope| {
+        scope
+            .pre_order()
+            .into_iter()
+            .filter(move |node| is_unqualified_column(node, &scope, src))
+    }) {
+        let name = get_node_text(&column, src).trim_matches('`');
+        let lower = name.to_lowercase();
+        if known.contains(&lower) {
+            continue;
+        }
+        diagnostics.push(diagnostic(&column, name, &lower, &sources));
+    }
+}
+
+/// An error naming the sources that have `name` when the known column lists
+/// show more than one, otherwise a warning.
+fn diagnostic(column: &NodeRef<'_>, name: &str, lower: &str, sources: &[Source<'_>]) -> Diagnostic {
+    let owners: Vec<&Source<'_>> = sources
+        .iter()
+        .filter(|source| {
+            source
+                .columns
+                .as_ref()
+                .is_some_and(|columns| columns.contains(&lower))
+        })
+        .collect();
+
+    if owners.len() > 1 {
+        return owners.iter().fold(
+            Diagnostic::for_node(
+                RULE_ID,
+                Severity::Error,
+                column,
+                format!("column `{}` is ambiguous", name),
+            )
+            .with_help("qualify it with the table it should come from"),
+            |diagnostic, owner| {
+                diagnostic.with_related(Related::for_node(
+                    &owner.node,
+                    format!("`{}` is a column of this table", name),
+                ))
+            },
+        );
+    }
+
+    let diagnostic = Diagnostic::for_node(
+        RULE_ID,
+        Severity::Warning,
+        column,
+        format!(
+            "column `{}` is not qualified in a query reading {} tables",
+            name,
+            sources.len()
+        ),
+    );
+    match owners.first().and_then(|owner| owner.qualifier.as_deref()) {
+        Some(qualifier) => diagnostic.with_help(format!("qualify it as `{}.{}`", qualifier, name)),
+        None => diagnostic.with_help("qualify it with the table or alias it comes from"),
+    }
+}
+
+/// The lowercase names a bare identifier may use in `select` without naming a
+/// column of one of the sources: the select list's aliases, the sources' own
+/// names (a whole row) and the columns of `USING`.
+fn known_names(
+    select: &NodeRef<'_>,
+    from: &NodeRef<'_>,
+    sources: &[Source<'_>],
+    src: &str,
+) -> HashSet<String> {
+    let aliases = find_child_of_kind(select, "ASTSelectList")
+        .map(|list| list.named_children())
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|column| find_child_of_kind(&column, "ASTAlias"))
+        .filter_map(|alias| alias.named_children().into_iter().last());
+    let using = from
+        .pre_order()
+        .into_iter()
+        .filter(|node| node.kind() == "ASTUsingClause")
+        .flat_map(|clause| clause.named_children());
+
+    aliases
+        .chain(using)
+        .map(|name| get_node_text(&name, src).trim_matches('`').to_lowercase())
+        .chain(sources.iter().filter_map(|source| source.qualifier.clone()))
+        .collect()
+}
+
+/// The sources of a `FROM` clause, looking through joins. A CTE shadows a
+/// declared table of the same name.
+fn sources<'a>(
+    node: NodeRef<'a>,
+    ctes: &'a HashMap<String, Vec<String>>,
+    schema: &'a SchemaCatalog,
+    src: &str,
+) -> Vec<Source<'a>> {
+    match node.kind() {
+        "ASTTablePathExpression" | "ASTTableSubquery" | "ASTTVF" => {
+            let path = find_child_of_kind(&node, "ASTPathExpression")
+                .map(|path| get_node_text(&path, src).trim_matches('`').to_lowercase());
+            let alias = find_child_of_kind(&node, "ASTAlias")
+                .and_then(|alias| alias.named_children().into_iter().last())
+                .map(|name| get_node_text(&name, src).trim_matches('`').to_lowercase());
+            let implicit = path
+                .as_deref()
+                .filter(|_| node.kind() == "ASTTablePathExpression")
+                .and_then(|path| path.rsplit('.').next())
+                .map(str::to_string);
+            let columns = path.and_then(|path| match ctes.get(&path) {
+                Some(columns) => Some(columns.iter().map(String::as_str).collect()),
+                None => schema
+                    .table(&path)
+                    .filter(|_| node.kind() == "ASTTablePathExpression")
+                    .map(|table| table.column_names().collect()),
+            });
+            vec![Source {
+                node,
+                columns,
+                qualifier: alias.or(implicit),
+            }]
+        }
+        "ASTFromClause" | "ASTJoin" | "ASTParenthesizedJoin" => node
+            .named_children()
+            .into_iter()
+            .flat_map(|child| sources(child, ctes, schema, src))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// True when `node` is a single-identifier column reference evaluated in the
+/// scope of `scope` (the select, or its query's `ORDER BY`): not in a nested
+/// query, not a table, function, type or date part name, and in `FROM` only
+/// inside an `ON` condition.
+fn is_unqualified_column(node: &NodeRef<'_>, scope: &NodeRef<'_>, src: &str) -> bool {
+    if node.kind() != "ASTPathExpression"
+        || node.named_children().len() != 1
+        || is_function_name(node)
+    {
+        return false;
+    }
+    match node.parent().map(|parent| parent.kind()) {
+        Some("ASTSimpleType") => return false,
+        Some("ASTFunctionCall" | "ASTExtractExpression") => {
+            let text = get_node_text(node, src);
+            if DATE_PARTS
+                .iter()
+                .any(|part| part.eq_ignore_ascii_case(text))
+            {
+                return false;
+            }
+        }
+        _ => {}
+    }
+
+    let mut in_on = false;
+    let mut current = node.parent();
+    while let Some(parent) = current {
+        if parent.id() == scope.id() {
+            return true;
+        }
+        match parent.kind() {
+            "ASTSelect" | "ASTLambda" => return false,
+            "ASTOnClause" => in_on = true,
+            "ASTFromClause" if !in_on => return false,
+            _ => {}
+        }
+        current = parent.parent();
+    }
+    false
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::indexing_slicing,
+    clippy::arithmetic_side_effects,
+    reason = "test code"
+)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::rules::helpers::{run_rule, run_rule_with_schema};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("SELECT name FROM users AS u JOIN orders AS o ON u.id = o.user_id")]
+    #[case("SELECT u.name FROM users AS u JOIN orders AS o ON u.id = user_id")]
+    #[case("SELECT u.name FROM users AS u, orders AS o WHERE status = 'paid'")]
+    #[case(
+        "SELECT id, item FROM users u JOIN orders o ON u.id = o.user_id, UNNEST(o.items) AS item"
+    )]
+    #[case(
+        "SELECT u.id FROM users u JOIN orders o ON u.id = o.user_id GROUP BY u.id HAVING SUM(amount) > 0"
+    )]
+    #[case("SELECT u.id FROM users u JOIN orders o ON u.id = o.user_id ORDER BY created_at")]
+    #[case(
+        "SELECT u.id FROM users u LEFT JOIN (SELECT user_id FROM orders) o ON u.id = o.user_id WHERE flag"
+    )]
+    fn flags_one_unqualified_column(#[case] sql: &str) {
+        let diagnostics = run_rule(&UnqualifiedColumnReference, sql);
+        assert_eq!(diagnostics.len(), 1, "{sql}");
+        assert_eq!(diagnostics[0].severity(), Severity::Warning, "{sql}");
+    }
+
+    #[rstest]
+    #[case("SELECT name FROM users")]
+    #[case("SELECT u.name FROM users AS u JOIN orders AS o ON u.id = o.user_id")]
+    #[case("SELECT users.name FROM users JOIN orders ON users.id = orders.user_id")]
+    #[case("SELECT id, u.name FROM users u JOIN orders o USING (id)")]
+    #[case("SELECT u.name AS n FROM users u JOIN orders o ON u.id = o.user_id ORDER BY n")]
+    #[case(
+        "SELECT u.name AS n, COUNT(*) FROM users u JOIN orders o ON u.id = o.user_id GROUP BY n"
+    )]
+    #[case("SELECT CAST(o.total AS INT64) FROM users u JOIN orders o ON u.id = o.user_id")]
+    #[case("SELECT u.id, tag FROM users u, UNNEST(u.tags) AS tag")]
+    #[case("SELECT id, tag FROM users AS u, UNNEST(u.tags) AS tag")]
+    #[case("SELECT id, item FROM users AS u, u.items AS item")]
+    #[case("SELECT id, part FROM users, users.items AS item, item.parts AS part")]
+    #[case("SELECT u FROM users u JOIN orders o ON u.id = o.user_id")]
+    #[case("SELECT DATE_TRUNC(o.created_at, MONTH) FROM users u JOIN orders o ON u.id = o.user_id")]
+    #[case("SELECT EXTRACT(DAY FROM o.created_at) FROM users u JOIN orders o ON u.id = o.user_id")]
+    #[case(
+        "SELECT u.id FROM users u JOIN orders o ON u.id = o.user_id WHERE u.id IN (SELECT id FROM banned)"
+    )]
+    fn accepts_qualified_or_single_source_queries(#[case] sql: &str) {
+        assert!(
+            run_rule(&UnqualifiedColumnReference, sql).is_empty(),
+            "{sql}"
+        );
+    }
+
+    #[test]
+    fn reports_an_error_when_ctes_share_the_name() {
+        let sql = "WITH a AS (SELECT id, x FROM t), b AS (SELECT id, y FROM u) \
+                   SELECT id FROM a JOIN b ON a.id = b.id";
+        let diagnostics = run_rule(&UnqualifiedColumnReference, sql);
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.severity(), Severity::Error);
+        assert_eq!(diagnostic.message(), "column `id` is ambiguous");
+        assert_eq!(diagnostic.related().len(), 2);
+    }
+
+    #[test]
+    fn suggests_the_cte_that_has_the_column() {
+        let sql = "WITH a AS (SELECT id, x FROM t), b AS (SELECT id, y FROM u) \
+                   SELECT x FROM a JOIN b ON a.id = b.id";
+        let diagnostics = run_rule(&UnqualifiedColumnReference, sql);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity(), Severity::Warning);
+        assert_eq!(diagnostics[0].help(), Some("qualify it as `a.x`"));
+    }
+
+    #[test]
+    fn reads_column_lists_from_the_schema() {
+        let config = Config::from_toml(
+            r#"
+[schema."shop.users"]
+id = { type = "INT64" }
+name = { type = "STRING" }
+
+[schema."shop.orders"]
+id = { type = "INT64" }
+user_id = { type = "INT64" }
+"#,
+        )
+        .unwrap();
+        let sql =
+            "SELECT id, name FROM shop.users AS u JOIN `proj.shop.orders` AS o ON u.id = o.user_id";
+        let diagnostics = run_rule_with_schema(&UnqualifiedColumnReference, sql, &config.schema);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].severity(), Severity::Error);
+        assert_eq!(diagnostics[0].message(), "column `id` is ambiguous");
+        assert_eq!(diagnostics[1].severity(), Severity::Warning);
+        assert_eq!(diagnostics[1].help(), Some("qualify it as `u.name`"));
+    }
+}
@@ -6,9 +6,12 @@ mod utils;
 mod visitor;
 mod visitors;
 
+use std::collections::HashMap;
+
 use crate::ast::Ast;
-use crate::diagnostic::{Diagnostic, Severity};
-use crate::rules::rule::Rule;
+use crate::diagnostic::{Diagnostic, Related, Severity};
+use crate::rules::helpers::{get_node_text, table_path};
+use crate::rules::rule::{Category, LintContext, Rule, RuleMetadata};
 
 use context::AnalysisContext;
 use visitor::NodeVisitor;
@@ -18,6 +21,23 @@ use visitors::{
 
 const RULE_ID: &str = "unused_column_in_cte";
 
+const METADATA: RuleMetadata<'static> = RuleMetadata {
+    name: "Contains unused columns in CTE",
+    default_severity: Severity::Warning,
+    default_enabled: true,
+    category: Category::Maintainability,
+    summary: "A column defined in a CTE is never referenced afterwards.",
+    description: "Columns a CTE selects but nothing downstream reads make the query harder to follow and invite questions about where they are used. Remove them, or select them where they are actually needed.",
+    bad_example: Some(
+        "WITH cte1 AS (SELECT col1, col2, unused FROM t) SELECT col1, col2 FROM cte1",
+    ),
+    good_example: Some("WITH cte1 AS (SELECT col1, col2 FROM t) SELECT col1, col2 FROM cte1"),
+    doc_url: Some(
+        "https://github.com/hirosassa/bqvalid/blob/main/docs/rules.md#contains-unused-columns-in-cte",
+    ),
+    fixable: false,
+};
+
 /// Flags columns defined in a CTE but never referenced afterwards.
 ///
 /// This rule needs cross-node analysis (which CTE columns get used anywhere in
@@ -26,12 +46,16 @@ const RULE_ID: &str = "unused_column_in_cte";
 pub struct UnusedColumnInCte;
 
 impl Rule for UnusedColumnInCte {
-    fn id(&self) -> &'static str {
+    fn id(&self) -> &str {
         RULE_ID
     }
 
-    fn check_tree(&self, ast: &Ast, sql: &str, diagnostics: &mut Vec<Diagnostic>) {
-        diagnostics.extend(check(ast, sql));
+    fn metadata(&self) -> RuleMetadata<'_> {
+        METADATA
+    }
+
+    fn check_tree(&self, ast: &Ast, cx: &LintContext<'_>, diagnostics: &mut Vec<Diagnostic>) {
+        diagnostics.extend(check(ast, cx.sql));
     }
 }
 
@@ -70,13 +94,75 @@ pub fn check(ast: &Ast, sql: &str) -> Vec<Diagnostic> {
         .collect_unused()
         .into_iter()
         .map(|col| {
-            Diagnostic::new(
+            let diagnostic = Diagnostic::new(
                 RULE_ID,
                 Severity::Warning,
                 col.row,
                 col.col,
                 format!("Unused column: {}", col.column_name),
             )
+            .with_help("remove it from the CTE's select list");
+            match &col.table_name {
+                Some(cte) => readers(ast, cte, sql).into_iter().fold(
+                    diagnostic.with_note(format!(
+                        "`{}` defines it, but nothing that reads `{}` selects it",
+                        cte, cte
+                    )),
+                    Diagnostic::with_related,
+                ),
+                None => diagnostic,
+            }
+        })
+        .collect()
+}
+
+/// The places that read the CTE `cte`, each named after the CTE or the main
+/// query that contains it.
+fn readers(ast: &Ast, cte: &str, sql: &str) -> Vec<Related> {
+    ast.pre_order()
+        .into_iter()
+        .filter(|node| {
+            table_path(node).is_some_and(|path| {
+                get_node_text(&path, sql)
+                    .trim_matches('`')
+                    .eq_ignore_ascii_case(cte)
+            })
+        })
+        .map(|reference| {
+            let mut current = reference.parent();
+            while let Some(node) = current
+                && node.kind() != "ASTAliasedQuery"
+            {
+                current = node.parent();
+            }
+            let consumer = current.map_or_else(
+                || "the main query".to_string(),
+                |node| format!("`{}`", utils::get_cte_name(&node, sql)),
+            );
+            Related::for_node(&reference, format!("{} reads `{}` here", consumer, cte))
+        })
+        .collect()
+}
+
+/// The columns each CTE of `ast` selects, as [`CteVisitor`] collects them,
+/// keyed by lowercase CTE name with lowercase column names.
+///
+/// A `*` over a table outside the query contributes nothing, so a listed
+/// column is known to exist while an unlisted one still may.
+pub fn cte_column_names(ast: &Ast, sql: &str) -> HashMap<String, Vec<String>> {
+    let mut context = AnalysisContext::new(sql);
+    for node in ast.pre_order() {
+        CteVisitor.visit(node, &mut context);
+    }
+    context
+        .cte_columns
+        .into_iter()
+        .map(|(cte, columns)| {
+            let names = columns
+                .into_iter()
+                .map(|column| column.column_name.to_lowercase())
+                .collect();
+            (cte.to_lowercase(), names)
         })
         .collect()
 }
@@ -856,4 +942,21 @@ from
 
         assert_eq!(found, expected, "unused columns mismatch for: {sql}");
     }
+
+    #[test]
+    fn points_at_the_queries_that_read_the_cte() {
+        let sql = "WITH d1 AS (SELECT k1, orphan FROM t), d2 AS (SELECT k1 FROM d1) \
+                   SELECT d2.k1 FROM d2 JOIN d1 USING (k1)";
+        let diagnostics = run_rule(&UnusedColumnInCte, sql);
+        assert_eq!(diagnostics.len(), 1);
+        let related: Vec<&str> = diagnostics[0]
+            .related()
+            .iter()
+            .map(|related| related.message.as_str())
+            .collect();
+        assert_eq!(
+            related,
+            ["`d2` reads `d1` here", "the main query reads `d1` here"]
+        );
+    }
 }
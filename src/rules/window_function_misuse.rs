@@ -0,0 +1,323 @@
+use crate::ast::NodeRef;
+
+use crate::diagnostic::{Diagnostic, Related, Severity};
+use crate::rules::helpers::{find_child_of_kind, find_parent_select, get_node_text};
+use crate::rules::rule::{Category, LintContext, Rule, RuleMetadata};
+
+const RULE_ID: &str = "window_function_misuse";
+
+const METADATA: RuleMetadata<'static> = RuleMetadata {
+    name: "Window function misuse",
+    default_severity: Severity::Warning,
+    default_enabled: true,
+    category: Category::Correctness,
+    summary: "A window function is missing the ORDER BY or frame it needs, or partitions by a constant.",
+    description: "Numbering and navigation functions depend on the order of the rows in their window. Without `ORDER BY`, `ROW_NUMBER`, `FIRST_VALUE`, `LAST_VALUE` and `NTH_VALUE` return arbitrary rows that can change between runs, and `RANK`, `DENSE_RANK`, `PERCENT_RANK`, `CUME_DIST`, `NTILE`, `LAG` and `LEAD` are rejected by BigQuery. `LAST_VALUE` with `ORDER BY` but no frame reaching `UNBOUNDED FOLLOWING` returns the current row rather than the last one, because the default frame ends at the current row. Partitioning by a constant puts every row in a single partition, which turns a `QUALIFY ROW_NUMBER() OVER (...) = 1` deduplication into keeping one row overall.",
+    bad_example: Some("SELECT * FROM events QUALIFY ROW_NUMBER() OVER (PARTITION BY user_id) = 1"),
+    good_example: Some(
+        "SELECT * FROM events QUALIFY ROW_NUMBER() OVER (PARTITION BY user_id ORDER BY ts DESC) = 1",
+    ),
+    doc_url: Some(
+        "https://github.com/hirosassa/bqvalid/blob/main/docs/rules.md#window-function-misuse",
+    ),
+    fixable: false,
+};
+
+/// Functions BigQuery rejects without a window `ORDER BY`.
+const ORDER_REQUIRED: &[&str] = &[
+    "CUME_DIST",
+    "DENSE_RANK",
+    "LAG",
+    "LEAD",
+    "NTILE",
+    "PERCENT_RANK",
+    "RANK",
+];
+
+/// Functions that accept a window without `ORDER BY` but then pick arbitrary
+/// rows.
+const ORDER_SENSITIVE: &[&str] = &["FIRST_VALUE", "LAST_VALUE", "NTH_VALUE", "ROW_NUMBER"];
+
+/// How many named windows deep a `WINDOW` clause reference is followed.
+const MAX_WINDOW_DEPTH: usize = 8;
+
+/// Flags window functions whose result is arbitrary, wrong or rejected because
+/// of how their window is specified.
+pub struct WindowFunctionMisuse;
+
+impl Rule for WindowFunctionMisuse {
+    fn id(&self) -> &str {
+        RULE_ID
+    }
+
+    fn metadata(&self) -> RuleMetadata<'_> {
+        METADATA
+    }
+
+    fn check_node(
+        &self,
+        node: NodeRef<'_>,
+        cx: &LintContext<'_>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        if node.kind() == "ASTAnalyticFunctionCall" {
+            check_analytic_call(&node, cx.sql, diagnostics);
+        }
+    }
+}
+
+/// The parts of a window that matter here, after following named windows.
+#[derive(Default)]
+struct Window<'a> {
+    partition_by: Option<NodeRef<'a>>,
+    order_by: Option<NodeRef<'a>>,
+    frame: Option<NodeRef<'a>>,
+}
+
+/// googlesql: `ASTAnalyticFunctionCall -> ASTFunctionCall, ASTWindowSpecification`.
+fn check_analytic_call(call: &NodeRef<'_>, src: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let (Some(function), Some(spec)) = (
+        find_child_of_kind(call, "ASTFunctionCall"),
+        find_child_of_kind(call, "ASTWindowSpecification"),
+    ) else {
+        return;
+    };
+    let Some(name_node) = function.child(0) else {
+        return;
+    };
+    let name = get_node_text(&name_node, src).to_uppercase();
+    let Some(window) = resolve_window(&spec, src) else {
+        // A named window we cannot find: nothing is known about its clauses.
+        return;
+    };
+
+    if window.order_by.is_none() {
+        if ORDER_REQUIRED.contains(&name.as_str()) {
+            diagnostics.push(
+                Diagnostic::for_node(
+                    RULE_ID,
+                    Severity::Error,
+                    call,
+                    format!("{} requires ORDER BY in its window", name),
+                )
+                .with_help("add ORDER BY to the OVER clause"),
+            );
+        } else if ORDER_SENSITIVE.contains(&name.as_str()) {
+            diagnostics.push(
+                Diagnostic::for_node(
+                    RULE_ID,
+                    Severity::Warning,
+                    call,
+                    format!(
+                        "{} without ORDER BY in its window picks arbitrary rows",
+                        name
+                    ),
+                )
+                .with_help("add ORDER BY to the OVER clause, with a tie-breaker column"),
+            );
+        }
+    } else if name == "LAST_VALUE" && !frame_reaches_the_end(window.frame.as_ref(), src) {
+        diagnostics.push(
+            Diagnostic::for_node(
+                RULE_ID,
+                Severity::Warning,
+                call,
+                "LAST_VALUE with ORDER BY returns the current row unless the frame reaches the end of the partition".to_string(),
+            )
+            .with_note("the default frame is RANGE BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW")
+            .with_help("add ROWS BETWEEN UNBOUNDED PRECEDING AND UNBOUNDED FOLLOWING, or use FIRST_VALUE with the order reversed"),
+        );
+    }
+
+    if let Some(partition_by) = window.partition_by.as_ref()
+        && is_constant_partition(partition_by)
+    {
+        let diagnostic = Diagnostic::for_node(
+            RULE_ID,
+            Severity::Warning,
+            partition_by,
+            "PARTITION BY a constant puts every row in one partition".to_string(),
+        )
+        .with_related(Related::for_node(call, "in this window"));
+        diagnostics.push(if is_in_qualify(call) {
+            diagnostic.with_note(
+                "a QUALIFY filter over this window deduplicates the whole result, not each key",
+            )
+        } else {
+            diagnostic
+        });
+    }
+}
+
+/// The effective window of `spec`: its own clauses, completed by those of the
+/// named window it builds on. `None` when a named window is not defined in the
+/// enclosing SELECT.
+fn resolve_window<'a>(spec: &NodeRef<'a>, src: &str) -> Option<Window<'a>> {
+    let mut window = Window::default();
+    let mut current = *spec;
+    for _ in 0..MAX_WINDOW_DEPTH {
+        window.partition_by = window
+            .partition_by
+            .or_else(|| find_child_of_kind(&current, "ASTPartitionBy"));
+        window.order_by = window
+            .order_by
+            .or_else(|| find_child_of_kind(&current, "ASTOrderBy"));
+        window.frame = window
+            .frame
+            .or_else(|| find_child_of_kind(&current, "ASTWindowFrame"));
+        // `OVER w` and `OVER (w ...)` name the base window with a bare
+        // identifier.
+        let Some(base) = find_child_of_kind(&current, "ASTIdentifier") else {
+            return Some(window);
+        };
+        current = named_window(spec, get_node_text(&base, src), src)?;
+    }
+    Some(window)
+}
+
+/// The specification of the window called `name` in the `WINDOW` clause of the
+/// SELECT enclosing `spec`.
+fn named_window<'a>(spec: &NodeRef<'a>, name: &str, src: &str) -> Option<NodeRef<'a>> {
+    let select = find_parent_select(spec)?;
+    let clause = find_child_of_kind(&select, "ASTWindowClause")?;
+    clause
+        .named_children()
+        .into_iter()
+        .filter(|definition| definition.kind() == "ASTWindowDefinition")
+        .find(|definition| {
+            definition
+                .child(0)
+                .is_some_and(|id| get_node_text(&id, src).eq_ignore_ascii_case(name))
+        })
+        .and_then(|definition| find_child_of_kind(&definition, "ASTWindowSpecification"))
+}
+
+/// True when the frame ends at `UNBOUNDED FOLLOWING`, the last row of the
+/// partition.
+///
+/// googlesql: `ASTWindowFrame -> ASTWindowFrameExpr(start), [ASTWindowFrameExpr(end)]`;
+/// a frame with a single bound ends at the current row. The bound's kind is not
+/// in the tree, so this reads the end bound's source.
+fn frame_reaches_the_end(frame: Option<&NodeRef<'_>>, src: &str) -> bool {
+    let Some(frame) = frame else {
+        return false;
+    };
+    let bounds: Vec<NodeRef<'_>> = frame
+        .named_children()
+        .into_iter()
+        .filter(|bound| bound.kind() == "ASTWindowFrameExpr")
+        .collect();
+    let [_, end] = bounds.as_slice() else {
+        return false;
+    };
+    let words: Vec<&str> = get_node_text(end, src).split_whitespace().collect();
+    matches!(words.as_slice(), [unbounded, following]
+        if unbounded.eq_ignore_ascii_case("unbounded") && following.eq_ignore_ascii_case("following"))
+}
+
+/// True when every `PARTITION BY` expression is a literal.
+fn is_constant_partition(partition_by: &NodeRef<'_>) -> bool {
+    let keys = partition_by.named_children();
+    !keys.is_empty() && keys.iter().all(|key| key.kind().ends_with("Literal"))
+}
+
+/// True when `node` is part of a `QUALIFY` condition.
+fn is_in_qualify(node: &NodeRef<'_>) -> bool {
+    let mut current = node.parent();
+    while let Some(parent) = current {
+        match parent.kind() {
+            "ASTQualify" => return true,
+            "ASTSelect" => return false,
+            _ => current = parent.parent(),
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::indexing_slicing,
+    clippy::arithmetic_side_effects,
+    reason = "test code"
+)]
+mod tests {
+    use super::*;
+    use crate::rules::helpers::run_rule;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("SELECT ROW_NUMBER() OVER () AS n FROM t", Severity::Warning)]
+    #[case(
+        "SELECT ROW_NUMBER() OVER (PARTITION BY k) AS n FROM t",
+        Severity::Warning
+    )]
+    #[case(
+        "SELECT FIRST_VALUE(x) OVER (PARTITION BY k) AS f FROM t",
+        Severity::Warning
+    )]
+    #[case("SELECT RANK() OVER (PARTITION BY k) AS r FROM t", Severity::Error)]
+    #[case("SELECT LAG(x) OVER (PARTITION BY k) AS prev FROM t", Severity::Error)]
+    #[case("SELECT lead(x, 2) OVER () AS next FROM t", Severity::Error)]
+    #[case(
+        "SELECT ROW_NUMBER() OVER w AS n FROM t WINDOW w AS (PARTITION BY k)",
+        Severity::Warning
+    )]
+    fn flags_windows_without_order_by(#[case] sql: &str, #[case] severity: Severity) {
+        let diagnostics = run_rule(&WindowFunctionMisuse, sql);
+        assert_eq!(diagnostics.len(), 1, "{sql}");
+        assert_eq!(diagnostics[0].severity(), severity, "{sql}");
+    }
+
+    #[rstest]
+    #[case("SELECT ROW_NUMBER() OVER (PARTITION BY k ORDER BY ts) AS n FROM t")]
+    #[case("SELECT RANK() OVER (ORDER BY score DESC) AS r FROM t")]
+    #[case("SELECT LAG(x) OVER (PARTITION BY k ORDER BY ts) AS prev FROM t")]
+    #[case("SELECT ROW_NUMBER() OVER w AS n FROM t WINDOW w AS (PARTITION BY k ORDER BY ts)")]
+    #[case("SELECT ROW_NUMBER() OVER (w ORDER BY ts) AS n FROM t WINDOW w AS (PARTITION BY k)")]
+    #[case("SELECT SUM(x) OVER (PARTITION BY k) AS total FROM t")]
+    #[case("SELECT COUNT(*) OVER () AS total FROM t")]
+    #[case(
+        "SELECT LAST_VALUE(x) OVER (PARTITION BY k ORDER BY ts ROWS BETWEEN UNBOUNDED PRECEDING AND UNBOUNDED FOLLOWING) AS l FROM t"
+    )]
+    #[case("SELECT ROW_NUMBER() OVER w AS n FROM t WINDOW other AS (ORDER BY ts)")]
+    fn accepts_well_specified_windows(#[case] sql: &str) {
+        assert!(run_rule(&WindowFunctionMisuse, sql).is_empty(), "{sql}");
+    }
+
+    #[rstest]
+    #[case("SELECT LAST_VALUE(x) OVER (PARTITION BY k ORDER BY ts) AS l FROM t")]
+    #[case(
+        "SELECT LAST_VALUE(x) OVER (ORDER BY ts ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW) AS l FROM t"
+    )]
+    #[case(
+        "SELECT LAST_VALUE(x) OVER (ORDER BY ts ROWS BETWEEN UNBOUNDED PRECEDING AND 1 FOLLOWING) AS l FROM t"
+    )]
+    #[case(
+        "SELECT LAST_VALUE(x) OVER (ORDER BY ts ROWS BETWEEN CURRENT ROW AND 2 FOLLOWING) AS l FROM t"
+    )]
+    #[case("SELECT LAST_VALUE(x) OVER (ORDER BY ts ROWS UNBOUNDED PRECEDING) AS l FROM t")]
+    fn flags_last_value_with_the_default_frame(#[case] sql: &str) {
+        let diagnostics = run_rule(&WindowFunctionMisuse, sql);
+        assert_eq!(diagnostics.len(), 1, "{sql}");
+        assert!(diagnostics[0].message().starts_with("LAST_VALUE"));
+    }
+
+    #[test]
+    fn flags_a_constant_partition_key_in_a_qualify_dedup() {
+        let sql = "SELECT * FROM t QUALIFY ROW_NUMBER() OVER (PARTITION BY 'all' ORDER BY ts) = 1";
+        let diagnostics = run_rule(&WindowFunctionMisuse, sql);
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.col(), sql.find("PARTITION").unwrap() + 1);
+        assert_eq!(diagnostic.related().len(), 1);
+        assert_eq!(diagnostic.notes().len(), 1);
+
+        let outside_qualify = "SELECT SUM(x) OVER (PARTITION BY 1) AS total FROM t";
+        let diagnostics = run_rule(&WindowFunctionMisuse, outside_qualify);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].notes().is_empty());
+    }
+}
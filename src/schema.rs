@@ -0,0 +1,175 @@
+//! A catalog of table schemas.
+//!
+//! The parse tree says nothing about the tables a query reads: which columns
+//! they have, of what type, and whether they can be NULL. [`SchemaCatalog`]
+//! holds what the `[schema]` section of the configuration declares about them,
+//! so rules can ask about a table or a column by name. Declarations may be
+//! partial; a column the catalog does not list is simply unknown.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::SqlType;
+
+/// Whether a column can be NULL, as BigQuery's column mode says.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ColumnMode {
+    #[default]
+    #[serde(alias = "nullable")]
+    Nullable,
+    #[serde(alias = "required")]
+    Required,
+    #[serde(alias = "repeated")]
+    Repeated,
+}
+
+/// A declared column: `{ type = "TIMESTAMP", mode = "REQUIRED" }`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Column {
+    #[serde(rename = "type")]
+    pub ty: SqlType,
+    /// `NULLABLE` when not given, as in BigQuery.
+    #[serde(default)]
+    pub mode: ColumnMode,
+}
+
+/// The `[schema]` configuration: table names, e.g. `analytics.events`, mapped
+/// to their columns by name.
+pub type SchemaConfig = BTreeMap<String, BTreeMap<String, Column>>;
+
+/// The declared columns of one table.
+#[derive(Debug, Default, Clone)]
+pub struct Table {
+    /// Keyed by lowercase name.
+    columns: BTreeMap<String, Column>,
+}
+
+impl Table {
+    /// The column `name`, ignoring case and backticks.
+    #[must_use]
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.get(&normalize(name))
+    }
+
+    /// The lowercase names of the declared columns.
+    pub fn column_names(&self) -> impl Iterator<Item = &str> {
+        self.columns.keys().map(String::as_str)
+    }
+}
+
+/// The declared tables, looked up by name.
+#[derive(Debug, Default, Clone)]
+pub struct SchemaCatalog {
+    /// Keyed by normalized name: lowercase, without backticks.
+    tables: HashMap<String, Table>,
+}
+
+impl SchemaCatalog {
+    /// The catalog the `[schema]` configuration declares.
+    #[must_use]
+    pub fn new(config: &SchemaConfig) -> Self {
+        let tables = config
+            .iter()
+            .map(|(name, columns)| {
+                let columns = columns
+                    .iter()
+                    .map(|(column, declared)| (normalize(column), *declared))
+                    .collect();
+                (normalize(name), Table { columns })
+            })
+            .collect();
+        Self { tables }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// The table a path in a query refers to. Names compare ignoring case and
+    /// backticks, and a path matches a declaration that differs only by
+    /// leading qualifiers (`proj.analytics.events` and `analytics.events`);
+    /// the longest such declaration wins.
+    #[must_use]
+    pub fn table(&self, path: &str) -> Option<&Table> {
+        let path = normalize(path);
+        self.tables.get(&path).or_else(|| {
+            self.tables
+                .iter()
+                .filter(|(declared, _)| {
+                    path.strip_suffix(declared.as_str())
+                        .is_some_and(|prefix| prefix.ends_with('.'))
+                })
+                .max_by_key(|(declared, _)| declared.len())
+                .map(|(_, table)| table)
+        })
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.trim().replace('`', "").to_lowercase()
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, reason = "test code")]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn catalog() -> SchemaCatalog {
+        let config = Config::from_toml(
+            r#"
+[schema."analytics.events"]
+event_id = { type = "STRING", mode = "REQUIRED" }
+Created_At = { type = "TIMESTAMP" }
+
+[schema.users]
+created_at = { type = "TIMESTAMP" }
+updated_at = { type = "DATETIME" }
+
+[schema.orders]
+updated_at = { type = "DATE", mode = "nullable" }
+"#,
+        )
+        .unwrap();
+        SchemaCatalog::new(&config.schema)
+    }
+
+    #[test]
+    fn finds_tables_by_qualified_suffix() {
+        let catalog = catalog();
+        let events = catalog.table("`Project.analytics.events`").unwrap();
+        assert_eq!(
+            events.column("event_id").map(|c| c.mode),
+            Some(ColumnMode::Required)
+        );
+        assert_eq!(
+            events.column_names().collect::<Vec<_>>(),
+            ["created_at", "event_id"]
+        );
+        assert!(catalog.table("events").is_none());
+        assert!(catalog.table("other_analytics.events").is_none());
+    }
+
+    #[test]
+    fn prefers_the_longest_matching_declaration() {
+        let config = Config::from_toml(
+            r#"
+[schema.events]
+id = { type = "INT64" }
+
+[schema."analytics.events"]
+id = { type = "STRING" }
+"#,
+        )
+        .unwrap();
+        let catalog = SchemaCatalog::new(&config.schema);
+        let id = |path| catalog.table(path).unwrap().column("id").unwrap().ty;
+        assert_eq!(id("proj.analytics.events"), SqlType::String);
+        assert_eq!(id("proj.other.events"), SqlType::Int64);
+        assert_eq!(id("events"), SqlType::Int64);
+    }
+}
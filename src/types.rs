@@ -0,0 +1,398 @@
+//! A lightweight inferencer for the types of scalar expressions.
+//!
+//! The parse tree carries no types, so this only knows what the source itself
+//! says: literals, casts, the return types of built-in functions, and the
+//! column types the [`SchemaCatalog`] declares. Every other expression is
+//! `None`, "unknown", and rules relying on it stay quiet about it.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast::NodeRef;
+use crate::rules::helpers::{
+    find_child_of_kind, find_parent_select, get_node_text, qualifiers, table_path,
+};
+use crate::schema::SchemaCatalog;
+
+/// A GoogleSQL scalar type, as far as the inferencer tells them apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum SqlType {
+    Bool,
+    Bytes,
+    Date,
+    Datetime,
+    Float64,
+    Int64,
+    Interval,
+    Numeric,
+    String,
+    Time,
+    Timestamp,
+}
+
+impl SqlType {
+    /// The type a type name denotes, ignoring case; `INTEGER`, `FLOAT` and the
+    /// other aliases BigQuery accepts count too.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        let ty = match name.trim().to_ascii_uppercase().as_str() {
+            "BOOL" | "BOOLEAN" => Self::Bool,
+            "BYTES" => Self::Bytes,
+            "DATE" => Self::Date,
+            "DATETIME" => Self::Datetime,
+            "FLOAT64" | "FLOAT" => Self::Float64,
+            "INT64" | "INT" | "INTEGER" | "SMALLINT" | "BIGINT" | "TINYINT" | "BYTEINT" => {
+                Self::Int64
+            }
+            "INTERVAL" => Self::Interval,
+            "NUMERIC" | "DECIMAL" | "BIGNUMERIC" | "BIGDECIMAL" => Self::Numeric,
+            "STRING" => Self::String,
+            "TIME" => Self::Time,
+            "TIMESTAMP" => Self::Timestamp,
+            _ => return None,
+        };
+        Some(ty)
+    }
+
+    /// The type's name as GoogleSQL spells it.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Bool => "BOOL",
+            Self::Bytes => "BYTES",
+            Self::Date => "DATE",
+            Self::Datetime => "DATETIME",
+            Self::Float64 => "FLOAT64",
+            Self::Int64 => "INT64",
+            Self::Interval => "INTERVAL",
+            Self::Numeric => "NUMERIC",
+            Self::String => "STRING",
+            Self::Time => "TIME",
+            Self::Timestamp => "TIMESTAMP",
+        }
+    }
+
+    /// Whether this is one of the date and time types.
+    #[must_use]
+    pub const fn is_temporal(self) -> bool {
+        matches!(
+            self,
+            Self::Date | Self::Datetime | Self::Time | Self::Timestamp
+        )
+    }
+}
+
+impl TryFrom<String> for SqlType {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        Self::from_name(&name).ok_or_else(|| format!("unknown type `{}`", name))
+    }
+}
+
+impl From<SqlType> for String {
+    fn from(ty: SqlType) -> Self {
+        ty.name().to_string()
+    }
+}
+
+impl fmt::Display for SqlType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Built-in functions by return type. Functions whose type depends on their
+/// arguments are in [`FIRST_ARGUMENT_TYPED`] instead.
+const FUNCTION_TYPES: &[(&str, SqlType)] = &[
+    ("CURRENT_DATE", SqlType::Date),
+    ("DATE", SqlType::Date),
+    ("DATE_ADD", SqlType::Date),
+    ("DATE_SUB", SqlType::Date),
+    ("DATE_FROM_UNIX_DATE", SqlType::Date),
+    ("LAST_DAY", SqlType::Date),
+    ("PARSE_DATE", SqlType::Date),
+    ("CURRENT_DATETIME", SqlType::Datetime),
+    ("DATETIME", SqlType::Datetime),
+    ("DATETIME_ADD", SqlType::Datetime),
+    ("DATETIME_SUB", SqlType::Datetime),
+    ("DATETIME_TRUNC", SqlType::Datetime),
+    ("PARSE_DATETIME", SqlType::Datetime),
+    ("CURRENT_TIME", SqlType::Time),
+    ("TIME", SqlType::Time),
+    ("TIME_ADD", SqlType::Time),
+    ("TIME_SUB", SqlType::Time),
+    ("TIME_TRUNC", SqlType::Time),
+    ("PARSE_TIME", SqlType::Time),
+    ("CURRENT_TIMESTAMP", SqlType::Timestamp),
+    ("TIMESTAMP", SqlType::Timestamp),
+    ("TIMESTAMP_ADD", SqlType::Timestamp),
+    ("TIMESTAMP_SUB", SqlType::Timestamp),
+    ("TIMESTAMP_TRUNC", SqlType::Timestamp),
+    ("TIMESTAMP_SECONDS", SqlType::Timestamp),
+    ("TIMESTAMP_MILLIS", SqlType::Timestamp),
+    ("TIMESTAMP_MICROS", SqlType::Timestamp),
+    ("PARSE_TIMESTAMP", SqlType::Timestamp),
+    ("FORMAT_DATE", SqlType::String),
+    ("FORMAT_DATETIME", SqlType::String),
+    ("FORMAT_TIME", SqlType::String),
+    ("FORMAT_TIMESTAMP", SqlType::String),
+    ("DATE_DIFF", SqlType::Int64),
+    ("DATETIME_DIFF", SqlType::Int64),
+    ("TIME_DIFF", SqlType::Int64),
+    ("TIMESTAMP_DIFF", SqlType::Int64),
+    ("UNIX_DATE", SqlType::Int64),
+    ("UNIX_SECONDS", SqlType::Int64),
+    ("UNIX_MILLIS", SqlType::Int64),
+    ("UNIX_MICROS", SqlType::Int64),
+];
+
+/// Built-in functions that return the type of their first typed argument:
+/// `DATE_TRUNC` takes any of the date and time types, the rest any type.
+const FIRST_ARGUMENT_TYPED: &[&str] = &["COALESCE", "DATE_TRUNC", "GREATEST", "IFNULL", "LEAST"];
+
+/// Pseudo columns of ingestion-time partitioned tables.
+const PSEUDO_COLUMNS: &[(&str, SqlType)] = &[
+    ("_PARTITIONDATE", SqlType::Date),
+    ("_PARTITIONTIME", SqlType::Timestamp),
+];
+
+/// The type of `expr`, or `None` when the source does not say. A column
+/// reference is looked up in `schema` through the tables its select reads; see
+/// [`column_type`].
+#[must_use]
+pub fn infer_type(expr: &NodeRef<'_>, sql: &str, schema: &SchemaCatalog) -> Option<SqlType> {
+    match expr.kind() {
+        "ASTBooleanLiteral" => Some(SqlType::Bool),
+        "ASTBytesLiteral" => Some(SqlType::Bytes),
+        "ASTFloatLiteral" => Some(SqlType::Float64),
+        "ASTIntLiteral" => Some(SqlType::Int64),
+        "ASTNumericLiteral" | "ASTBigNumericLiteral" => Some(SqlType::Numeric),
+        "ASTStringLiteral" => Some(SqlType::String),
+        "ASTIntervalExpr" => Some(SqlType::Interval),
+        // `DATE '2024-01-01'`: the type keyword leads the literal's text.
+        "ASTDateOrTimeLiteral" => get_node_text(expr, sql)
+            .split_whitespace()
+            .next()
+            .and_then(SqlType::from_name),
+        // `CAST(x AS DATE)` and `SAFE_CAST(x AS DATE)`.
+        "ASTCastExpression" => find_child_of_kind(expr, "ASTSimpleType")
+            .and_then(|ty| SqlType::from_name(get_node_text(&ty, sql))),
+        "ASTFunctionCall" => function_type(expr, sql, schema),
+        "ASTPathExpression" => {
+            let path = get_node_text(expr, sql);
+            let name = path.rsplit('.').next().unwrap_or(path).trim_matches('`');
+            PSEUDO_COLUMNS
+                .iter()
+                // `CURRENT_DATE` and friends without parentheses.
+                .chain(
+                    FUNCTION_TYPES
+                        .iter()
+                        .filter(|(f, _)| f.starts_with("CURRENT_")),
+                )
+                .filter(|_| !path.contains('.'))
+                .find(|(known, _)| known.eq_ignore_ascii_case(name))
+                .map(|&(_, ty)| ty)
+                .or_else(|| column_type(expr, sql, schema))
+        }
+        _ => None,
+    }
+}
+
+/// The declared type of the column `path` references. The tables the
+/// enclosing select reads are searched, then those of the selects around it
+/// for a correlated reference. A qualified `alias.column` belongs to the table
+/// with that alias (or that name); a bare column is only typed when the
+/// declared tables the select reads with such a column agree on its type.
+fn column_type(path: &NodeRef<'_>, sql: &str, schema: &SchemaCatalog) -> Option<SqlType> {
+    let text = get_node_text(path, sql).to_lowercase().replace('`', "");
+    let mut segments = text.rsplit('.');
+    let name = segments.next()?;
+    let qualifier = segments.next();
+    let mut select = find_parent_select(path);
+    while let Some(current) = select {
+        let tables = find_child_of_kind(&current, "ASTFromClause")
+            .map(|from| read_tables(&from, sql))
+            .unwrap_or_default();
+        if let Some(qualifier) = qualifier {
+            if let Some((_, table)) = tables.iter().find(|(alias, _)| alias == qualifier) {
+                return schema.table(table)?.column(name).map(|column| column.ty);
+            }
+        } else {
+            let mut types = tables
+                .iter()
+                .filter_map(|(_, table)| schema.table(table)?.column(name))
+                .map(|column| column.ty);
+            if let Some(first) = types.next() {
+                return types.all(|ty| ty == first).then_some(first);
+            }
+        }
+        select = find_parent_select(&current);
+    }
+    None
+}
+
+/// The tables `item` reads, as `(qualifier, path)` pairs: the lowercase alias
+/// (or last part of the path) columns are qualified with, and the table's
+/// path. Subqueries, `UNNEST` and table functions read no declared table.
+fn read_tables(item: &NodeRef<'_>, sql: &str) -> Vec<(String, String)> {
+    match item.kind() {
+        "ASTFromClause" | "ASTJoin" | "ASTParenthesizedJoin" => item
+            .named_children()
+            .iter()
+            .flat_map(|child| read_tables(child, sql))
+            .collect(),
+        _ => table_path(item)
+            .map(|path| get_node_text(&path, sql).to_string())
+            .and_then(|path| {
+                let qualifier = qualifiers(item, sql).into_iter().next()?;
+                Some((qualifier, path))
+            })
+            .into_iter()
+            .collect(),
+    }
+}
+
+/// The return type of a call to a built-in function, `None` for unknown and
+/// user-defined functions.
+fn function_type(call: &NodeRef<'_>, sql: &str, schema: &SchemaCatalog) -> Option<SqlType> {
+    let name = call
+        .child(0)
+        .filter(|name| name.kind() == "ASTPathExpression")
+        .map(|name| get_node_text(&name, sql))?;
+    let name = match name.split_once('.') {
+        Some((prefix, rest)) if prefix.eq_ignore_ascii_case("safe") => rest,
+        Some(_) => return None,
+        None => name,
+    };
+    if FIRST_ARGUMENT_TYPED
+        .iter()
+        .any(|f| f.eq_ignore_ascii_case(name))
+    {
+        return call
+            .named_children()
+            .iter()
+            .skip(1)
+            .find_map(|arg| infer_type(arg, sql, schema));
+    }
+    FUNCTION_TYPES
+        .iter()
+        .find(|(f, _)| f.eq_ignore_ascii_case(name))
+        .map(|&(_, ty)| ty)
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::indexing_slicing,
+    clippy::arithmetic_side_effects,
+    reason = "test code"
+)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::rules::helpers::parse_sql;
+    use rstest::rstest;
+
+    /// The inferred type of the only select column of `SELECT <expr> FROM t`.
+    fn type_of(expr: &str) -> Option<SqlType> {
+        let sql = format!("SELECT {} FROM t", expr);
+        let ast = parse_sql(&sql);
+        let Ok(config) = Config::from_toml("[schema.t]\ncreated_at = { type = \"TIMESTAMP\" }")
+        else {
+            panic!("invalid schema");
+        };
+        let schema = SchemaCatalog::new(&config.schema);
+        let Some(column) = ast
+            .pre_order()
+            .into_iter()
+            .find(|node| node.kind() == "ASTSelectColumn")
+        else {
+            panic!("no select column in {sql}");
+        };
+        let Some(expr) = column.named_child(0) else {
+            panic!("empty select column in {sql}");
+        };
+        infer_type(&expr, &sql, &schema)
+    }
+
+    #[rstest]
+    #[case("1", Some(SqlType::Int64))]
+    #[case("'2024-01-01'", Some(SqlType::String))]
+    #[case("DATE '2024-01-01'", Some(SqlType::Date))]
+    #[case("timestamp '2024-01-01 00:00:00'", Some(SqlType::Timestamp))]
+    #[case("CAST(x AS DATETIME)", Some(SqlType::Datetime))]
+    #[case("SAFE_CAST(x AS int64)", Some(SqlType::Int64))]
+    #[case("CURRENT_DATE()", Some(SqlType::Date))]
+    #[case("CURRENT_TIMESTAMP", Some(SqlType::Timestamp))]
+    #[case("SAFE.PARSE_DATE('%Y%m%d', x)", Some(SqlType::Date))]
+    #[case("DATE_TRUNC(created_at, DAY)", Some(SqlType::Timestamp))]
+    #[case("DATE_TRUNC(x, MONTH)", None)]
+    #[case("COALESCE(x, DATE '2024-01-01')", Some(SqlType::Date))]
+    #[case("t.created_at", Some(SqlType::Timestamp))]
+    #[case("created_at", Some(SqlType::Timestamp))]
+    #[case("u.created_at", None)]
+    #[case("_PARTITIONTIME", Some(SqlType::Timestamp))]
+    #[case("x", None)]
+    #[case("my_udf(x)", None)]
+    fn infers_types_the_source_states(#[case] expr: &str, #[case] expected: Option<SqlType>) {
+        assert_eq!(type_of(expr), expected, "{expr}");
+    }
+
+    #[rstest]
+    #[case("SELECT created_at FROM t", Some(SqlType::Timestamp))]
+    #[case("SELECT created_at FROM other", None)]
+    #[case(
+        "SELECT e.created_at FROM t AS e JOIN u ON TRUE",
+        Some(SqlType::Timestamp)
+    )]
+    #[case("SELECT u.created_at FROM t AS e JOIN u ON TRUE", None)]
+    #[case("SELECT created_at FROM t JOIN u ON TRUE", Some(SqlType::Timestamp))]
+    #[case("SELECT created_at FROM t JOIN u2 ON TRUE", None)]
+    #[case("SELECT (SELECT created_at FROM other) FROM t", None)]
+    #[case(
+        "SELECT (SELECT t.created_at FROM other) FROM t",
+        Some(SqlType::Timestamp)
+    )]
+    fn types_columns_through_the_tables_read(#[case] sql: &str, #[case] expected: Option<SqlType>) {
+        let ast = parse_sql(sql);
+        let Ok(config) = Config::from_toml(
+            r#"
+[schema.t]
+created_at = { type = "TIMESTAMP" }
+
+[schema.u]
+id = { type = "INT64" }
+
+[schema.u2]
+created_at = { type = "DATE" }
+"#,
+        ) else {
+            panic!("invalid schema");
+        };
+        let schema = SchemaCatalog::new(&config.schema);
+        let Some(column) = ast
+            .pre_order()
+            .into_iter()
+            .filter(|node| node.kind() == "ASTPathExpression")
+            .find(|node| get_node_text(node, sql).ends_with("created_at"))
+        else {
+            panic!("no created_at in {sql}");
+        };
+        assert_eq!(infer_type(&column, sql, &schema), expected, "{sql}");
+    }
+
+    #[test]
+    fn reads_type_names_and_their_aliases() {
+        assert_eq!(SqlType::from_name("integer"), Some(SqlType::Int64));
+        assert_eq!(SqlType::from_name(" Timestamp "), Some(SqlType::Timestamp));
+        assert_eq!(SqlType::from_name("STRUCT"), None);
+        assert_eq!(SqlType::Datetime.to_string(), "DATETIME");
+        assert!(SqlType::Time.is_temporal());
+        assert!(!SqlType::String.is_temporal());
+    }
+}
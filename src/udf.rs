@@ -0,0 +1,291 @@
+//! A catalog of user-defined functions.
+//!
+//! BigQuery only knows a call is an aggregate, non-deterministic or backed by
+//! JavaScript once it sees the function's `CREATE FUNCTION` statement, and the
+//! rules otherwise treat every unknown function as a plain deterministic
+//! scalar. [`UdfCatalog`] collects those statements — from the linted script
+//! itself and from a configured directory of shared definitions — so rules can
+//! ask about a callee by name.
+//!
+//! ZetaSQL keeps the `TEMP`/`AGGREGATE` modifiers and the determinism level as
+//! node fields the arena does not mirror, so they are read from the statement's
+//! source text around the `ASTFunctionDeclaration`.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::ast::{Ast, NodeRef};
+use crate::rules::helpers::find_child_of_kind;
+
+/// One function declared with `CREATE [TEMP] [AGGREGATE] FUNCTION`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Udf {
+    /// The name as written in the declaration, e.g. `my_dataset.my_agg`.
+    pub name: String,
+    /// Declared with `CREATE AGGREGATE FUNCTION`.
+    pub aggregate: bool,
+    /// Declared with `TEMP`/`TEMPORARY`, i.e. scoped to the script.
+    pub temporary: bool,
+    /// `Some(true)` for `DETERMINISTIC`, `Some(false)` for `NOT
+    /// DETERMINISTIC`, `None` when the declaration does not say.
+    pub deterministic: Option<bool>,
+    /// The lowercase `LANGUAGE` name (`js` for JavaScript), or `None` for a
+    /// SQL function.
+    pub language: Option<String>,
+}
+
+impl Udf {
+    /// Whether the function body is JavaScript (`LANGUAGE js`).
+    #[must_use]
+    pub fn is_javascript(&self) -> bool {
+        self.language.as_deref() == Some("js")
+    }
+}
+
+/// User-defined functions by normalized name (backticks and whitespace
+/// dropped, uppercased).
+#[derive(Debug, Default, Clone)]
+pub struct UdfCatalog {
+    functions: HashMap<String, Udf>,
+}
+
+impl UdfCatalog {
+    /// The functions declared by the statements of one script.
+    #[must_use]
+    pub fn from_script(asts: &[Ast], sql: &str) -> Self {
+        let mut catalog = Self::default();
+        for ast in asts {
+            catalog.add_statement(ast, sql);
+        }
+        catalog
+    }
+
+    /// Record the function `ast` declares, if it is a `CREATE FUNCTION`
+    /// statement. A later declaration of the same name replaces the earlier
+    /// one, like `CREATE OR REPLACE` does.
+    pub fn add_statement(&mut self, ast: &Ast, sql: &str) {
+        if let Some(udf) = declared_function(&ast.root(), sql) {
+            self.functions.insert(normalize(&udf.name), udf);
+        }
+    }
+
+    /// Add every function from `other`, replacing same-named entries.
+    pub fn extend(&mut self, other: Self) {
+        self.functions.extend(other.functions);
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.functions.len()
+    }
+
+    /// The functions in the catalog, in no particular order.
+    pub fn functions(&self) -> impl Iterator<Item = &Udf> {
+        self.functions.values()
+    }
+
+    /// The function a call to `name` resolves to. Names compare ignoring case
+    /// and backticks, and a call matches a declaration it only adds leading
+    /// qualifiers to (`proj.ds.f` calls `ds.f`, but `f` does not); the longest
+    /// such declaration wins.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Udf> {
+        let name = normalize(name);
+        self.functions.get(&name).or_else(|| {
+            self.functions
+                .iter()
+                .filter(|(declared, _)| qualifies(&name, declared))
+                .max_by_key(|(declared, _)| declared.len())
+                .map(|(_, udf)| udf)
+        })
+    }
+
+    /// Whether `name` is a user-defined aggregate function.
+    #[must_use]
+    pub fn is_aggregate(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|udf| udf.aggregate)
+    }
+
+    /// The declared determinism of `name`, `None` when it is unknown or the
+    /// declaration does not say.
+    #[must_use]
+    pub fn is_deterministic(&self, name: &str) -> Option<bool> {
+        self.get(name).and_then(|udf| udf.deterministic)
+    }
+
+    /// Whether `name` is a JavaScript UDF.
+    #[must_use]
+    pub fn is_javascript(&self, name: &str) -> bool {
+        self.get(name).is_some_and(Udf::is_javascript)
+    }
+}
+
+/// The [`Udf`] a `CREATE FUNCTION` statement declares, or `None` for any other
+/// statement.
+fn declared_function(statement: &NodeRef<'_>, sql: &str) -> Option<Udf> {
+    if statement.kind() != "ASTCreateFunctionStatement" {
+        return None;
+    }
+    let declaration = find_child_of_kind(statement, "ASTFunctionDeclaration")?;
+    let name = find_child_of_kind(&declaration, "ASTPathExpression")?.text(sql)?;
+
+    // `CREATE [OR REPLACE] [TEMP] [AGGREGATE] FUNCTION [IF NOT EXISTS]` precedes
+    // the declaration; only the words after the last CREATE belong to it.
+    let prefix: Vec<String> = words(sql.get(..declaration.start_byte())?);
+    let create = prefix.iter().rposition(|word| word == "CREATE")?;
+    let modifiers = prefix.get(create..).unwrap_or_default();
+    let aggregate = modifiers.iter().any(|word| word == "AGGREGATE");
+    let temporary = modifiers
+        .iter()
+        .any(|word| word == "TEMP" || word == "TEMPORARY");
+
+    // `RETURNS type [NOT] DETERMINISTIC` sits between the declaration and the
+    // language, options or body, whichever comes first.
+    let tail_end = statement
+        .children()
+        .into_iter()
+        .filter(|child| child.start_byte() >= declaration.end_byte())
+        .filter(|child| !child.kind().ends_with("Type"))
+        .map(|child| child.start_byte())
+        .min()
+        .unwrap_or_else(|| statement.end_byte());
+    let tail = words(
+        sql.get(declaration.end_byte()..tail_end)
+            .unwrap_or_default(),
+    );
+    let deterministic = tail
+        .iter()
+        .position(|word| word == "DETERMINISTIC")
+        .map(|i| {
+            i.checked_sub(1)
+                .and_then(|prev| tail.get(prev))
+                .is_none_or(|prev| prev != "NOT")
+        });
+
+    // The language is the statement's own identifier child (`LANGUAGE js`).
+    let language = find_child_of_kind(statement, "ASTIdentifier")
+        .and_then(|ident| ident.text(sql))
+        .map(str::to_ascii_lowercase);
+
+    Some(Udf {
+        name: name.to_string(),
+        aggregate,
+        temporary,
+        deterministic,
+        language,
+    })
+}
+
+/// The uppercased identifier-like words of `text`.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_uppercase)
+        .collect()
+}
+
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| *c != '`' && !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase()
+}
+
+/// True when the normalized name `call` is `declared` with extra leading
+/// qualifiers.
+fn qualifies(call: &str, declared: &str) -> bool {
+    call.strip_suffix(declared)
+        .is_some_and(|head| head.ends_with('.'))
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::indexing_slicing,
+    clippy::arithmetic_side_effects,
+    reason = "test code"
+)]
+mod tests {
+    use super::*;
+
+    fn catalog(script: &str) -> UdfCatalog {
+        let mut module = crate::build_module().expect("googlesql module builds");
+        let parsed = module.parse_statements(script).expect("parses");
+        let asts: Vec<Ast> = parsed
+            .statements()
+            .iter()
+            .map(|statement| Ast::from_googlesql_root(statement.root(), script))
+            .collect();
+        UdfCatalog::from_script(&asts, script)
+    }
+
+    #[test]
+    fn reads_aggregate_and_temp_modifiers() {
+        let udfs = catalog(
+            "CREATE TEMP FUNCTION add_one(x INT64) AS (x + 1);\n\
+             CREATE AGGREGATE FUNCTION ds.my_agg(x INT64) AS (SUM(x));",
+        );
+        assert_eq!(udfs.len(), 2);
+
+        let add_one = udfs.get("ADD_ONE").unwrap();
+        assert!(add_one.temporary && !add_one.aggregate);
+        assert_eq!(add_one.language, None);
+
+        assert!(udfs.is_aggregate("ds.my_agg"));
+        assert!(!udfs.is_aggregate("add_one"));
+        assert!(!udfs.is_aggregate("unknown"));
+    }
+
+    #[test]
+    fn reads_language_and_determinism() {
+        let udfs = catalog(
+            "CREATE TEMP FUNCTION rnd(x FLOAT64) RETURNS FLOAT64 NOT DETERMINISTIC \
+             LANGUAGE js AS r\"\"\"return Math.random() * x;\"\"\";\n\
+             CREATE TEMP FUNCTION twice(x FLOAT64) RETURNS FLOAT64 DETERMINISTIC \
+             LANGUAGE js AS r\"\"\"return 2 * x;\"\"\";\n\
+             CREATE TEMP FUNCTION plain(x INT64) AS (x);",
+        );
+        assert!(udfs.is_javascript("rnd"));
+        assert_eq!(udfs.is_deterministic("rnd"), Some(false));
+        assert_eq!(udfs.is_deterministic("twice"), Some(true));
+        assert_eq!(udfs.is_deterministic("plain"), None);
+        assert!(!udfs.is_javascript("plain"));
+    }
+
+    #[test]
+    fn qualified_names_match_ignoring_backticks_and_leading_qualifiers() {
+        let udfs = catalog("CREATE AGGREGATE FUNCTION `ds.my_agg`(x INT64) AS (SUM(x));");
+        assert!(udfs.is_aggregate("ds.my_agg"));
+        assert!(udfs.is_aggregate("`proj.ds.my_agg`"));
+        assert!(!udfs.is_aggregate("my_agg"));
+        assert!(!udfs.is_aggregate("other_ds.my_agg"));
+    }
+
+    #[test]
+    fn calls_resolve_to_the_most_qualified_matching_declaration() {
+        let udfs = catalog(
+            "CREATE TEMP FUNCTION f(x INT64) AS (x);\n\
+             CREATE AGGREGATE FUNCTION ds.f(x INT64) AS (SUM(x));\n\
+             CREATE FUNCTION ds.json_extract(x STRING) AS (x);",
+        );
+        assert!(!udfs.is_aggregate("f"));
+        assert!(udfs.is_aggregate("ds.f"));
+        assert!(udfs.is_aggregate("proj.ds.f"));
+        assert!(udfs.get("json_extract").is_none());
+        assert!(udfs.get("proj.ds.json_extract").is_some());
+    }
+
+    #[test]
+    fn other_statements_are_ignored() {
+        let udfs = catalog("SELECT 1;\nCREATE TABLE t (x INT64);");
+        assert!(udfs.is_empty());
+    }
+}